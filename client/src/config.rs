@@ -1,3 +1,4 @@
+use core::net::Login;
 use serde::Deserialize;
 use std::net::SocketAddr;
 
@@ -9,9 +10,19 @@ struct Tcp {
     port: u16,
 }
 
+#[derive(Default, Deserialize)]
+struct Account {
+    name: Option<String>,
+    pass: Option<String>,
+    #[serde(default)]
+    register: bool,
+}
+
 #[derive(Deserialize)]
 pub struct Config {
     tcp: Tcp,
+    #[serde(default)]
+    account: Account,
 }
 
 impl Config {
@@ -20,9 +31,49 @@ impl Config {
         toml::from_str(&content).expect("parse config")
     }
 
+    /// Overrides the config with command line arguments.
+    ///
+    /// Supported arguments:
+    ///     --name <name>
+    ///     --pass <pass>
+    ///     --register
+    pub fn with_args<A>(mut self, args: A) -> Result<Self, String>
+    where
+        A: IntoIterator<Item = String>,
+    {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--name" => self.account.name = Some(args.next().ok_or("expected name")?),
+                "--pass" => self.account.pass = Some(args.next().ok_or("expected pass")?),
+                "--register" => self.account.register = true,
+                _ => return Err(format!("unknown argument {arg}")),
+            }
+        }
+
+        Ok(self)
+    }
+
     pub fn socket_addr(&self) -> SocketAddr {
         format!("{}:{}", self.tcp.host, self.tcp.port)
             .parse()
             .unwrap()
     }
+
+    /// Returns the credentials and whether to register a new account.
+    pub fn login(&self) -> Option<(Login, bool)> {
+        let Account {
+            name,
+            pass,
+            register,
+        } = &self.account;
+
+        Some((
+            Login {
+                name: name.clone()?,
+                pass: pass.clone()?,
+            },
+            *register,
+        ))
+    }
 }
@@ -2,25 +2,61 @@ mod config;
 mod window;
 
 use self::{config::Config, window::Window};
-use core::net::{Login, Packed};
+use core::{
+    net::{self, ClientMessage, LoginResult, ServerMessage, PROTOCOL_VERSION},
+    path::Action,
+};
 use eng::{Control, Game, Render};
 use glutin::event::{ElementState, MouseButton, VirtualKeyCode};
 use std::{
-    io::{self, Write},
+    fmt,
     net::TcpStream,
+    path::Path,
+    sync::mpsc::{self, Receiver},
+    thread,
     time::Duration,
 };
 
 pub struct App {
     game: Game,
     render: Render,
+    stream: TcpStream,
+    messages: Receiver<ServerMessage>,
 }
 
 impl App {
     fn draw_frame(&mut self, delta: f32) {
+        self.receive();
         self.game.draw(&mut self.render, delta)
     }
 
+    /// Applies the messages received from the server.
+    fn receive(&mut self) {
+        while let Ok(message) = self.messages.try_recv() {
+            match message {
+                ServerMessage::Chunk { cl, slabs } => {
+                    self.game.load_chunk(&self.render, cl, &slabs)
+                }
+                ServerMessage::Unload { cl } => self.game.unload_chunk(cl),
+                ServerMessage::TilePlaced { pn, tile, variant } => {
+                    self.game.place_tile(&self.render, pn, tile, variant)
+                }
+                ServerMessage::Spawned { pn } | ServerMessage::Corrected { pn } => {
+                    self.game.place_player(pn)
+                }
+                ServerMessage::Chat { from, text } => println!("{from}: {text}"),
+                message => println!("Unhandled message: {message:?}"),
+            }
+        }
+    }
+
+    /// Sends the action of the player, the server streams the chunks around it.
+    fn act(&mut self, action: Action) {
+        if let Err(err) = net::write(&mut self.stream, &ClientMessage::Act { action }) {
+            println!("Send failed: {err}");
+        }
+    }
+
     fn resize(&mut self, size: (u32, u32)) {
         self.render.resize(size, 1);
         self.game.resize(size);
@@ -32,6 +68,7 @@ impl App {
         }
 
         let control = match key {
+            VirtualKeyCode::F12 => return self.export(),
             VirtualKeyCode::W => Control::Forward,
             VirtualKeyCode::A => Control::Left,
             VirtualKeyCode::S => Control::Back,
@@ -39,41 +76,131 @@ impl App {
             _ => return,
         };
 
-        self.game.input(control)
+        if let Some(action) = self.game.input(control) {
+            self.act(action);
+        }
+    }
+
+    /// Exports the cluster the camera looks at to the current directory.
+    fn export(&mut self) {
+        let cl = match self.game.focus() {
+            Some(pn) => pn.cluster_point(),
+            None => return,
+        };
+
+        match self.game.export(cl, Path::new(".")) {
+            Ok(()) => println!("Exported {cl}"),
+            Err(err) => println!("Export failed: {err}"),
+        }
     }
 
     fn mouse_move(&mut self, (x, y): (f32, f32)) {
-        self.game.input(Control::Look(x, y))
+        self.game.input(Control::Look(x, y));
     }
 
     fn mouse(&mut self, _: MouseButton, _: ElementState) {}
 
     fn scroll(&mut self, (x, y): (f32, f32)) {
-        self.game.input(Control::Scroll(x, y))
+        self.game.input(Control::Scroll(x, y));
+    }
+}
+
+#[derive(Debug)]
+enum LoginError {
+    NoCredentials,
+    Net(net::Error),
+    Unexpected(ServerMessage),
+    Refused(LoginResult),
+}
+
+impl fmt::Display for LoginError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoCredentials => write!(f, "no credentials, set the name and the pass"),
+            Self::Net(err) => write!(f, "net error {err}"),
+            Self::Unexpected(message) => write!(f, "unexpected message {message:?}"),
+            Self::Refused(result) => write!(f, "refused with {result:?}"),
+        }
     }
 }
 
-fn login(login: Login) -> Result<(), io::Error> {
-    let config = Config::load();
+impl From<net::Error> for LoginError {
+    fn from(err: net::Error) -> Self {
+        Self::Net(err)
+    }
+}
+
+fn login(config: &Config) -> Result<TcpStream, LoginError> {
+    let (login, register) = config.login().ok_or(LoginError::NoCredentials)?;
     let addr = config.socket_addr();
     println!("Wait for connection ..");
-    let mut stream = TcpStream::connect_timeout(&addr, Duration::from_secs(30))?;
-    let packed = Packed::new(&login).unwrap();
-    stream.write_all(packed.bytes())?;
-    stream.flush()
+    let mut stream =
+        TcpStream::connect_timeout(&addr, Duration::from_secs(30)).map_err(net::Error::Io)?;
+    let handshake = ClientMessage::Handshake {
+        version: PROTOCOL_VERSION,
+    };
+    net::write(&mut stream, &handshake)?;
+    match net::read(&mut stream)? {
+        ServerMessage::Handshake { version } => println!("Server protocol version: {version}"),
+        message => return Err(LoginError::Unexpected(message)),
+    }
+
+    let message = match register {
+        false => ClientMessage::Login(login),
+        true => ClientMessage::Register(login),
+    };
+
+    net::write(&mut stream, &message)?;
+    match net::read(&mut stream)? {
+        ServerMessage::LoginResult(LoginResult::Accepted) => println!("Logged in"),
+        ServerMessage::LoginResult(result) => return Err(LoginError::Refused(result)),
+        message => return Err(LoginError::Unexpected(message)),
+    }
+
+    Ok(stream)
+}
+
+/// Reads messages from the server in a separate thread.
+fn read_messages(mut stream: TcpStream) -> Receiver<ServerMessage> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || loop {
+        match net::read(&mut stream) {
+            Ok(message) => {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+            Err(err) => {
+                println!("Connection closed: {err}");
+                break;
+            }
+        }
+    });
+
+    receiver
 }
 
 fn main() {
-    login(Login {
-        name: "nano".into(),
-        pass: "123".into(),
-    })
-    .expect("login");
+    let config = Config::load()
+        .with_args(std::env::args().skip(1))
+        .expect("parse arguments");
+
+    let stream = match login(&config) {
+        Ok(stream) => stream,
+        Err(err) => {
+            println!("Login failed: {err}");
+            return;
+        }
+    };
+
+    let messages = read_messages(stream.try_clone().expect("clone stream"));
 
     let (window, render) = Window::new("hui 0.0.1");
     let app = App {
         game: Game::new(&render),
         render,
+        stream,
+        messages,
     };
 
     window.run(app, 30, (800, 600))
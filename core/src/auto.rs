@@ -0,0 +1,206 @@
+use crate::{map::Map, path::Pass, prelude::*, tile::Variant};
+use serde::Deserialize;
+use std::collections::HashSet;
+
+/// Conditions on the neighbours of a tile for the variant to be picked.
+///
+/// Horizontal sides are checked next to the base of the tile,
+/// `u` above the top of the tile and `d` below the base.
+#[derive(Copy, Clone, Debug, Default, Deserialize)]
+pub struct Rule {
+    #[serde(default)]
+    pub solid: Sides,
+    #[serde(default)]
+    pub open: Sides,
+}
+
+impl Rule {
+    fn len(self) -> usize {
+        self.solid.len() + self.open.len()
+    }
+
+    fn matches<P>(self, passes: &P, pn: Point, height: Height) -> bool
+    where
+        P: Passes,
+    {
+        let Self {
+            mut solid,
+            mut open,
+        } = self;
+        solid.all(|side| is_solid(passes, pn, height, side))
+            && open.all(|side| !is_solid(passes, pn, height, side))
+    }
+}
+
+/// The passes of the placed tiles.
+pub trait Passes {
+    fn pass(&self, pn: Point) -> Option<Pass>;
+}
+
+impl<T> Passes for Map<T>
+where
+    T: AsRef<Chunk<Pass>>,
+{
+    fn pass(&self, pn: Point) -> Option<Pass> {
+        self.get(pn).copied()
+    }
+}
+
+/// Picks the variant of the tile placed at the point.
+///
+/// The matching rule with the most conditions wins, the first variant
+/// is picked if nothing matches. Returns `None` if no variant has a rule,
+/// then the variant is chosen by hand.
+pub fn pick<P>(variants: &[Variant], pn: Point, height: Height, passes: &P) -> Option<VariantIndex>
+where
+    P: Passes,
+{
+    if variants.iter().all(|variant| variant.auto.is_none()) {
+        return None;
+    }
+
+    let mut best: Option<(VariantIndex, usize)> = None;
+    for variant in variants {
+        let rule = match variant.auto {
+            Some(rule) => rule,
+            None => continue,
+        };
+
+        let better = best.is_none_or(|(_, len)| rule.len() > len);
+        if better && rule.matches(passes, pn, height) {
+            best = Some((variant.idx, rule.len()));
+        }
+    }
+
+    Some(best.map_or(VariantIndex(0), |(idx, _)| idx))
+}
+
+/// Returns the points of the tiles whose rules can depend on the column.
+///
+/// The points can be anywhere in the tiles, not only at their bases.
+pub fn neighbours(pn: Point, height: Height) -> HashSet<Point> {
+    let mut points = HashSet::default();
+    points.insert(pn.to(Side::Down));
+    let mut level = pn;
+    for _ in 0..height.get() {
+        for side in [Side::Left, Side::Right, Side::Forth, Side::Back] {
+            points.insert(level.to(side));
+        }
+
+        level = level.to(Side::Up);
+    }
+
+    points.insert(level);
+    points
+}
+
+/// Checks if the neighbour on the side blocks the tile.
+///
+/// The slope ascending towards the tile doesn't block it.
+fn is_solid<P>(passes: &P, pn: Point, height: Height, side: Side) -> bool
+where
+    P: Passes,
+{
+    let neighbour = match side {
+        Side::Up => (0..height.get()).fold(pn, |pn, _| pn.to(Side::Up)),
+        _ => pn.to(side),
+    };
+
+    let pass = match passes.pass(neighbour) {
+        Some(pass) => pass,
+        None => return false,
+    };
+
+    match Rotation::from_side(side.opposite()) {
+        Some(rotation) if pass.ascent_from(rotation) => false,
+        _ => pass.is_solid(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Solid(HashSet<Point>);
+
+    impl Passes for Solid {
+        fn pass(&self, pn: Point) -> Option<Pass> {
+            self.0.get(&pn).map(|_| Pass::solid())
+        }
+    }
+
+    fn point(x: i64, y: i64, z: i64) -> Point {
+        Point::from_absolute(x, y, z).unwrap()
+    }
+
+    fn variant(idx: u8, auto: Option<(&str, &str)>) -> Variant {
+        Variant {
+            idx: VariantIndex(idx),
+            name: "test".into(),
+            rotation: Rotation::Q0,
+            passes: vec![Pass::solid()],
+            auto: auto.map(|(solid, open)| Rule {
+                solid: solid.parse().unwrap(),
+                open: open.parse().unwrap(),
+            }),
+        }
+    }
+
+    #[test]
+    fn pick_variant() {
+        let height = Height::new(1).unwrap();
+        let variants = [
+            variant(0, Some(("", ""))),
+            variant(1, Some(("b", "fu"))),
+            variant(2, Some(("l", "ru"))),
+            variant(3, None),
+        ];
+
+        let pn = point(0, 0, 0);
+        let mut solid = Solid(HashSet::default());
+        assert_eq!(pick(&variants, pn, height, &solid), Some(VariantIndex(0)));
+
+        solid.0.insert(pn.to(Side::Back));
+        assert_eq!(pick(&variants, pn, height, &solid), Some(VariantIndex(1)));
+
+        // Closed above
+        solid.0.insert(pn.to(Side::Up));
+        assert_eq!(pick(&variants, pn, height, &solid), Some(VariantIndex(0)));
+
+        solid.0.clear();
+        solid.0.insert(pn.to(Side::Left));
+        assert_eq!(pick(&variants, pn, height, &solid), Some(VariantIndex(2)));
+
+        assert_eq!(pick(&variants[3..], pn, height, &solid), None);
+    }
+
+    #[test]
+    fn ascent() {
+        struct Slope(Point);
+
+        impl Passes for Slope {
+            fn pass(&self, pn: Point) -> Option<Pass> {
+                (pn == self.0).then(|| Pass::ascent([Rotation::Q0]))
+            }
+        }
+
+        // The slope is ascended from the tile, so it doesn't block it
+        let height = Height::new(1).unwrap();
+        let pn = point(0, 0, 0);
+        let slope = Slope(pn.to(Side::Back));
+        assert!(!is_solid(&slope, pn, height, Side::Back));
+        let slope = Slope(pn.to(Side::Forth));
+        assert!(is_solid(&slope, pn, height, Side::Forth));
+    }
+
+    #[test]
+    fn neighbour_points() {
+        let pn = point(0, 0, 0);
+        let points = neighbours(pn, Height::new(2).unwrap());
+        assert_eq!(points.len(), 10);
+        assert!(points.contains(&point(0, -1, 0)));
+        assert!(points.contains(&point(0, 2, 0)));
+        assert!(points.contains(&point(1, 1, 0)));
+        assert!(!points.contains(&pn));
+    }
+}
@@ -0,0 +1,280 @@
+use crate::chunk::Chunk;
+use std::{collections::HashMap, error, fmt, hash::Hash};
+
+/// The chunk format.
+///
+/// All numbers are little endian.
+///
+/// magic    [u8; 4]
+/// version  u8
+/// palette  u16 len, then len * u32 values
+/// runs     per column along Y in the `[z][x]` order:
+///          u8 len, then u8 index (u16 if the palette is longer than 256)
+/// checksum u32 CRC-32 of all previous bytes
+const MAGIC: [u8; 4] = *b"ACHK";
+
+/// The format version.
+///
+/// Must be increased on every incompatible change of the format.
+pub const VERSION: u8 = 1;
+
+const HEADER_LEN: usize = MAGIC.len() + 1;
+const CHECKSUM_LEN: usize = 4;
+
+/// A value that can be stored in the chunk format.
+pub trait Encode: Copy + Eq + Hash {
+    fn encode(self) -> u32;
+
+    fn decode(raw: u32) -> Option<Self>;
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Error {
+    Len,
+    Magic,
+    Version(u8),
+    Checksum,
+    Palette,
+    Value(u32),
+    Run,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Len => write!(f, "unexpected end of data"),
+            Self::Magic => write!(f, "wrong magic"),
+            Self::Version(version) => write!(f, "unsupported version {version}"),
+            Self::Checksum => write!(f, "checksum mismatch"),
+            Self::Palette => write!(f, "wrong palette"),
+            Self::Value(raw) => write!(f, "wrong value {raw:#x}"),
+            Self::Run => write!(f, "wrong run"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// Encodes the chunk.
+pub fn encode<T>(chunk: &Chunk<T>) -> Vec<u8>
+where
+    T: Encode,
+{
+    let mut palette = Vec::new();
+    let mut indices = HashMap::new();
+    for column in chunk.columns() {
+        for &val in column {
+            indices.entry(val).or_insert_with(|| {
+                palette.push(val);
+                palette.len() - 1
+            });
+        }
+    }
+
+    assert!(palette.len() <= u16::MAX as usize);
+    let wide = palette.len() > u8::MAX as usize + 1;
+
+    let mut buf = Vec::with_capacity(64);
+    buf.extend(MAGIC);
+    buf.push(VERSION);
+    buf.extend((palette.len() as u16).to_le_bytes());
+    for val in &palette {
+        buf.extend(val.encode().to_le_bytes());
+    }
+
+    for column in chunk.columns() {
+        let mut rest = &column[..];
+        while let Some(&val) = rest.first() {
+            let len = rest.iter().take_while(|&&other| other == val).count();
+            buf.push(len as u8);
+            let idx = indices[&val];
+            if wide {
+                buf.extend((idx as u16).to_le_bytes());
+            } else {
+                buf.push(idx as u8);
+            }
+
+            rest = &rest[len..];
+        }
+    }
+
+    let checksum = crc32fast::hash(&buf);
+    buf.extend(checksum.to_le_bytes());
+    buf
+}
+
+/// Decodes the chunk.
+pub fn decode<T>(bytes: &[u8]) -> Result<Chunk<T>, Error>
+where
+    T: Encode,
+{
+    if bytes.len() < HEADER_LEN + CHECKSUM_LEN {
+        return Err(Error::Len);
+    }
+
+    let (data, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+    if data[..MAGIC.len()] != MAGIC {
+        return Err(Error::Magic);
+    }
+
+    let version = data[MAGIC.len()];
+    if version != VERSION {
+        return Err(Error::Version(version));
+    }
+
+    if crc32fast::hash(data) != u32::from_le_bytes(checksum.try_into().unwrap()) {
+        return Err(Error::Checksum);
+    }
+
+    let mut reader = Reader(&data[HEADER_LEN..]);
+    let palette_len = reader.u16()? as usize;
+    if palette_len == 0 {
+        return Err(Error::Palette);
+    }
+
+    let palette = (0..palette_len)
+        .map(|_| {
+            let raw = reader.u32()?;
+            T::decode(raw).ok_or(Error::Value(raw))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let wide = palette_len > u8::MAX as usize + 1;
+    let mut chunk = Chunk::filled(palette[0]);
+    for column in chunk.columns_mut() {
+        let mut rest = &mut column[..];
+        while !rest.is_empty() {
+            let len = reader.u8()? as usize;
+            let idx = if wide {
+                reader.u16()?
+            } else {
+                reader.u8()? as u16
+            };
+            let val = *palette.get(idx as usize).ok_or(Error::Palette)?;
+            if len == 0 || len > rest.len() {
+                return Err(Error::Run);
+            }
+
+            let (run, tail) = rest.split_at_mut(len);
+            run.fill(val);
+            rest = tail;
+        }
+    }
+
+    if !reader.0.is_empty() {
+        return Err(Error::Len);
+    }
+
+    Ok(chunk)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        if self.0.len() < N {
+            return Err(Error::Len);
+        }
+
+        let (head, tail) = self.0.split_at(N);
+        self.0 = tail;
+        Ok(head.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        self.take().map(u8::from_le_bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        self.take().map(u32::from_le_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk::SIDE, point::ChunkPoints};
+
+    impl Encode for u32 {
+        fn encode(self) -> u32 {
+            self
+        }
+
+        fn decode(raw: u32) -> Option<Self> {
+            Some(raw)
+        }
+    }
+
+    fn assert_eq_chunks(a: &Chunk<u32>, b: &Chunk<u32>) {
+        for ch in ChunkPoints::new() {
+            assert_eq!(a.get(ch), b.get(ch), "{ch}");
+        }
+    }
+
+    #[test]
+    fn empty() {
+        let chunk = Chunk::filled(0);
+        let bytes = encode(&chunk);
+
+        // One run per column
+        assert_eq!(
+            bytes.len(),
+            HEADER_LEN + 2 + 4 + SIDE * SIDE * 2 + CHECKSUM_LEN
+        );
+        assert_eq_chunks(&decode(&bytes).unwrap(), &chunk);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut chunk = Chunk::filled(0);
+        for (i, ch) in ChunkPoints::new().enumerate() {
+            *chunk.get_mut(ch) = (i as u32 / 3) % 7 * 0x10001;
+        }
+
+        let bytes = encode(&chunk);
+        assert_eq_chunks(&decode(&bytes).unwrap(), &chunk);
+    }
+
+    #[test]
+    fn wide_palette() {
+        let mut chunk = Chunk::filled(0);
+        for (i, ch) in ChunkPoints::new().enumerate() {
+            *chunk.get_mut(ch) = i as u32 % 300;
+        }
+
+        let bytes = encode(&chunk);
+        assert_eq_chunks(&decode(&bytes).unwrap(), &chunk);
+    }
+
+    #[test]
+    fn corrupted() {
+        let mut chunk = Chunk::filled(0);
+        *chunk.get_mut(ChunkPoints::new().nth(40).unwrap()) = 1;
+        let bytes = encode(&chunk);
+
+        let mut wrong = bytes.clone();
+        wrong[HEADER_LEN + 4] ^= 1;
+        assert_eq!(decode::<u32>(&wrong).err(), Some(Error::Checksum));
+
+        let mut wrong = bytes.clone();
+        wrong[0] = b'B';
+        assert_eq!(decode::<u32>(&wrong).err(), Some(Error::Magic));
+
+        let mut wrong = bytes.clone();
+        wrong[MAGIC.len()] = VERSION + 1;
+        assert_eq!(
+            decode::<u32>(&wrong).err(),
+            Some(Error::Version(VERSION + 1))
+        );
+
+        assert_eq!(decode::<u32>(&bytes[..4]).err(), Some(Error::Len));
+        assert_eq!(
+            decode::<u32>(&bytes[..bytes.len() - 1]).err(),
+            Some(Error::Checksum)
+        );
+    }
+}
@@ -0,0 +1,8 @@
+mod format;
+mod this;
+
+pub(crate) use self::this::{HEIGHT, SIDE};
+pub use self::{
+    format::{decode, encode, Encode, Error, VERSION},
+    this::Chunk,
+};
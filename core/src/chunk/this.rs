@@ -3,6 +3,7 @@ use crate::point::ChunkPoint;
 pub(crate) const SIDE: usize = 16;
 pub(crate) const HEIGHT: usize = SIDE * 2;
 
+#[derive(Clone)]
 pub struct Chunk<T>([[[T; HEIGHT]; SIDE]; SIDE]);
 
 impl<T> Chunk<T> {
@@ -37,6 +38,15 @@ impl<T> Chunk<T> {
         unsafe { self.slice_unchecked_mut(x as usize, y as usize, z as usize, u as usize) }
     }
 
+    /// Iterates over the columns along Y in the memory order.
+    pub(crate) fn columns(&self) -> impl Iterator<Item = &[T; HEIGHT]> {
+        self.0.iter().flatten()
+    }
+
+    pub(crate) fn columns_mut(&mut self) -> impl Iterator<Item = &mut [T; HEIGHT]> {
+        self.0.iter_mut().flatten()
+    }
+
     unsafe fn get_unchecked(&self, x: usize, y: usize, z: usize) -> &T {
         debug_assert!(x < SIDE);
         debug_assert!(y < HEIGHT);
@@ -1,4 +1,5 @@
-mod chunk;
+pub mod auto;
+pub mod chunk;
 mod height;
 mod load;
 pub mod map;
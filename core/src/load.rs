@@ -1,6 +1,6 @@
-use crate::{height::Height, path::Pass, prelude::Rotation, tile::TileList};
+use crate::{auto::Rule, height::Height, path::Pass, prelude::Rotation, tile::TileList};
 use serde::Deserialize;
-use std::io;
+use std::{error, fmt, io};
 
 const PATH: &str = "./assets/tiles.json";
 
@@ -11,6 +11,18 @@ pub enum Error {
     Serde(serde_json::Error),
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::PassesLen(len) => write!(f, "wrong passes len {len}"),
+            Self::Io(err) => write!(f, "io error {err}"),
+            Self::Serde(err) => write!(f, "serde error {err}"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Self {
         Self::Io(err)
@@ -29,19 +41,28 @@ struct RawVariant {
     #[serde(default)]
     rotation: Rotation,
     passes: Vec<Pass>,
+    #[serde(default)]
+    auto: Option<Rule>,
 }
 
 #[derive(Deserialize)]
 struct RawTile<'a> {
     name: &'a str,
     height: Height,
+    #[serde(default = "base")]
+    behaviour: &'a str,
     variants: Vec<RawVariant>,
 }
 
+fn base() -> &'static str {
+    "base"
+}
+
 fn load(tile: RawTile, list: &mut TileList) -> Result<(), Error> {
     let RawTile {
         name,
         height,
+        behaviour,
         variants,
     } = tile;
 
@@ -55,12 +76,14 @@ fn load(tile: RawTile, list: &mut TileList) -> Result<(), Error> {
     list.add(
         name,
         height,
+        behaviour,
         variants.into_iter().map(
             |RawVariant {
                  name,
                  rotation,
                  passes,
-             }| (name, rotation, passes),
+                 auto,
+             }| (name, rotation, passes, auto),
         ),
     );
     Ok(())
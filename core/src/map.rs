@@ -32,7 +32,7 @@ impl<T> Map<T> {
         chunk.as_mut().get_mut(ch)
     }
 
-    pub fn column<S>(&self, pn: Point, height: Height) -> Option<Column<S>>
+    pub fn column<S>(&self, pn: Point, height: Height) -> Option<Column<'_, S>>
     where
         T: AsRef<Chunk<S>>,
     {
@@ -55,7 +55,7 @@ impl<T> Map<T> {
         }
     }
 
-    pub fn column_mut<S>(&mut self, pn: Point, height: Height) -> ColumnMut<S>
+    pub fn column_mut<S>(&mut self, pn: Point, height: Height) -> ColumnMut<'_, S>
     where
         T: AsMut<Chunk<S>> + Default,
     {
@@ -70,8 +70,8 @@ impl<T> Map<T> {
                 let hh = u - HEIGHT as u8;
                 let lh = height - hh;
                 let up = cl.to(Side::Up);
-                self.chunks.entry(cl).or_insert_with(T::default);
-                self.chunks.entry(up).or_insert_with(T::default);
+                self.chunks.entry(cl).or_default();
+                self.chunks.entry(up).or_default();
                 let lo = self.chunks.get_mut(&cl).unwrap() as *mut T;
                 let hi = self.chunks.get_mut(&up).unwrap() as *mut T;
                 let (x, _, z) = ch.axes();
@@ -93,10 +93,14 @@ impl<T> Map<T> {
     where
         T: Default,
     {
-        self.chunks.entry(cl).or_insert_with(T::default)
+        self.chunks.entry(cl).or_default()
     }
 
-    pub fn vicinity(&self, cl: ClusterPoint) -> Option<Vicinity<T>> {
+    pub fn remove(&mut self, cl: ClusterPoint) -> Option<T> {
+        self.chunks.remove(&cl)
+    }
+
+    pub fn vicinity(&self, cl: ClusterPoint) -> Option<Vicinity<'_, T>> {
         Some(Vicinity {
             chunks: [None; 10],
             center: self.chunk(cl)?,
@@ -105,7 +109,7 @@ impl<T> Map<T> {
         })
     }
 
-    pub fn iter<S>(&self, cl: ClusterPoint) -> Option<Iter<S>>
+    pub fn iter<S>(&self, cl: ClusterPoint) -> Option<Iter<'_, S>>
     where
         T: AsRef<Chunk<S>>,
     {
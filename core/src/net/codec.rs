@@ -0,0 +1,127 @@
+use crate::net::pack::{Error, Packed, Unpacked, LEN_BYTES};
+use serde::{de::DeserializeOwned, Serialize};
+use std::io::{Read, Write};
+
+/// Writes a length-prefixed message to the `writer`.
+pub fn write<W, T>(writer: &mut W, val: &T) -> Result<(), Error>
+where
+    W: Write,
+    T: Serialize,
+{
+    let packed = Packed::new(val)?;
+    writer.write_all(packed.bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads a length-prefixed message from the `reader`.
+pub fn read<R, T>(reader: &mut R) -> Result<T, Error>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    let mut len = [0; LEN_BYTES];
+    reader.read_exact(&mut len)?;
+    let mut unpacked = Unpacked::new(u32::from_be_bytes(len))?;
+    reader.read_exact(unpacked.bytes())?;
+    unpacked.to()
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_codec {
+    use super::*;
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+    /// Writes a length-prefixed message to the async `writer`.
+    pub async fn write_async<W, T>(writer: &mut W, val: &T) -> Result<(), Error>
+    where
+        W: AsyncWrite + Unpin,
+        T: Serialize,
+    {
+        let packed = Packed::new(val)?;
+        writer.write_all(packed.bytes()).await?;
+        writer.flush().await?;
+        Ok(())
+    }
+
+    /// Reads a length-prefixed message from the async `reader`.
+    pub async fn read_async<R, T>(reader: &mut R) -> Result<T, Error>
+    where
+        R: AsyncRead + Unpin,
+        T: DeserializeOwned,
+    {
+        let len = reader.read_u32().await?;
+        let mut unpacked = Unpacked::new(len)?;
+        reader.read_exact(unpacked.bytes()).await?;
+        unpacked.to()
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub use self::tokio_codec::{read_async, write_async};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        net::{ClientMessage, Login, ServerMessage, PROTOCOL_VERSION},
+        path::Action,
+        prelude::*,
+    };
+    use std::io::{Cursor, ErrorKind};
+
+    #[test]
+    fn stream() {
+        let messages = [
+            ClientMessage::Handshake {
+                version: PROTOCOL_VERSION,
+            },
+            ClientMessage::Login(Login {
+                name: "nano".into(),
+                pass: "123".into(),
+            }),
+            ClientMessage::Act {
+                action: Action::Step(Rotation::Q1),
+            },
+            ClientMessage::Act {
+                action: Action::Fly(Side::Up),
+            },
+            ClientMessage::Chat("hi".into()),
+        ];
+
+        let mut buf = Vec::new();
+        for message in &messages {
+            write(&mut buf, message).unwrap();
+        }
+
+        let mut reader = Cursor::new(buf);
+        for message in messages {
+            let read: ClientMessage = read(&mut reader).unwrap();
+            assert_eq!(read, message);
+        }
+
+        let err = read::<_, ClientMessage>(&mut reader).unwrap_err();
+        assert!(matches!(err, Error::Io(err) if err.kind() == ErrorKind::UnexpectedEof));
+    }
+
+    #[test]
+    fn limit() {
+        let message = ServerMessage::Chat {
+            from: "nano".into(),
+            text: "a".repeat(1 << 18),
+        };
+
+        let mut buf = Vec::new();
+        assert!(matches!(
+            write(&mut buf, &message),
+            Err(Error::LimitReached)
+        ));
+        assert!(buf.is_empty());
+
+        let mut reader = Cursor::new(u32::MAX.to_be_bytes());
+        assert!(matches!(
+            read::<_, ServerMessage>(&mut reader),
+            Err(Error::LimitReached)
+        ));
+    }
+}
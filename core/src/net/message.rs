@@ -0,0 +1,159 @@
+use crate::{chunk::Encode, path::Action, point::Point, prelude::*};
+use serde::{Deserialize, Serialize};
+
+/// The protocol version.
+///
+/// Must be increased on every incompatible change of the messages.
+pub const PROTOCOL_VERSION: u16 = 5;
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Login {
+    pub name: String,
+    pub pass: String,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum LoginResult {
+    Accepted,
+    /// Wrong name or password.
+    Rejected,
+    /// Too many failed attempts, try again in `secs`.
+    Locked {
+        secs: u64,
+    },
+    AlreadyOnline,
+    NameTaken,
+    InvalidName,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Disconnect {
+    Quit,
+    VersionMismatch { server: u16 },
+    ProtocolError,
+    Timeout,
+    Shutdown,
+}
+
+/// A slab of the chunk as the client sees it.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum TileSlab {
+    Empty,
+    Base(TileIndex, VariantIndex),
+    /// The level of the trunk above the base, starting from 0.
+    Trunk(u8),
+}
+
+impl Encode for TileSlab {
+    fn encode(self) -> u32 {
+        match self {
+            Self::Empty => 0,
+            Self::Base(tile, variant) => 1 << 30 | (tile.get() as u32) << 8 | variant.get() as u32,
+            Self::Trunk(level) => 2 << 30 | level as u32,
+        }
+    }
+
+    fn decode(raw: u32) -> Option<Self> {
+        match (raw >> 30, raw & !(0b11 << 30)) {
+            (0, 0) => Some(Self::Empty),
+            (1, rest) if rest >> 24 == 0 => Some(Self::Base(
+                TileIndex::new((rest >> 8) as u16)?,
+                VariantIndex(rest as u8),
+            )),
+            (2, rest) if rest <= u8::MAX as u32 => Some(Self::Trunk(rest as u8)),
+            _ => None,
+        }
+    }
+}
+
+impl From<(TileIndex, VariantIndex)> for TileSlab {
+    fn from((tile, variant): (TileIndex, VariantIndex)) -> Self {
+        Self::Base(tile, variant)
+    }
+}
+
+/// A message from the client to the server.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum ClientMessage {
+    Handshake {
+        version: u16,
+    },
+    Login(Login),
+    Register(Login),
+    PlaceTile {
+        pn: Point,
+        tile: TileIndex,
+    },
+    /// Moves the player by the `action`, the server validates it.
+    Act {
+        action: Action,
+    },
+    Chat(String),
+    Disconnect(Disconnect),
+}
+
+/// A message from the server to the client.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum ServerMessage {
+    Handshake {
+        version: u16,
+    },
+    LoginResult(LoginResult),
+    /// The `TileSlab`s of the cluster in the chunk format.
+    Chunk {
+        cl: ClusterPoint,
+        slabs: Vec<u8>,
+    },
+    Unload {
+        cl: ClusterPoint,
+    },
+    TilePlaced {
+        pn: Point,
+        tile: TileIndex,
+        variant: VariantIndex,
+    },
+    EntityMoved {
+        entity: u32,
+        pn: Point,
+    },
+    /// The player appears at `pn` after the login.
+    Spawned {
+        pn: Point,
+    },
+    /// The last action of the player was rejected, it's returned to `pn`.
+    Corrected {
+        pn: Point,
+    },
+    Chat {
+        from: String,
+        text: String,
+    },
+    Disconnect(Disconnect),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk, point::ChunkPoints};
+
+    #[test]
+    fn encode() {
+        let mut slabs = Chunk::filled(TileSlab::Empty);
+        for (i, ch) in ChunkPoints::new().enumerate() {
+            *slabs.get_mut(ch) = match i % 5 {
+                0 | 1 => TileSlab::Empty,
+                2 => TileSlab::Base(TileIndex::new(i as u16).unwrap(), VariantIndex(i as u8)),
+                _ => TileSlab::Trunk(ch.y()),
+            };
+        }
+
+        let bytes = chunk::encode(&slabs);
+        let decoded: Chunk<TileSlab> = chunk::decode(&bytes).unwrap();
+        for ch in ChunkPoints::new() {
+            assert_eq!(decoded.get(ch), slabs.get(ch));
+        }
+
+        assert!(TileSlab::decode(1 << 30).is_none());
+        assert!(TileSlab::decode(3 << 30).is_none());
+    }
+}
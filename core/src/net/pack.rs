@@ -0,0 +1,85 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{error, fmt, io};
+
+#[derive(Debug)]
+pub enum Error {
+    Len,
+    LimitReached,
+    Io(io::Error),
+    Bincode(bincode::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Len => write!(f, "wrong len"),
+            Self::LimitReached => write!(f, "limit reached"),
+            Self::Io(err) => write!(f, "io error {err}"),
+            Self::Bincode(err) => write!(f, "bincode error {err}"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<bincode::Error> for Error {
+    fn from(err: bincode::Error) -> Self {
+        Self::Bincode(err)
+    }
+}
+
+pub(crate) const LEN_BYTES: usize = std::mem::size_of::<u32>();
+pub(crate) const MAX_LEN: u32 = 1 << 17;
+
+pub struct Packed(Vec<u8>);
+
+impl Packed {
+    pub fn new<T>(val: &T) -> Result<Self, Error>
+    where
+        T: Serialize,
+    {
+        let mut buf = Vec::with_capacity(32);
+        buf.extend([0, 0, 0, 0]);
+        bincode::serialize_into(&mut buf, val)?;
+        let len = (buf.len() - LEN_BYTES) as u32;
+        if len > MAX_LEN {
+            return Err(Error::LimitReached);
+        }
+
+        buf[..LEN_BYTES].copy_from_slice(&len.to_be_bytes()[..]);
+        Ok(Self(buf))
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+pub struct Unpacked(Vec<u8>);
+
+impl Unpacked {
+    pub fn new(len: u32) -> Result<Self, Error> {
+        if len > MAX_LEN {
+            return Err(Error::LimitReached);
+        }
+
+        Ok(Self(vec![0; len as usize]))
+    }
+
+    pub fn bytes(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+
+    pub fn to<T>(&self) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(bincode::deserialize(&self.0)?)
+    }
+}
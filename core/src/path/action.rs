@@ -1,6 +1,7 @@
 use crate::{rotation::Rotation, side::Side};
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum Action {
     Stay,
     Step(Rotation),
@@ -11,6 +12,8 @@ pub enum Action {
     JumpOver(Rotation),
     Fall(Rotation),
     Fly(Side),
+    Climb(Side),
+    Swim(Side),
     Impossible,
 }
 
@@ -26,12 +29,17 @@ impl Action {
             Self::JumpOver(rotation) => Self::JumpOver(rotation.opposite()),
             Self::Fall { .. } => Self::Impossible,
             Self::Fly(side) => Self::Fly(side.opposite()),
+            Self::Climb(side) => Self::Climb(side.opposite()),
+            Self::Swim(side) => Self::Swim(side.opposite()),
             Self::Impossible => Self::Impossible,
         }
     }
 
     pub fn is_final(self) -> bool {
-        !matches!(self, Self::LiftUp | Self::LiftDown | Self::Fly(_))
+        !matches!(
+            self,
+            Self::LiftUp | Self::LiftDown | Self::Fly(_) | Self::Climb(_)
+        )
     }
 
     pub fn cost(self) -> Option<u32> {
@@ -47,6 +55,8 @@ impl Action {
             Self::Fly(side) => {
                 Some(1 + matches!(side, Side::Left | Side::Right | Side::Forth | Side::Back) as u32)
             }
+            Self::Climb(_) => Some(2),
+            Self::Swim(_) => Some(2),
             Self::Impossible => Some(0),
         }
     }
@@ -0,0 +1,354 @@
+use crate::{
+    path::{finder::distance, Action, Close, Position, Reservations, Space, Walk},
+    point::Point,
+};
+use fxhash::FxHashSet as HashSet;
+use std::{cmp::Reverse, collections::BinaryHeap, ops::Range};
+
+/// The move of the planned path.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Timed {
+    pub action: Action,
+    pub pn: Point,
+    /// The tick when the move ends.
+    pub time: u32,
+}
+
+struct Node {
+    parent: usize,
+    action: Action,
+    pos: Position,
+    time: u32,
+}
+
+/// Windowed cooperative A*.
+///
+/// Plans the agents one by one in the order of priority in space and time,
+/// each path avoids the cells reserved by the paths planned before,
+/// so the agents wait for each other instead of meeting on a ladder.
+/// Reservations are respected only within the window, so the agents
+/// must be planned again before it ends.
+pub struct Cooperative {
+    pub window: u32,
+    pub table: Reservations,
+}
+
+impl Cooperative {
+    pub fn new(window: u32) -> Self {
+        Self {
+            window,
+            table: Reservations::new(),
+        }
+    }
+
+    /// Plans the path of the agent from the start at the tick to the goal
+    /// and reserves it.
+    ///
+    /// The value of the start position caps the cost of the path, waiting is free.
+    /// A move occupies both of its cells during the ticks of its cost.
+    /// Returns the moves from the start, the first one is `Action::Stay` at the start.
+    /// The previous reservations of the agent are kept while searching,
+    /// if no path is found, the agent is parked at the start instead.
+    pub fn plan<W, S>(
+        &mut self,
+        agent: u32,
+        now: u32,
+        start: Position,
+        goal: Point,
+        walk: &W,
+        space: &S,
+    ) -> Option<Vec<Timed>>
+    where
+        W: Walk<S>,
+        S: Space,
+    {
+        let end = now.saturating_add(self.window);
+        let mut nodes = vec![Node {
+            parent: usize::MAX,
+            action: Action::Stay,
+            pos: start,
+            time: now,
+        }];
+
+        let mut closed = HashSet::default();
+        let mut heap = BinaryHeap::new();
+        heap.push(Reverse((distance(start.pn, goal), 0)));
+
+        while let Some(Reverse((_, idx))) = heap.pop() {
+            let Node {
+                action, pos, time, ..
+            } = nodes[idx];
+
+            // After the window the time isn't tracked anymore
+            if !closed.insert((pos.pn, time.min(end))) {
+                continue;
+            }
+
+            if pos.pn == goal && action.is_final() && self.is_free(agent, goal, time..end) {
+                let path = self.path(&nodes, idx);
+                self.table.release(agent);
+                self.reserve(agent, &path, end);
+                return Some(path);
+            }
+
+            let mut collect = Collect(Vec::new());
+            walk.walk(space, pos, &mut collect);
+            if time < end {
+                collect.0.push((Action::Stay, pos));
+            }
+
+            for (action, next) in collect.0 {
+                let ticks = (pos.value - next.value).max(1);
+                let arrive = time.saturating_add(ticks);
+                let during = time..arrive.saturating_add(1).min(end);
+                if !self.is_free(agent, pos.pn, during.clone())
+                    || !self.is_free(agent, next.pn, during)
+                {
+                    continue;
+                }
+
+                let estimate = (arrive - now).saturating_add(distance(next.pn, goal));
+                heap.push(Reverse((estimate, nodes.len())));
+                nodes.push(Node {
+                    parent: idx,
+                    action,
+                    pos: next,
+                    time: arrive,
+                });
+            }
+        }
+
+        self.table.release(agent);
+        self.table.park(agent, start.pn, now);
+        None
+    }
+
+    fn is_free(&self, agent: u32, pn: Point, mut ticks: Range<u32>) -> bool {
+        ticks.all(|tick| self.table.is_free(agent, pn, tick))
+    }
+
+    fn path(&self, nodes: &[Node], mut idx: usize) -> Vec<Timed> {
+        let mut path = Vec::new();
+        while let Some(node) = nodes.get(idx) {
+            path.push(Timed {
+                action: node.action,
+                pn: node.pos.pn,
+                time: node.time,
+            });
+
+            idx = node.parent;
+        }
+
+        path.reverse();
+        path
+    }
+
+    /// Reserves the path within the window and parks the agent at its end.
+    fn reserve(&mut self, agent: u32, path: &[Timed], end: u32) {
+        for pair in path.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            let ticks = from.time..to.time.saturating_add(1).min(end);
+            self.table.reserve(agent, from.pn, ticks.clone());
+            self.table.reserve(agent, to.pn, ticks);
+        }
+
+        if let Some(last) = path.last() {
+            self.table.park(agent, last.pn, last.time);
+        }
+    }
+}
+
+struct Collect(Vec<(Action, Position)>);
+
+impl Close for Collect {
+    fn close(&mut self, action: Action, pos: Position) {
+        self.0.push((action, pos));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        height::Height,
+        map::Column,
+        path::{Pass, PathFinder, Reserved},
+        rotation::Rotation,
+    };
+    use fxhash::FxHashMap as HashMap;
+
+    /// The flat floor at zero height with the walls.
+    struct Walls(HashSet<(i64, i64)>);
+
+    impl Space for Walls {
+        fn get(&self, pn: Point) -> Pass {
+            match pn.absolute_point() {
+                (x, 0, z) if !self.0.contains(&(x, z)) => Pass::empty(),
+                _ => Pass::solid(),
+            }
+        }
+
+        fn column(&self, _: Point, _: Height) -> Column<'_, Pass> {
+            unreachable!()
+        }
+    }
+
+    struct Grid;
+
+    impl<S: Space> Walk<S> for Grid {
+        fn walk<C>(&self, space: &S, pos: Position, close: &mut C)
+        where
+            C: Close,
+        {
+            for rotation in [Rotation::Q0, Rotation::Q1, Rotation::Q2, Rotation::Q3] {
+                let action = Action::Step(rotation);
+                if let Some(pos) = self.run(space, pos, action) {
+                    close.close(action, pos);
+                }
+            }
+        }
+
+        fn run(&self, space: &S, pos: Position, action: Action) -> Option<Position> {
+            let value = pos.value.checked_sub(action.cost()?)?;
+            let pn = match action {
+                Action::Step(rotation) => pos.pn.to(rotation),
+                _ => return None,
+            };
+
+            (!space.get(pn).is_solid()).then_some(Position { pn, value, ..pos })
+        }
+    }
+
+    fn point(x: i64, z: i64) -> Point {
+        Point::from_absolute(x, 0, z).unwrap()
+    }
+
+    fn start(pn: Point) -> Position {
+        Position {
+            pn,
+            value: u32::MAX,
+            heading: None,
+        }
+    }
+
+    /// The corridor from (0, 0) to (8, 0) with the pocket at (6, 1).
+    fn corridor() -> Walls {
+        let walls = (-1..=9)
+            .flat_map(|x| (-1..=2).map(move |z| (x, z)))
+            .filter(|&(x, z)| z != 0 || !(0..=8).contains(&x))
+            .filter(|&cell| cell != (6, 1))
+            .collect();
+
+        Walls(walls)
+    }
+
+    /// Checks no cell is occupied by two agents at once.
+    fn check(paths: &[Vec<Timed>], horizon: u32) {
+        let mut occupied = HashMap::default();
+        let mut occupy = |agent, pn, tick| {
+            let holder = *occupied.entry((pn, tick)).or_insert(agent);
+            assert_eq!(holder, agent, "{pn:?} at {tick}");
+        };
+
+        for (agent, path) in paths.iter().enumerate() {
+            for pair in path.windows(2) {
+                for tick in pair[0].time..=pair[1].time {
+                    occupy(agent, pair[0].pn, tick);
+                    occupy(agent, pair[1].pn, tick);
+                }
+            }
+
+            let last = path.last().unwrap();
+            for tick in last.time..horizon {
+                occupy(agent, last.pn, tick);
+            }
+        }
+    }
+
+    #[test]
+    fn corridor_pass() {
+        let space = corridor();
+        let mut planner = Cooperative::new(32);
+        let first = planner
+            .plan(0, 0, start(point(0, 0)), point(8, 0), &Grid, &space)
+            .unwrap();
+        assert_eq!(first.len(), 9);
+        assert_eq!(first.last().unwrap().time, 8);
+
+        // The second agent dodges into the pocket and waits
+        let second = planner
+            .plan(1, 0, start(point(8, 0)), point(0, 0), &Grid, &space)
+            .unwrap();
+        assert!(second.iter().any(|timed| timed.pn == point(6, 1)));
+        assert!(second.iter().any(|timed| timed.action == Action::Stay));
+        check(&[first, second], 32);
+
+        // Goes straight without the first agent
+        planner.table.release(0);
+        let path = planner
+            .plan(1, 0, start(point(8, 0)), point(0, 0), &Grid, &space)
+            .unwrap();
+        assert_eq!(path.len(), 9);
+    }
+
+    #[test]
+    fn failed_plan() {
+        let space = corridor();
+        let mut planner = Cooperative::new(32);
+        planner
+            .plan(0, 0, start(point(0, 0)), point(8, 0), &Grid, &space)
+            .unwrap();
+
+        // The wall can't be reached, the agent stays parked
+        let failed = planner.plan(0, 8, start(point(8, 0)), point(6, 2), &Grid, &space);
+        assert_eq!(failed, None);
+        assert_eq!(planner.table.holder(point(8, 0), 100), Some(0));
+        assert_eq!(planner.table.holder(point(4, 0), 4), None);
+    }
+
+    #[test]
+    fn far_goal() {
+        // The estimate is clamped at the most distant goals
+        let space = corridor();
+        let mut planner = Cooperative::new(32);
+        let start = Position {
+            value: 10,
+            ..start(point(0, 0))
+        };
+        let path = planner.plan(0, 0, start, point(1 << 34, 0), &Grid, &space);
+        assert_eq!(path, None);
+    }
+
+    #[test]
+    fn reserved() {
+        let space = Walls(HashSet::default());
+        let mut table = Reservations::new();
+        table.reserve(1, point(1, 0), 0..1);
+        table.park(1, point(5, 0), 10);
+
+        let mut pf = PathFinder::new();
+        let goal = point(2, 0);
+        let reserved = |tick| Reserved {
+            space: &space,
+            table: &table,
+            agent: 0,
+            tick,
+        };
+
+        assert_eq!(
+            pf.find_to(start(point(0, 0)), goal, &Grid, &reserved(0)),
+            Some(4)
+        );
+        assert_eq!(
+            pf.find_to(start(point(0, 0)), goal, &Grid, &reserved(1)),
+            Some(2)
+        );
+        assert_eq!(table.holder(point(5, 0), 9), None);
+        assert_eq!(table.holder(point(5, 0), 100), Some(1));
+        assert!(table.is_free(1, point(1, 0), 0));
+
+        table.release(1);
+        assert_eq!(table.holder(point(1, 0), 0), None);
+        assert_eq!(table.holder(point(5, 0), 100), None);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{path::Action, side::Side};
+
+/// The costs of the actions of an agent.
+///
+/// `None` means the agent refuses the action.
+/// Moves on the horizontal axes cost at least the number of cells they pass,
+/// the lower costs are raised, otherwise `PathFinder::find_to` could miss the cheapest path.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct CostProfile {
+    pub step: Option<u32>,
+    pub lift: Option<u32>,
+    /// The cost of every level of the jump up, including the takeoff.
+    pub jump_up: Option<u32>,
+    pub jump_down: Option<u32>,
+    pub jump_over: Option<u32>,
+    pub fall: Option<u32>,
+    pub fly: Option<u32>,
+    pub fly_across: Option<u32>,
+    pub climb: Option<u32>,
+    pub swim: Option<u32>,
+}
+
+impl CostProfile {
+    /// Returns the cost of the action.
+    ///
+    /// The `spent` is the cost the walker found with the default profile,
+    /// it's needed for the actions which cost depends on the space.
+    pub fn cost(&self, action: Action, spent: u32) -> Option<u32> {
+        let cost = match action {
+            Action::Stay => Some(0),
+            Action::Step(_) => self.step,
+            Action::LiftUp | Action::LiftDown => self.lift,
+            Action::JumpUp(_) => self.jump_up?.checked_mul(spent),
+            Action::JumpDown(_) => self.jump_down,
+            Action::JumpOver(_) => self.jump_over,
+            Action::Fall(_) => self.fall,
+            Action::Fly(Side::Up | Side::Down) => self.fly,
+            Action::Fly(_) => self.fly_across,
+            Action::Climb(_) => self.climb,
+            Action::Swim(_) => self.swim,
+            Action::Impossible => None,
+        };
+
+        cost.map(|cost| cost.max(crossed(action)))
+    }
+}
+
+/// The number of cells the action passes on the horizontal axes.
+fn crossed(action: Action) -> u32 {
+    match action {
+        Action::Step(_) | Action::JumpUp(_) | Action::JumpDown(_) | Action::Fall(_) => 1,
+        Action::JumpOver(_) => 2,
+        Action::Fly(side) | Action::Climb(side) | Action::Swim(side) => {
+            !matches!(side, Side::Up | Side::Down) as u32
+        }
+        Action::Stay | Action::LiftUp | Action::LiftDown | Action::Impossible => 0,
+    }
+}
+
+impl Default for CostProfile {
+    fn default() -> Self {
+        Self {
+            step: Some(1),
+            lift: Some(1),
+            jump_up: Some(1),
+            jump_down: Some(2),
+            jump_over: Some(3),
+            fall: Some(1),
+            fly: Some(1),
+            fly_across: Some(2),
+            climb: Some(2),
+            swim: Some(2),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rotation::Rotation;
+
+    #[test]
+    fn default() {
+        let profile = CostProfile::default();
+        for rotation in [Rotation::Q0, Rotation::Q1, Rotation::Q2, Rotation::Q3] {
+            for action in [
+                Action::Step(rotation),
+                Action::JumpDown(rotation),
+                Action::JumpOver(rotation),
+                Action::Fall(rotation),
+            ] {
+                assert_eq!(profile.cost(action, 0), action.cost());
+            }
+
+            assert_eq!(profile.cost(Action::JumpUp(rotation), 4), Some(4));
+        }
+
+        for side in Side::ENUM {
+            for action in [Action::Fly(side), Action::Climb(side), Action::Swim(side)] {
+                assert_eq!(profile.cost(action, 0), action.cost());
+            }
+        }
+
+        for action in [Action::Stay, Action::LiftUp, Action::LiftDown] {
+            assert_eq!(profile.cost(action, 0), action.cost());
+        }
+    }
+
+    #[test]
+    fn admissible() {
+        let profile = CostProfile {
+            step: Some(0),
+            jump_up: Some(0),
+            jump_over: Some(1),
+            fly: Some(0),
+            fly_across: Some(0),
+            climb: Some(0),
+            ..CostProfile::default()
+        };
+
+        let rotation = Rotation::Q0;
+        assert_eq!(profile.cost(Action::Step(rotation), 0), Some(1));
+        assert_eq!(profile.cost(Action::JumpUp(rotation), 3), Some(1));
+        assert_eq!(profile.cost(Action::JumpOver(rotation), 0), Some(2));
+        assert_eq!(profile.cost(Action::Fly(Side::Left), 0), Some(1));
+        assert_eq!(profile.cost(Action::Climb(Side::Back), 0), Some(1));
+
+        // The vertical moves pass no cells
+        assert_eq!(profile.cost(Action::Fly(Side::Up), 0), Some(0));
+        assert_eq!(profile.cost(Action::Climb(Side::Down), 0), Some(0));
+    }
+}
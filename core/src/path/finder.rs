@@ -6,13 +6,32 @@ use crate::{
         walk::{Close, Position, Walk},
     },
     point::Point,
+    rotation::Rotation,
 };
 use fxhash::{FxBuildHasher, FxHashMap as HashMap};
-use std::collections::hash_map::Entry;
+use std::{
+    cmp::Reverse,
+    collections::{hash_map::Entry, BinaryHeap},
+};
+
+/// The searched state, the same point with other heading is another state.
+type Key = (Point, Option<Rotation>);
+
+fn key(pos: Position) -> Key {
+    (pos.pn, pos.heading)
+}
+
+const HEADINGS: [Option<Rotation>; 5] = [
+    None,
+    Some(Rotation::Q0),
+    Some(Rotation::Q1),
+    Some(Rotation::Q2),
+    Some(Rotation::Q3),
+];
 
 #[derive(Default)]
 pub struct PathFinder {
-    closed: HashMap<Point, NodePtr>,
+    closed: HashMap<Key, NodePtr>,
     open: Vec<NodePtr>,
     buf: Vec<NodePtr>,
     tree: Tree<(Action, Position)>,
@@ -28,6 +47,10 @@ impl PathFinder {
         }
     }
 
+    /// Finds the points reachable with the value of the position.
+    ///
+    /// Expands the positions layer by layer, so the found paths
+    /// take the least actions, but not always cost the least.
     pub fn find<W, S>(&mut self, pos: Position, walk: &W, space: &S)
     where
         W: Walk<S>,
@@ -55,7 +78,7 @@ impl PathFinder {
 
             for ptr in self.open.drain(..) {
                 let node = self.tree.get(ptr);
-                match self.closed.entry(node.1.pn) {
+                match self.closed.entry(key(node.1)) {
                     Entry::Occupied(mut en) => {
                         let old = self.tree.get(*en.get());
                         if old.1.value < node.1.value {
@@ -76,19 +99,147 @@ impl PathFinder {
         }
     }
 
-    pub fn path(&self) -> Path {
+    /// Finds the cheapest paths to the points reachable
+    /// with the value of the position.
+    ///
+    /// Expands the positions with the most value left first (Dijkstra),
+    /// so every point is closed with the least cost.
+    pub fn find_cheapest<W, S>(&mut self, pos: Position, walk: &W, space: &S)
+    where
+        W: Walk<S>,
+        S: Space,
+    {
+        self.clear();
+        if pos.value == 0 {
+            return;
+        }
+
+        let mut heap = BinaryHeap::new();
+        let ptr = self.tree.push(NodePtr::ROOT, (Action::Stay, pos));
+        heap.push((pos.value, Reverse(ptr)));
+
+        while let Some((_, Reverse(ptr))) = heap.pop() {
+            let pos = self.tree.get(ptr).1;
+            match self.closed.entry(key(pos)) {
+                Entry::Occupied(_) => continue,
+                Entry::Vacant(en) => en.insert(ptr),
+            };
+
+            let mut close = Closer {
+                closed: &self.closed,
+                buf: &mut self.buf,
+                tree: &mut self.tree,
+                parent: ptr,
+            };
+
+            walk.walk(space, pos, &mut close);
+            for ptr in self.buf.drain(..) {
+                heap.push((self.tree.get(ptr).1.value, Reverse(ptr)));
+            }
+        }
+    }
+
+    /// Finds the cheapest path from the start to the goal with A*.
+    ///
+    /// The value of the start position caps the cost of the path,
+    /// pass `u32::MAX` to search without the cap.
+    /// Returns the cost of the found path, then `Path::to(goal)` lists it.
+    pub fn find_to<W, S>(
+        &mut self,
+        start: Position,
+        goal: Point,
+        walk: &W,
+        space: &S,
+    ) -> Option<u32>
+    where
+        W: Walk<S>,
+        S: Space,
+    {
+        self.clear();
+        let mut heap = BinaryHeap::new();
+        let ptr = self.tree.push(NodePtr::ROOT, (Action::Stay, start));
+        heap.push(Reverse((distance(start.pn, goal), ptr)));
+
+        while let Some(Reverse((_, ptr))) = heap.pop() {
+            let (action, pos) = *self.tree.get(ptr).value();
+            if self.closed.contains_key(&key(pos)) {
+                continue;
+            }
+
+            let cost = start.value - pos.value;
+            if pos.pn == goal {
+                if action.is_final() {
+                    self.closed.insert(key(pos), ptr);
+                    return Some(cost);
+                }
+            } else {
+                self.closed.insert(key(pos), ptr);
+            }
+
+            let mut open = Opener {
+                closed: &self.closed,
+                buf: &mut self.buf,
+                tree: &mut self.tree,
+                parent: ptr,
+            };
+
+            walk.walk(space, pos, &mut open);
+            for ptr in self.buf.drain(..) {
+                let pos = self.tree.get(ptr).1;
+                let cost = start.value - pos.value;
+                heap.push(Reverse((cost.saturating_add(distance(pos.pn, goal)), ptr)));
+            }
+        }
+
+        None
+    }
+
+    /// Returns the value left at the closed point.
+    pub fn value(&self, pn: Point) -> Option<u32> {
+        self.best(pn).map(|ptr| self.tree.get(ptr).1.value)
+    }
+
+    /// Returns the closed points with the most value left at them.
+    #[cfg(test)]
+    pub(crate) fn closed(&self) -> impl Iterator<Item = (Point, u32)> + '_ {
+        self.closed
+            .keys()
+            .map(|&(pn, _)| (pn, self.value(pn).unwrap()))
+    }
+
+    /// The closed node of the point with the most value left in any heading.
+    fn best(&self, pn: Point) -> Option<NodePtr> {
+        HEADINGS
+            .iter()
+            .filter_map(|&heading| self.closed.get(&(pn, heading)).copied())
+            .max_by_key(|&ptr| self.tree.get(ptr).1.value)
+    }
+
+    pub fn path(&self) -> Path<'_> {
         Path(self)
     }
 
     pub fn clear(&mut self) {
         self.closed.clear();
         self.open.clear();
+        self.buf.clear();
         self.tree.clear();
     }
 }
 
+/// The lower bound of the cost between the points.
+///
+/// Every action moving on the horizontal axes costs at least
+/// the number of cells it passes, the vertical axis is free.
+pub(crate) fn distance(a: Point, b: Point) -> u32 {
+    let (ax, _, az) = a.absolute_point();
+    let (bx, _, bz) = b.absolute_point();
+    let dist = ax.abs_diff(bx) + az.abs_diff(bz);
+    dist.min(u32::MAX as u64) as u32
+}
+
 struct Closer<'a> {
-    closed: &'a HashMap<Point, NodePtr>,
+    closed: &'a HashMap<Key, NodePtr>,
     buf: &'a mut Vec<NodePtr>,
     tree: &'a mut Tree<(Action, Position)>,
     parent: NodePtr,
@@ -96,7 +247,7 @@ struct Closer<'a> {
 
 impl Close for Closer<'_> {
     fn close(&mut self, action: Action, pos: Position) {
-        if self.closed.contains_key(&pos.pn) {
+        if self.closed.contains_key(&key(pos)) {
             return;
         }
 
@@ -107,6 +258,24 @@ impl Close for Closer<'_> {
     }
 }
 
+/// Keeps all reached positions, even with no value left,
+/// since the goal can be reached with the last step.
+struct Opener<'a> {
+    closed: &'a HashMap<Key, NodePtr>,
+    buf: &'a mut Vec<NodePtr>,
+    tree: &'a mut Tree<(Action, Position)>,
+    parent: NodePtr,
+}
+
+impl Close for Opener<'_> {
+    fn close(&mut self, action: Action, pos: Position) {
+        if !self.closed.contains_key(&key(pos)) {
+            let ptr = self.tree.push(self.parent, (action, pos));
+            self.buf.push(ptr);
+        }
+    }
+}
+
 pub struct Path<'a>(&'a PathFinder);
 
 impl Path<'_> {
@@ -114,14 +283,157 @@ impl Path<'_> {
         self.0
             .closed
             .iter()
-            .filter_map(|(pn, ptr)| self.0.tree.get(*ptr).0.is_final().then(|| *pn))
+            .filter(|&(&(pn, _), &ptr)| self.0.best(pn) == Some(ptr))
+            .filter_map(|(&(pn, _), ptr)| self.0.tree.get(*ptr).0.is_final().then_some(pn))
     }
 
     pub fn to(&self, pn: Point) -> impl Iterator<Item = (Action, Point)> + '_ {
-        let ptr = self.0.closed[&pn];
+        let ptr = self.0.best(pn).expect("closed point");
         self.0.tree.list(ptr).map(|node| {
             let (action, pos) = node.value();
             (*action, pos.pn)
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        path::testing::{check, Void},
+        rotation::Rotation,
+    };
+    use proptest::prelude::*;
+    use std::collections::HashSet;
+
+    /// Steps on the flat grid with walls, can jump over them if `jump`.
+    struct Grid {
+        walls: HashSet<(i64, i64)>,
+        jump: bool,
+    }
+
+    impl Grid {
+        fn is_free(&self, pn: Point) -> bool {
+            let (x, _, z) = pn.absolute_point();
+            !self.walls.contains(&(x, z))
+        }
+    }
+
+    impl Walk<Void> for Grid {
+        fn walk<C>(&self, space: &Void, pos: Position, close: &mut C)
+        where
+            C: Close,
+        {
+            for rotation in [Rotation::Q0, Rotation::Q1, Rotation::Q2, Rotation::Q3] {
+                let action = Action::Step(rotation);
+                if let Some(pos) = self.run(space, pos, action) {
+                    close.close(action, pos);
+                }
+
+                let action = Action::JumpOver(rotation);
+                if let Some(pos) = self.run(space, pos, action) {
+                    close.close(action, pos);
+                }
+            }
+        }
+
+        fn run(&self, _: &Void, pos: Position, action: Action) -> Option<Position> {
+            let value = pos.value.checked_sub(action.cost().unwrap_or(0))?;
+            let pn = match action {
+                Action::Step(rotation) => pos.pn.to(rotation),
+                Action::JumpOver(rotation) if self.jump => pos.pn.to(rotation).to(rotation),
+                _ => return None,
+            };
+
+            self.is_free(pn).then_some(Position { pn, value, ..pos })
+        }
+    }
+
+    fn point(x: i64, z: i64) -> Point {
+        Point::from_absolute(x, 0, z).unwrap()
+    }
+
+    #[test]
+    fn find_to() {
+        // The wall between the start and the goal with the gap at z = 5
+        let walls = (-20..20).filter(|&z| z != 5).map(|z| (3, z)).collect();
+        let grid = Grid { walls, jump: false };
+        let (start, goal) = (point(0, 0), point(6, 0));
+
+        let mut pf = PathFinder::new();
+        let pos = Position {
+            pn: start,
+            value: u32::MAX,
+            heading: None,
+        };
+        assert_eq!(pf.find_to(pos, goal, &grid, &Void), Some(16));
+
+        let path: Vec<_> = pf.path().to(goal).map(|(_, pn)| pn).collect();
+        assert_eq!(path.len(), 17);
+        assert_eq!(path.first(), Some(&goal));
+        assert_eq!(path.last(), Some(&start));
+        assert!(path.contains(&point(3, 5)));
+
+        // The search stops at the goal
+        assert!(pf.closed.len() < 40 * 40);
+
+        let pos = Position {
+            pn: start,
+            value: 15,
+            heading: None,
+        };
+        assert_eq!(pf.find_to(pos, goal, &grid, &Void), None);
+        let pos = Position {
+            pn: start,
+            value: 16,
+            heading: None,
+        };
+        assert_eq!(pf.find_to(pos, goal, &grid, &Void), Some(16));
+    }
+
+    #[test]
+    fn unreachable() {
+        let walls = (-3..=3)
+            .flat_map(|x| [(x, -3), (x, 3), (-3, x), (3, x)])
+            .collect();
+        let grid = Grid { walls, jump: false };
+        let pos = Position {
+            pn: point(0, 0),
+            value: u32::MAX,
+            heading: None,
+        };
+
+        let mut pf = PathFinder::new();
+        assert_eq!(pf.find_to(pos, point(10, 10), &grid, &Void), None);
+        assert_eq!(pf.closed.len(), 25);
+        assert_eq!(pf.find_to(pos, pos.pn, &grid, &Void), Some(0));
+    }
+
+    #[test]
+    fn far_goal() {
+        // The estimate is clamped at the most distant goals
+        let grid = Grid {
+            walls: HashSet::default(),
+            jump: false,
+        };
+        let pos = Position {
+            pn: point(0, 0),
+            value: 10,
+            heading: None,
+        };
+
+        let mut pf = PathFinder::new();
+        assert_eq!(pf.find_to(pos, point(1 << 34, 0), &grid, &Void), None);
+    }
+
+    proptest! {
+        #[test]
+        fn cheapest_jumper(walls in prop::collection::hash_set((-4..4i64, -4..4i64), 0..24), value in 1..10u32) {
+            // Jumping over costs more than two steps,
+            // so the path with less actions isn't the cheapest
+            let walls = walls.into_iter().filter(|&wall| wall != (0, 0)).collect();
+            let walk = Grid { walls, jump: true };
+            check(Position { pn: point(0, 0), value, heading: None }, &walk, &Void)?;
+        }
+    }
+}
@@ -0,0 +1,529 @@
+use crate::{
+    chunk::{HEIGHT, SIDE},
+    path::{
+        finder::{distance, PathFinder},
+        Action, Close, Position, Space, Walk,
+    },
+    point::{ChunkPoints, ClusterPoint, Point},
+    side::Side,
+};
+use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+/// How far from a cell the walkers look on the horizontal axes.
+const REACH_XZ: u8 = 2;
+/// How far from a cell the walkers look on the vertical axis.
+const REACH_Y: u8 = 10;
+/// Clusters around the start and the goal the coarse search can go through.
+const SLACK: u8 = 1;
+
+/// The portals of a chunk.
+#[derive(Default)]
+struct ChunkGraph {
+    /// Steps out of the chunk: the point in the chunk,
+    /// the point in the neighbour and the cost.
+    exits: Vec<(Point, Point, u32)>,
+    /// The cheapest costs from the points in the chunk to its exits,
+    /// found when the point is first reached.
+    reach: HashMap<Point, Vec<(usize, u32)>>,
+}
+
+/// Hierarchical path search.
+///
+/// Keeps the portals of every chunk, searches the route over them first
+/// and then the path with the walker only inside the chunks of the route.
+/// The portals depend on the walker, so it must stay the same.
+pub struct Hierarchy {
+    graphs: HashMap<ClusterPoint, ChunkGraph>,
+    pf: PathFinder,
+    slack: u8,
+}
+
+impl Default for Hierarchy {
+    fn default() -> Self {
+        Self::with_slack(SLACK)
+    }
+}
+
+impl Hierarchy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates the hierarchy searching the routes through `slack` clusters
+    /// around the start and the goal.
+    pub fn with_slack(slack: u8) -> Self {
+        Self {
+            graphs: HashMap::default(),
+            pf: PathFinder::new(),
+            slack,
+        }
+    }
+
+    /// Drops the portals which can depend on the changed point.
+    ///
+    /// Must be called when a tile is placed or removed.
+    pub fn invalidate(&mut self, pn: Point) {
+        let (x, y, z) = pn.chunk_point().axes();
+        let near = |val: u8, len: usize, reach: u8, lo: Side, hi: Side| {
+            let mut sides = vec![None];
+            if val < reach {
+                sides.push(Some(lo));
+            }
+
+            if val as usize >= len - reach as usize {
+                sides.push(Some(hi));
+            }
+
+            sides
+        };
+
+        let cl = pn.cluster_point();
+        for sx in near(x, SIDE, REACH_XZ, Side::Right, Side::Left) {
+            for sy in near(y, HEIGHT, REACH_Y, Side::Down, Side::Up) {
+                for sz in near(z, SIDE, REACH_XZ, Side::Back, Side::Forth) {
+                    let cl = [sx, sy, sz]
+                        .into_iter()
+                        .flatten()
+                        .fold(cl, ClusterPoint::to);
+                    self.graphs.remove(&cl);
+                }
+            }
+        }
+    }
+
+    /// Drops the portals of the cluster and its neighbours,
+    /// when the whole cluster is loaded or unloaded.
+    pub fn invalidate_cluster(&mut self, cl: ClusterPoint) {
+        self.graphs.remove(&cl);
+        for side in Side::ENUM {
+            self.graphs.remove(&cl.to(side));
+        }
+    }
+
+    /// Finds the path from the start to the goal.
+    ///
+    /// The value of the start position caps the cost of the path.
+    /// Only the box of the clusters around the start and the goal, widened
+    /// by the slack, is searched, so the paths detouring farther aren't found.
+    /// Returns the cost of the found path, then `pf.path().to(goal)` lists it.
+    pub fn find_to<W, S>(
+        &mut self,
+        pf: &mut PathFinder,
+        start: Position,
+        goal: Point,
+        walk: &W,
+        space: &S,
+    ) -> Option<u32>
+    where
+        W: Walk<S>,
+        S: Space,
+    {
+        let route = self.route(start, goal, walk, space)?;
+        let clusters = route.iter().map(|pn| pn.cluster_point()).collect();
+        let walk = Within {
+            walk,
+            clusters: &clusters,
+        };
+
+        pf.find_to(start, goal, &walk, space)
+    }
+
+    /// Searches the route over the portals with A*.
+    ///
+    /// Returns the points where the route enters the chunks.
+    fn route<W, S>(
+        &mut self,
+        start: Position,
+        goal: Point,
+        walk: &W,
+        space: &S,
+    ) -> Option<Vec<Point>>
+    where
+        W: Walk<S>,
+        S: Space,
+    {
+        let bounds = Bounds::new(start.pn.cluster_point(), goal.cluster_point(), self.slack);
+        let goal_cl = goal.cluster_point();
+        let mut costs = HashMap::default();
+        let mut parents = HashMap::default();
+        let mut heap = BinaryHeap::new();
+        let mut opened = vec![start.pn];
+        costs.insert(start.pn, 0);
+        heap.push(Reverse((distance(start.pn, goal), 0, 0)));
+
+        while let Some(Reverse((_, cost, idx))) = heap.pop() {
+            let pn = opened[idx];
+            if cost > costs[&pn] {
+                continue;
+            }
+
+            if pn == goal {
+                let mut route = vec![goal];
+                let mut pn = goal;
+                while let Some(&parent) = parents.get(&pn) {
+                    route.push(parent);
+                    pn = parent;
+                }
+
+                route.reverse();
+                return Some(route);
+            }
+
+            let mut next = Vec::new();
+            let cl = pn.cluster_point();
+            if cl == goal_cl {
+                let clusters = [cl].into_iter().collect();
+                let within = Within {
+                    walk,
+                    clusters: &clusters,
+                };
+
+                let pos = Position {
+                    pn,
+                    value: start.value - cost,
+                    heading: None,
+                };
+
+                if let Some(rest) = self.pf.find_to(pos, goal, &within, space) {
+                    next.push((goal, rest));
+                }
+            }
+
+            self.graph(cl, walk, space);
+            let graph = self.reach(pn, walk, space);
+            for &(idx, to_exit) in &graph.reach[&pn] {
+                let (_, to, step) = graph.exits[idx];
+                if bounds.contains(to.cluster_point()) {
+                    next.push((to, to_exit + step));
+                }
+            }
+
+            for (to, step) in next {
+                let cost = match cost.checked_add(step) {
+                    Some(cost) if cost <= start.value => cost,
+                    _ => continue,
+                };
+
+                if costs.get(&to).is_none_or(|&old| cost < old) {
+                    costs.insert(to, cost);
+                    parents.insert(to, pn);
+                    let estimate = cost.saturating_add(distance(to, goal));
+                    heap.push(Reverse((estimate, cost, opened.len())));
+                    opened.push(to);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Finds the exits of the chunk if they aren't known.
+    fn graph<W, S>(&mut self, cl: ClusterPoint, walk: &W, space: &S)
+    where
+        W: Walk<S>,
+        S: Space,
+    {
+        if self.graphs.contains_key(&cl) {
+            return;
+        }
+
+        let mut graph = ChunkGraph::default();
+        for ch in ChunkPoints::new() {
+            let pn = Point::new(ch, cl);
+            let mut collect = Collect(Vec::new());
+            walk.walk(
+                space,
+                Position {
+                    pn,
+                    value: u32::MAX,
+                    heading: None,
+                },
+                &mut collect,
+            );
+            for (_, pos) in collect.0 {
+                if pos.pn.cluster_point() != cl {
+                    graph.exits.push((pn, pos.pn, u32::MAX - pos.value));
+                }
+            }
+        }
+
+        self.graphs.insert(cl, graph);
+    }
+
+    /// Finds the costs from the point to the exits of its chunk if they aren't known.
+    fn reach<W, S>(&mut self, pn: Point, walk: &W, space: &S) -> &ChunkGraph
+    where
+        W: Walk<S>,
+        S: Space,
+    {
+        let cl = pn.cluster_point();
+        let graph = self.graphs.get_mut(&cl).unwrap();
+        if !graph.reach.contains_key(&pn) {
+            let clusters = [cl].into_iter().collect();
+            let within = Within {
+                walk,
+                clusters: &clusters,
+            };
+
+            let value = u32::MAX;
+            self.pf.find_cheapest(
+                Position {
+                    pn,
+                    value,
+                    heading: None,
+                },
+                &within,
+                space,
+            );
+            let closed = &self.pf;
+            let reach = graph
+                .exits
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, &(from, _, _))| {
+                    let left = closed.value(from)?;
+                    Some((idx, value - left))
+                })
+                .collect();
+
+            graph.reach.insert(pn, reach);
+        }
+
+        graph
+    }
+}
+
+/// The clusters the coarse search can go through.
+struct Bounds {
+    lo: (i32, i32, i32),
+    hi: (i32, i32, i32),
+}
+
+impl Bounds {
+    fn new(a: ClusterPoint, b: ClusterPoint, slack: u8) -> Self {
+        let slack = slack as i32;
+        Self {
+            lo: (
+                a.x().min(b.x()).saturating_sub(slack),
+                a.y().min(b.y()).saturating_sub(slack),
+                a.z().min(b.z()).saturating_sub(slack),
+            ),
+            hi: (
+                a.x().max(b.x()).saturating_add(slack),
+                a.y().max(b.y()).saturating_add(slack),
+                a.z().max(b.z()).saturating_add(slack),
+            ),
+        }
+    }
+
+    fn contains(&self, cl: ClusterPoint) -> bool {
+        (self.lo.0..=self.hi.0).contains(&cl.x())
+            && (self.lo.1..=self.hi.1).contains(&cl.y())
+            && (self.lo.2..=self.hi.2).contains(&cl.z())
+    }
+}
+
+/// The walker which doesn't leave the clusters.
+struct Within<'a, W> {
+    walk: &'a W,
+    clusters: &'a HashSet<ClusterPoint>,
+}
+
+impl<S, W> Walk<S> for Within<'_, W>
+where
+    S: Space,
+    W: Walk<S>,
+{
+    fn walk<C>(&self, space: &S, pos: Position, close: &mut C)
+    where
+        C: Close,
+    {
+        let mut close = WithinCloser {
+            close,
+            clusters: self.clusters,
+        };
+
+        self.walk.walk(space, pos, &mut close);
+    }
+
+    fn run(&self, space: &S, pos: Position, action: Action) -> Option<Position> {
+        let pos = self.walk.run(space, pos, action)?;
+        self.clusters
+            .contains(&pos.pn.cluster_point())
+            .then_some(pos)
+    }
+}
+
+struct WithinCloser<'a, C> {
+    close: &'a mut C,
+    clusters: &'a HashSet<ClusterPoint>,
+}
+
+impl<C: Close> Close for WithinCloser<'_, C> {
+    fn close(&mut self, action: Action, pos: Position) {
+        if self.clusters.contains(&pos.pn.cluster_point()) {
+            self.close.close(action, pos);
+        }
+    }
+}
+
+struct Collect(Vec<(Action, Position)>);
+
+impl Close for Collect {
+    fn close(&mut self, action: Action, pos: Position) {
+        self.0.push((action, pos));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{height::Height, map::Column, path::Pass, rotation::Rotation};
+    use proptest::prelude::*;
+
+    /// The flat floor at zero height with the walls.
+    struct Walls(HashSet<(i64, i64)>);
+
+    impl Space for Walls {
+        fn get(&self, pn: Point) -> Pass {
+            match pn.absolute_point() {
+                (x, 0, z) if !self.0.contains(&(x, z)) => Pass::empty(),
+                _ => Pass::solid(),
+            }
+        }
+
+        fn column(&self, _: Point, _: Height) -> Column<'_, Pass> {
+            unreachable!()
+        }
+    }
+
+    struct Grid;
+
+    impl Walk<Walls> for Grid {
+        fn walk<C>(&self, space: &Walls, pos: Position, close: &mut C)
+        where
+            C: Close,
+        {
+            for rotation in [Rotation::Q0, Rotation::Q1, Rotation::Q2, Rotation::Q3] {
+                let action = Action::Step(rotation);
+                if let Some(pos) = self.run(space, pos, action) {
+                    close.close(action, pos);
+                }
+            }
+        }
+
+        fn run(&self, space: &Walls, pos: Position, action: Action) -> Option<Position> {
+            let value = pos.value.checked_sub(action.cost()?)?;
+            let pn = match action {
+                Action::Step(rotation) if !space.get(pos.pn).is_solid() => pos.pn.to(rotation),
+                _ => return None,
+            };
+
+            (!space.get(pn).is_solid()).then_some(Position { pn, value, ..pos })
+        }
+    }
+
+    fn point(x: i64, z: i64) -> Point {
+        Point::from_absolute(x, 0, z).unwrap()
+    }
+
+    fn start(pn: Point) -> Position {
+        Position {
+            pn,
+            value: u32::MAX,
+            heading: None,
+        }
+    }
+
+    #[test]
+    fn invalidate() {
+        // The wall between the start and the goal crossing the chunks
+        let mut walls: HashSet<_> = (-40..60).map(|z| (20, z)).collect();
+        let (from, goal) = (point(2, 2), point(30, 4));
+        let mut hierarchy = Hierarchy::new();
+        let mut pf = PathFinder::new();
+        let space = Walls(walls.clone());
+        assert_eq!(
+            hierarchy.find_to(&mut pf, start(from), goal, &Grid, &space),
+            None
+        );
+
+        // Break through the wall
+        walls.remove(&(20, 10));
+        hierarchy.invalidate(point(20, 10));
+        let space = Walls(walls.clone());
+        let cost = hierarchy.find_to(&mut pf, start(from), goal, &Grid, &space);
+        assert_eq!(cost, Some(28 + 14));
+        let path: Vec<_> = pf.path().to(goal).map(|(_, pn)| pn).collect();
+        assert_eq!(path.len(), 28 + 14 + 1);
+        assert!(path.contains(&point(20, 10)));
+
+        // Close it again
+        walls.insert((20, 10));
+        hierarchy.invalidate(point(20, 10));
+        let space = Walls(walls);
+        assert_eq!(
+            hierarchy.find_to(&mut pf, start(from), goal, &Grid, &space),
+            None
+        );
+    }
+
+    #[test]
+    fn slack() {
+        // The only gap in the wall is two clusters away from the start and the goal
+        let walls = (-40..80).filter(|&z| z != 50).map(|z| (20, z)).collect();
+        let (from, goal) = (point(2, 2), point(30, 4));
+        let space = Walls(walls);
+        let mut pf = PathFinder::new();
+        let expected = pf.find_to(start(from), goal, &Grid, &space);
+        assert!(expected.is_some());
+
+        let mut hierarchy = Hierarchy::new();
+        let found = hierarchy.find_to(&mut pf, start(from), goal, &Grid, &space);
+        assert_eq!(found, None);
+
+        let mut hierarchy = Hierarchy::with_slack(3);
+        let found = hierarchy.find_to(&mut pf, start(from), goal, &Grid, &space);
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn far_goal() {
+        // The estimate is clamped at the most distant goals
+        let start = Position {
+            pn: point(0, 0),
+            value: 10,
+            heading: None,
+        };
+
+        let mut hierarchy = Hierarchy::new();
+        let mut pf = PathFinder::new();
+        let space = Walls(HashSet::default());
+        let goal = point(1 << 34, 0);
+        assert_eq!(hierarchy.find_to(&mut pf, start, goal, &Grid, &space), None);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(16))]
+
+        #[test]
+        fn same_cost(
+            walls in prop::collection::hash_set((-8..40i64, -8..40i64), 0..400),
+            goal in (-8..40i64, -8..40i64),
+        ) {
+            let walls: HashSet<_> = walls
+                .into_iter()
+                .filter(|&wall| wall != (0, 0) && wall != goal)
+                .collect();
+            let space = Walls(walls);
+            let goal = point(goal.0, goal.1);
+
+            let mut pf = PathFinder::new();
+            let expected = pf.find_to(start(point(0, 0)), goal, &Grid, &space);
+            let mut hierarchy = Hierarchy::new();
+            let found = hierarchy.find_to(&mut pf, start(point(0, 0)), goal, &Grid, &space);
+            prop_assert_eq!(found, expected);
+        }
+    }
+}
@@ -1,14 +1,33 @@
 mod action;
+mod cooperative;
+mod cost;
 mod finder;
+mod hierarchy;
+mod motion;
 mod pass;
+mod player;
+mod replan;
+mod reserve;
 mod space;
+#[cfg(test)]
+mod testing;
 mod tree;
 mod walk;
 
 pub use self::{
     action::Action,
+    cooperative::{Cooperative, Timed},
+    cost::CostProfile,
     finder::PathFinder,
+    hierarchy::Hierarchy,
+    motion::{Motion, Segment, Trajectory},
     pass::Pass,
+    player::{player, PlayerWalk},
+    replan::Replanner,
+    reserve::{Reservations, Reserved},
     space::Space,
-    walk::{Close, Flyer, Jumper, Pedestrian, Position, Walk},
+    walk::{
+        Climber, Close, Flyer, Footprint, Jumper, Merge, Pedestrian, Position, Profiled, Swimmer,
+        Walk, Wide,
+    },
 };
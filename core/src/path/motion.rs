@@ -0,0 +1,337 @@
+use crate::{
+    height::Height,
+    path::{Action, Space},
+    point::Point,
+    side::Side,
+};
+use shr::cgm::*;
+
+/// How high the agent rises over the higher end of a jump.
+const JUMP_ARC: f32 = 0.5;
+/// The step of sampling the straight lines checked by the smoothing.
+const SAMPLE: f32 = 0.1;
+/// How far from the line the agent body reaches.
+const RADIUS: f32 = 0.3;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Motion {
+    Wait,
+    Walk,
+    Jump,
+    Climb,
+    Fall,
+    Fly,
+    Swim,
+}
+
+impl Motion {
+    fn of(action: Action) -> Self {
+        match action {
+            Action::Stay => Self::Wait,
+            Action::JumpUp(_) | Action::JumpDown(_) | Action::JumpOver(_) => Self::Jump,
+            Action::LiftUp | Action::LiftDown | Action::Climb(_) => Self::Climb,
+            Action::Fall(_) => Self::Fall,
+            Action::Fly(_) => Self::Fly,
+            Action::Swim(_) => Self::Swim,
+            _ => Self::Walk,
+        }
+    }
+}
+
+/// The part of the trajectory made with one motion.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub motion: Motion,
+    pub from: Point,
+    pub to: Point,
+    pub duration: f32,
+}
+
+impl Segment {
+    /// Returns the position at the part of the segment from 0 to 1.
+    pub fn at(&self, s: f32) -> Vec3 {
+        let (a, b) = (center(self.from), center(self.to));
+        let mut pos = a.lerp(b, s);
+        match self.motion {
+            Motion::Jump => {
+                // The parabola through both ends with the top over the higher one
+                let top = a.y.max(b.y) + JUMP_ARC;
+                let (p, q) = ((top - a.y).sqrt(), (top - b.y).sqrt());
+                pos.y = top - ((p + q) * s - p).powi(2);
+            }
+            Motion::Fall => pos.y = a.y + (b.y - a.y) * s * s,
+            _ => {}
+        }
+
+        pos
+    }
+}
+
+/// The bottom center of the cell.
+fn center(pn: Point) -> Vec3 {
+    Vec3::from(pn) + Vec3::new(0.5, 0., 0.5)
+}
+
+/// Time-parameterised movement along a path.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trajectory {
+    segments: Vec<Segment>,
+}
+
+impl Trajectory {
+    /// Makes the trajectory of the moves from the start.
+    ///
+    /// Every move takes the `pace` of time for a unit of its cost,
+    /// the first move is the start itself. A wait takes one unit at least.
+    pub fn new<I>(moves: I, pace: f32) -> Self
+    where
+        I: IntoIterator<Item = (Action, Point)>,
+    {
+        let mut segments = Vec::new();
+        let mut moves = moves.into_iter();
+        let mut from = match moves.next() {
+            Some((_, pn)) => pn,
+            None => return Self { segments },
+        };
+
+        for (action, to) in moves {
+            let cost = match action {
+                Action::Stay => 1,
+                _ => action.cost().unwrap_or_else(|| {
+                    // The cost of the jump up is its height
+                    let (_, fy, _) = from.absolute_point();
+                    let (_, ty, _) = to.absolute_point();
+                    ty.abs_diff(fy) as u32
+                }),
+            };
+
+            segments.push(Segment {
+                motion: Motion::of(action),
+                from,
+                to,
+                duration: cost as f32 * pace,
+            });
+
+            from = to;
+        }
+
+        Self { segments }
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.segments.iter().map(|seg| seg.duration).sum()
+    }
+
+    /// Returns the position at the time since the start.
+    pub fn at(&self, mut time: f32) -> Option<Vec3> {
+        let last = self.segments.last()?;
+        for seg in &self.segments {
+            if time <= seg.duration {
+                let s = if seg.duration > 0. {
+                    time / seg.duration
+                } else {
+                    1.
+                };
+
+                return Some(seg.at(s));
+            }
+
+            time -= seg.duration;
+        }
+
+        Some(center(last.to))
+    }
+
+    /// Removes the zig-zags of walking by pulling the path straight
+    /// where the agent of the height can walk along the line.
+    ///
+    /// The walk keeps its speed, so the straight path takes less time.
+    /// The waits aren't merged, the agent stops there.
+    pub fn smooth<S: Space>(&mut self, height: Height, space: &S) {
+        let mut smooth: Vec<Segment> = Vec::with_capacity(self.segments.len());
+        for &seg in &self.segments {
+            if let Some(last) = smooth.last_mut() {
+                if last.motion == Motion::Walk
+                    && seg.motion == Motion::Walk
+                    && is_straight(last.from, seg.to, height, space)
+                {
+                    let speed = seg.duration / (center(seg.to) - center(seg.from)).magnitude();
+                    last.duration = (center(seg.to) - center(last.from)).magnitude() * speed;
+                    last.to = seg.to;
+                    continue;
+                }
+            }
+
+            smooth.push(seg);
+        }
+
+        self.segments = smooth;
+    }
+}
+
+/// Checks the agent can walk the straight line between the points on the same level.
+fn is_straight<S: Space>(a: Point, b: Point, height: Height, space: &S) -> bool {
+    let (_, ay, _) = a.absolute_point();
+    let (_, by, _) = b.absolute_point();
+    if ay != by {
+        return false;
+    }
+
+    let (a, b) = (center(a), center(b));
+    let len = (b - a).magnitude();
+    let n = (len / SAMPLE).ceil() as usize;
+    (0..=n).all(|i| {
+        let pos = a.lerp(b, i as f32 / n.max(1) as f32);
+        [
+            (-RADIUS, -RADIUS),
+            (-RADIUS, RADIUS),
+            (RADIUS, -RADIUS),
+            (RADIUS, RADIUS),
+        ]
+        .into_iter()
+        .all(|(dx, dz)| {
+            let x = (pos.x + dx).floor() as i64;
+            let z = (pos.z + dz).floor() as i64;
+            match Point::from_absolute(x, ay, z) {
+                Ok(pn) => is_walkable(pn, height, space),
+                Err(_) => false,
+            }
+        })
+    })
+}
+
+/// Checks the agent can stand at the point.
+fn is_walkable<S: Space>(pn: Point, height: Height, space: &S) -> bool {
+    space.get(pn.to(Side::Down)).is_passable()
+        && space.column(pn, height).iter().all(|pass| !pass.is_solid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{map::Column, path::Pass, rotation::Rotation};
+    use std::collections::HashSet;
+
+    /// The floor at zero height with the walls of the full height.
+    struct Floor(HashSet<(i64, i64)>);
+
+    impl Space for Floor {
+        fn get(&self, pn: Point) -> Pass {
+            let (x, y, z) = pn.absolute_point();
+            if y == 0 || self.0.contains(&(x, z)) {
+                Pass::solid()
+            } else {
+                Pass::empty()
+            }
+        }
+
+        fn column(&self, pn: Point, height: Height) -> Column<'_, Pass> {
+            const EMPTY: [Pass; Height::HEIGHT as usize] = [Pass::empty(); Height::HEIGHT as usize];
+            const SOLID: [Pass; Height::HEIGHT as usize] = [Pass::solid(); Height::HEIGHT as usize];
+
+            let (x, _, z) = pn.absolute_point();
+            let column = if self.0.contains(&(x, z)) {
+                &SOLID
+            } else {
+                &EMPTY
+            };
+
+            Column(&column[..height.get() as usize], &[])
+        }
+    }
+
+    fn point(x: i64, z: i64) -> Point {
+        Point::from_absolute(x, 1, z).unwrap()
+    }
+
+    #[test]
+    fn playback() {
+        let moves = [
+            (Action::Stay, point(0, 0)),
+            (Action::Step(Rotation::Q0), point(0, 1)),
+            (Action::JumpOver(Rotation::Q0), point(0, 3)),
+        ];
+
+        let trajectory = Trajectory::new(moves, 0.5);
+        assert_eq!(trajectory.segments().len(), 2);
+        assert_eq!(trajectory.segments()[1].motion, Motion::Jump);
+        assert_eq!(trajectory.duration(), 2.);
+        assert_eq!(trajectory.at(0.), Some(center(point(0, 0))));
+        assert_eq!(trajectory.at(0.5), Some(center(point(0, 1))));
+
+        // The jump up costs its height
+        let up = Point::from_absolute(0, 3, 2).unwrap();
+        let jump = Trajectory::new([moves[1], (Action::JumpUp(Rotation::Q0), up)], 1.);
+        assert_eq!(jump.duration(), 2.);
+
+        // It clears the edge of the higher block
+        let edge = jump.at(1.).unwrap();
+        assert_eq!(edge.z, 2.);
+        assert!(edge.y > Vec3::from(up).y);
+        assert_eq!(trajectory.at(5.), Some(center(point(0, 3))));
+
+        // The top of the jump arc
+        let top = trajectory.at(1.25).unwrap();
+        assert_eq!(top, center(point(0, 2)) + Vec3::new(0., JUMP_ARC, 0.));
+        assert_eq!(Trajectory::new([], 1.).at(0.), None);
+    }
+
+    #[test]
+    fn smooth() {
+        let moves = [
+            (Action::Stay, point(0, 0)),
+            (Action::Step(Rotation::Q1), point(1, 0)),
+            (Action::Step(Rotation::Q0), point(1, 1)),
+            (Action::Step(Rotation::Q1), point(2, 1)),
+            (Action::Step(Rotation::Q0), point(2, 2)),
+        ];
+
+        let height = Height::new(2).unwrap();
+        let mut trajectory = Trajectory::new(moves, 1.);
+        trajectory.smooth(height, &Floor(HashSet::default()));
+        assert_eq!(trajectory.segments().len(), 1);
+        assert!((trajectory.duration() - 8f32.sqrt()).abs() < 1e-5);
+
+        // The wall at the corner keeps the first turn
+        let mut trajectory = Trajectory::new(moves, 1.);
+        trajectory.smooth(height, &Floor([(0, 1)].into_iter().collect()));
+        let ends: Vec<_> = trajectory
+            .segments()
+            .iter()
+            .map(|seg| (seg.from, seg.to))
+            .collect();
+        assert_eq!(
+            ends,
+            [(point(0, 0), point(1, 0)), (point(1, 0), point(2, 2))]
+        );
+    }
+
+    #[test]
+    fn wait() {
+        let moves = [
+            (Action::Stay, point(0, 0)),
+            (Action::Step(Rotation::Q0), point(0, 1)),
+            (Action::Stay, point(0, 1)),
+            (Action::Stay, point(0, 1)),
+            (Action::Step(Rotation::Q0), point(0, 2)),
+        ];
+
+        let height = Height::new(2).unwrap();
+        let mut trajectory = Trajectory::new(moves, 0.5);
+        assert_eq!(trajectory.duration(), 2.);
+
+        // The steps around the waits stay apart
+        trajectory.smooth(height, &Floor(HashSet::default()));
+        let motions: Vec<_> = trajectory.segments().iter().map(|seg| seg.motion).collect();
+        assert_eq!(
+            motions,
+            [Motion::Walk, Motion::Wait, Motion::Wait, Motion::Walk]
+        );
+        assert_eq!(trajectory.duration(), 2.);
+        assert_eq!(trajectory.at(0.75), Some(center(point(0, 1))));
+    }
+}
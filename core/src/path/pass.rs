@@ -19,8 +19,10 @@ impl error::Error for ParseError {}
 
 /// Pass layout.
 ///
-/// Layout: 00abcdls
+/// Layout: wkabcdls
 /// where
+///     w: liquid
+///     k: climbable
 ///     a: ascent from Q0
 ///     b: ascent from Q1
 ///     c: ascent from Q2
@@ -48,6 +50,14 @@ impl Pass {
         Self(0b11)
     }
 
+    pub const fn liquid() -> Self {
+        Self(0b1000_0000)
+    }
+
+    pub const fn climbable() -> Self {
+        Self(0b0100_0001)
+    }
+
     pub fn ascent<R>(rotations: R) -> Self
     where
         R: IntoIterator<Item = Rotation>,
@@ -71,6 +81,14 @@ impl Pass {
         self.0 & 0b11 == 0b01
     }
 
+    pub const fn is_liquid(self) -> bool {
+        self.0 & 0b1000_0000 != 0
+    }
+
+    pub const fn is_climbable(self) -> bool {
+        self.0 & 0b0100_0001 == 0b0100_0001
+    }
+
     pub const fn ascent_from(self, rotation: Rotation) -> bool {
         self.0 & (1 << (rotation as u8 + 2)) != 0
     }
@@ -93,6 +111,8 @@ impl TryFrom<PassFrom<'_>> for Pass {
                 "solid" => Ok(Self::solid()),
                 "lift" => Ok(Self::lift()),
                 "pathless" => Ok(Self::pathless()),
+                "liquid" => Ok(Self::liquid()),
+                "climbable" => Ok(Self::climbable()),
                 _ => Err(ParseError::String(str.into())),
             },
             PassFrom::Rotations(rotations) => Ok(Self::ascent(rotations)),
@@ -0,0 +1,25 @@
+use crate::{
+    path::{Jumper, Merge, Pedestrian},
+    prelude::*,
+};
+
+/// The walker of the players.
+///
+/// Shared by the client predicting the moves and the server validating them.
+pub type PlayerWalk = Merge<(Pedestrian, Jumper)>;
+
+/// Makes the walker of the players.
+pub fn player() -> PlayerWalk {
+    let pedestrian = || Pedestrian {
+        height: Height::new(2).unwrap(),
+        jump_down: Height::new(4).unwrap(),
+    };
+
+    Merge((
+        pedestrian(),
+        Jumper {
+            walk: pedestrian(),
+            jump: Height::new(3).unwrap(),
+        },
+    ))
+}
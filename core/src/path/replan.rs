@@ -0,0 +1,446 @@
+use crate::{
+    height::Height,
+    path::{
+        action::Action,
+        finder::distance,
+        space::Space,
+        walk::{Close, Position, Walk},
+    },
+    point::Point,
+};
+use fxhash::FxHashMap as HashMap;
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+const INF: u32 = u32::MAX;
+
+struct Edge {
+    action: Action,
+    to: usize,
+    cost: u32,
+}
+
+struct Node {
+    pn: Point,
+    /// The cost of the start to the point by the last expansion.
+    g: u32,
+    /// The cost of the start to the point by the best predecessor.
+    rhs: u32,
+    /// The edges of the walk, `None` until the node is expanded.
+    succs: Option<Vec<Edge>>,
+    preds: Vec<usize>,
+}
+
+struct Collect(Vec<(Action, Position)>);
+
+impl Close for Collect {
+    fn close(&mut self, action: Action, pos: Position) {
+        self.0.push((action, pos));
+    }
+}
+
+/// Plans the cheapest path from the start to the goal
+/// and repairs it when the space changes (Lifelong Planning A*).
+///
+/// Unlike `PathFinder` it keeps the search between the calls,
+/// so after a few points are changed only the affected part is searched again.
+pub struct Replanner {
+    nodes: Vec<Node>,
+    index: HashMap<Point, usize>,
+    heap: BinaryHeap<Reverse<((u32, u32), usize)>>,
+    goal: usize,
+    cap: u32,
+    radius: u32,
+}
+
+impl Replanner {
+    const START: usize = 0;
+
+    /// Creates the planner from the start to the goal.
+    ///
+    /// The value of the start position caps the cost of the path.
+    /// The `radius` is how far from its position on the horizontal axes
+    /// the walker looks at the space, it's 2 for the stock walkers.
+    pub fn new(start: Position, goal: Point, radius: u32) -> Self {
+        let mut planner = Self {
+            nodes: Vec::default(),
+            index: HashMap::default(),
+            heap: BinaryHeap::default(),
+            goal: Self::START,
+            cap: start.value,
+            radius,
+        };
+
+        let start = planner.node(start.pn);
+        planner.goal = planner.node(goal);
+        planner.nodes[start].rhs = 0;
+        planner.push(start);
+        planner
+    }
+
+    /// Plans the path.
+    ///
+    /// Returns the cost of the path, then `path` lists it.
+    pub fn plan<W, S>(&mut self, walk: &W, space: &S) -> Option<u32>
+    where
+        W: Walk<S>,
+        S: Space,
+    {
+        while let Some(&Reverse((key, idx))) = self.heap.peek() {
+            let goal = &self.nodes[self.goal];
+            if key >= self.key(self.goal) && goal.g == goal.rhs {
+                break;
+            }
+
+            self.heap.pop();
+            let node = &self.nodes[idx];
+            if node.g == node.rhs || self.key(idx) != key {
+                // The entry is stale
+                continue;
+            }
+
+            if node.g > node.rhs {
+                let rhs = node.rhs;
+                self.nodes[idx].g = rhs;
+                for to in self.expand(idx, walk, space) {
+                    self.update(to);
+                }
+            } else {
+                self.nodes[idx].g = INF;
+                self.update(idx);
+                for to in self.expand(idx, walk, space) {
+                    self.update(to);
+                }
+            }
+        }
+
+        self.cost()
+    }
+
+    /// Repairs the search after the `changed` points of the space
+    /// and plans the path again.
+    pub fn replan<W, S, I>(&mut self, changed: I, walk: &W, space: &S) -> Option<u32>
+    where
+        W: Walk<S>,
+        S: Space,
+        I: IntoIterator<Item = Point>,
+    {
+        let changed: Vec<_> = changed.into_iter().map(Point::absolute_point).collect();
+        let affected: Vec<_> = (0..self.nodes.len())
+            .filter(|&idx| {
+                let node = &self.nodes[idx];
+                let (x, y, z) = node.pn.absolute_point();
+                node.succs.is_some()
+                    && changed.iter().any(|&(cx, cy, cz)| {
+                        x.abs_diff(cx).max(z.abs_diff(cz)) <= self.radius as u64
+                            && y.abs_diff(cy) <= Height::HEIGHT as u64
+                    })
+            })
+            .collect();
+
+        for idx in affected {
+            let old = self.nodes[idx].succs.take().unwrap_or_default();
+            for edge in &old {
+                self.nodes[edge.to].preds.retain(|&pred| pred != idx);
+            }
+
+            let new = self.expand(idx, walk, space);
+            for to in old.into_iter().map(|edge| edge.to).chain(new) {
+                self.update(to);
+            }
+        }
+
+        self.plan(walk, space)
+    }
+
+    /// Returns the cost of the planned path.
+    pub fn cost(&self) -> Option<u32> {
+        let goal = &self.nodes[self.goal];
+        (goal.g != INF && goal.g == goal.rhs).then_some(goal.g)
+    }
+
+    /// Lists the planned path from the goal back to the start, like `Path::to`.
+    pub fn path(&self) -> Vec<(Action, Point)> {
+        let mut path = Vec::new();
+        if self.cost().is_none() {
+            return path;
+        }
+
+        let mut idx = self.goal;
+        while idx != Self::START {
+            let g = self.nodes[idx].g;
+            let (action, pred) = self
+                .edges_to(idx)
+                .find(|&(pred, edge)| self.nodes[pred].g.saturating_add(edge.cost) == g)
+                .map(|(pred, edge)| (edge.action, pred))
+                .expect("consistent node has the predecessor");
+
+            path.push((action, self.nodes[idx].pn));
+            idx = pred;
+        }
+
+        path.push((Action::Stay, self.nodes[Self::START].pn));
+        path
+    }
+
+    /// Checks the planned path is still walkable with the same cost.
+    ///
+    /// It only runs the actions of the path, so it's cheap to call
+    /// every time the space is changed before `replan`.
+    pub fn is_valid<W, S>(&self, walk: &W, space: &S) -> bool
+    where
+        W: Walk<S>,
+        S: Space,
+    {
+        let path = self.path();
+        if path.is_empty() {
+            return false;
+        }
+
+        let mut g = 0;
+        path.windows(2).rev().all(|pair| {
+            let [(_, from), (action, to)] = [pair[1], pair[0]];
+            let pos = Position {
+                pn: from,
+                value: INF,
+                heading: None,
+            };
+
+            match walk.run(space, pos, action) {
+                Some(pos) if pos.pn == to => {
+                    g += INF - pos.value;
+                    g == self.nodes[self.index[&to]].g
+                }
+                _ => false,
+            }
+        })
+    }
+
+    fn node(&mut self, pn: Point) -> usize {
+        let nodes = &mut self.nodes;
+        *self.index.entry(pn).or_insert_with(|| {
+            nodes.push(Node {
+                pn,
+                g: INF,
+                rhs: INF,
+                succs: None,
+                preds: Vec::default(),
+            });
+            nodes.len() - 1
+        })
+    }
+
+    fn key(&self, idx: usize) -> (u32, u32) {
+        let node = &self.nodes[idx];
+        let g = node.g.min(node.rhs);
+        let h = distance(node.pn, self.nodes[self.goal].pn);
+        (g.saturating_add(h), g)
+    }
+
+    fn push(&mut self, idx: usize) {
+        self.heap.push(Reverse((self.key(idx), idx)));
+    }
+
+    /// Iterates over the edges to the node with their predecessors.
+    ///
+    /// The goal can only be reached with the final action.
+    fn edges_to(&self, idx: usize) -> impl Iterator<Item = (usize, &Edge)> {
+        let goal = idx == self.goal;
+        self.nodes[idx].preds.iter().flat_map(move |&pred| {
+            self.nodes[pred]
+                .succs
+                .iter()
+                .flatten()
+                .filter(move |edge| edge.to == idx && (!goal || edge.action.is_final()))
+                .map(move |edge| (pred, edge))
+        })
+    }
+
+    /// Recalculates the `rhs` of the node and queues it if it's inconsistent.
+    fn update(&mut self, idx: usize) {
+        if idx != Self::START {
+            let rhs = self
+                .edges_to(idx)
+                .map(|(pred, edge)| self.nodes[pred].g.saturating_add(edge.cost))
+                .min()
+                .filter(|&rhs| rhs <= self.cap)
+                .unwrap_or(INF);
+
+            self.nodes[idx].rhs = rhs;
+        }
+
+        let node = &self.nodes[idx];
+        if node.g != node.rhs {
+            self.push(idx);
+        }
+    }
+
+    /// Walks from the node if it isn't yet and returns its successors.
+    fn expand<W, S>(&mut self, idx: usize, walk: &W, space: &S) -> Vec<usize>
+    where
+        W: Walk<S>,
+        S: Space,
+    {
+        if let Some(succs) = &self.nodes[idx].succs {
+            return succs.iter().map(|edge| edge.to).collect();
+        }
+
+        let pos = Position {
+            pn: self.nodes[idx].pn,
+            value: INF,
+            heading: None,
+        };
+
+        let mut collect = Collect(Vec::new());
+        walk.walk(space, pos, &mut collect);
+        let mut succs = Vec::with_capacity(collect.0.len());
+        for (action, pos) in collect.0 {
+            let to = self.node(pos.pn);
+            if !self.nodes[to].preds.contains(&idx) {
+                self.nodes[to].preds.push(idx);
+            }
+
+            succs.push(Edge {
+                action,
+                to,
+                cost: INF - pos.value,
+            });
+        }
+
+        let tos = succs.iter().map(|edge| edge.to).collect();
+        self.nodes[idx].succs = Some(succs);
+        tos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{map::Column, path::Pass, path::PathFinder, rotation::Rotation};
+    use proptest::prelude::*;
+    use std::collections::HashSet;
+
+    /// The flat floor at zero height with the walls.
+    struct Walls(HashSet<(i64, i64)>);
+
+    impl Space for Walls {
+        fn get(&self, pn: Point) -> Pass {
+            match pn.absolute_point() {
+                (x, 0, z) if !self.0.contains(&(x, z)) => Pass::empty(),
+                _ => Pass::solid(),
+            }
+        }
+
+        fn column(&self, _: Point, _: Height) -> Column<'_, Pass> {
+            unreachable!()
+        }
+    }
+
+    struct Grid;
+
+    impl Walk<Walls> for Grid {
+        fn walk<C>(&self, space: &Walls, pos: Position, close: &mut C)
+        where
+            C: Close,
+        {
+            for rotation in [Rotation::Q0, Rotation::Q1, Rotation::Q2, Rotation::Q3] {
+                let action = Action::Step(rotation);
+                if let Some(pos) = self.run(space, pos, action) {
+                    close.close(action, pos);
+                }
+            }
+        }
+
+        fn run(&self, space: &Walls, pos: Position, action: Action) -> Option<Position> {
+            let value = pos.value.checked_sub(action.cost()?)?;
+            let pn = match action {
+                Action::Step(rotation) if !space.get(pos.pn).is_solid() => pos.pn.to(rotation),
+                _ => return None,
+            };
+
+            (!space.get(pn).is_solid()).then_some(Position { pn, value, ..pos })
+        }
+    }
+
+    fn point(x: i64, z: i64) -> Point {
+        Point::from_absolute(x, 0, z).unwrap()
+    }
+
+    const CAP: u32 = 60;
+
+    fn start() -> Position {
+        Position {
+            pn: point(0, 0),
+            value: CAP,
+            heading: None,
+        }
+    }
+
+    #[test]
+    fn replan() {
+        let goal = point(6, 0);
+        let mut walls: HashSet<_> = HashSet::default();
+        let mut planner = Replanner::new(start(), goal, 1);
+        assert_eq!(planner.plan(&Grid, &Walls(walls.clone())), Some(6));
+        assert_eq!(planner.path().len(), 7);
+        assert!(planner.is_valid(&Grid, &Walls(walls.clone())));
+
+        // Block the straight way
+        walls.extend([(3, -1), (3, 0), (3, 1)]);
+        let space = Walls(walls.clone());
+        assert!(!planner.is_valid(&Grid, &space));
+        let changed = [point(3, -1), point(3, 0), point(3, 1)];
+        assert_eq!(planner.replan(changed, &Grid, &space), Some(10));
+        assert!(planner.is_valid(&Grid, &space));
+        let path = planner.path();
+        assert_eq!(path.first().map(|&(_, pn)| pn), Some(goal));
+        assert_eq!(path.last(), Some(&(Action::Stay, point(0, 0))));
+
+        // Open it again
+        walls.remove(&(3, 0));
+        let space = Walls(walls);
+        assert!(planner.is_valid(&Grid, &space));
+        assert_eq!(planner.replan([point(3, 0)], &Grid, &space), Some(6));
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn same_cost(
+            walls in prop::collection::hash_set((-8..16i64, -8..16i64), 0..100),
+            changes in prop::collection::vec((-8..16i64, -8..16i64), 1..20),
+            goal in (-8..16i64, -8..16i64),
+        ) {
+            let mut walls: HashSet<_> = walls
+                .into_iter()
+                .filter(|&wall| wall != (0, 0) && wall != goal)
+                .collect();
+            let goal = point(goal.0, goal.1);
+
+            let mut pf = PathFinder::new();
+            let mut planner = Replanner::new(start(), goal, 1);
+            let space = Walls(walls.clone());
+            let expected = pf.find_to(start(), goal, &Grid, &space);
+            prop_assert_eq!(planner.plan(&Grid, &space), expected);
+
+            // Toggle the walls one by one
+            for (x, z) in changes {
+                if (x, z) == (0, 0) || point(x, z) == goal {
+                    continue;
+                }
+
+                if !walls.remove(&(x, z)) {
+                    walls.insert((x, z));
+                }
+
+                let space = Walls(walls.clone());
+                let expected = pf.find_to(start(), goal, &Grid, &space);
+                prop_assert_eq!(planner.replan([point(x, z)], &Grid, &space), expected);
+                if expected.is_some() {
+                    prop_assert!(planner.is_valid(&Grid, &space));
+                    prop_assert_eq!(planner.path().len() as u32, expected.unwrap() + 1);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::{
+    height::Height,
+    map::Column,
+    path::{Pass, Space},
+    point::Point,
+    side::Side,
+};
+use fxhash::FxHashMap as HashMap;
+use std::ops::Range;
+
+/// The cells the agents occupy over time.
+#[derive(Default)]
+pub struct Reservations {
+    cells: HashMap<(Point, u32), u32>,
+    /// The cells where the agents stay since the tick.
+    parked: HashMap<Point, (u32, u32)>,
+    /// The reserved cells of every agent.
+    agents: HashMap<u32, Vec<(Point, Range<u32>)>>,
+}
+
+impl Reservations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reserves the cell for the agent during the ticks.
+    ///
+    /// Overrides the reservations of other agents.
+    pub fn reserve(&mut self, agent: u32, pn: Point, ticks: Range<u32>) {
+        for tick in ticks.clone() {
+            self.cells.insert((pn, tick), agent);
+        }
+
+        self.agents.entry(agent).or_default().push((pn, ticks));
+    }
+
+    /// Reserves the cell for the agent since the tick.
+    pub fn park(&mut self, agent: u32, pn: Point, since: u32) {
+        self.parked.insert(pn, (since, agent));
+    }
+
+    /// Returns the agent occupying the cell at the tick.
+    pub fn holder(&self, pn: Point, tick: u32) -> Option<u32> {
+        if let Some(&agent) = self.cells.get(&(pn, tick)) {
+            return Some(agent);
+        }
+
+        match self.parked.get(&pn) {
+            Some(&(since, agent)) if since <= tick => Some(agent),
+            _ => None,
+        }
+    }
+
+    /// Checks the cell isn't occupied by other agents at the tick.
+    pub fn is_free(&self, agent: u32, pn: Point, tick: u32) -> bool {
+        self.holder(pn, tick).is_none_or(|holder| holder == agent)
+    }
+
+    /// Drops all reservations of the agent.
+    pub fn release(&mut self, agent: u32) {
+        for (pn, ticks) in self.agents.remove(&agent).unwrap_or_default() {
+            for tick in ticks {
+                if self.cells.get(&(pn, tick)) == Some(&agent) {
+                    self.cells.remove(&(pn, tick));
+                }
+            }
+        }
+
+        self.parked.retain(|_, &mut (_, holder)| holder != agent);
+    }
+
+    /// Drops the reservations before the tick.
+    pub fn forget(&mut self, before: u32) {
+        self.cells.retain(|&(_, tick), _| tick >= before);
+        for cells in self.agents.values_mut() {
+            cells.retain(|(_, ticks)| ticks.end > before);
+        }
+    }
+}
+
+/// The space where the cells occupied by other agents at the tick are blocked.
+///
+/// A column crossing an occupied cell is reported as pathless as a whole.
+pub struct Reserved<'a, S> {
+    pub space: &'a S,
+    pub table: &'a Reservations,
+    pub agent: u32,
+    pub tick: u32,
+}
+
+impl<S> Reserved<'_, S> {
+    fn is_free(&self, pn: Point) -> bool {
+        self.table.is_free(self.agent, pn, self.tick)
+    }
+}
+
+impl<S: Space> Space for Reserved<'_, S> {
+    fn get(&self, pn: Point) -> Pass {
+        if self.is_free(pn) {
+            self.space.get(pn)
+        } else {
+            Pass::pathless()
+        }
+    }
+
+    fn column(&self, pn: Point, height: Height) -> Column<'_, Pass> {
+        const PATHLESS: [Pass; Height::HEIGHT as usize] =
+            [Pass::pathless(); Height::HEIGHT as usize];
+
+        let mut cell = pn;
+        for _ in 0..height.get() {
+            if !self.is_free(cell) {
+                return Column(&PATHLESS[..height.get() as usize], &[]);
+            }
+
+            cell = cell.to(Side::Up);
+        }
+
+        self.space.column(pn, height)
+    }
+}
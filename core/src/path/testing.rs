@@ -0,0 +1,236 @@
+use crate::{
+    chunk::Chunk,
+    height::Height,
+    map::{Column, Map},
+    path::{Action, Close, Pass, PathFinder, Position, Space, Walk},
+    point::Point,
+    rotation::Rotation,
+    side::Side,
+};
+use fxhash::FxHashMap as HashMap;
+use proptest::prelude::*;
+
+/// The space with nothing in it.
+pub(crate) struct Void;
+
+impl Space for Void {
+    fn get(&self, _: Point) -> Pass {
+        Pass::empty()
+    }
+
+    fn column(&self, _: Point, _: Height) -> Column<'_, Pass> {
+        unreachable!()
+    }
+}
+
+struct Passes(Chunk<Pass>);
+
+impl Default for Passes {
+    fn default() -> Self {
+        Self(Chunk::filled(Pass::empty()))
+    }
+}
+
+impl AsRef<Chunk<Pass>> for Passes {
+    fn as_ref(&self) -> &Chunk<Pass> {
+        &self.0
+    }
+}
+
+impl AsMut<Chunk<Pass>> for Passes {
+    fn as_mut(&mut self) -> &mut Chunk<Pass> {
+        &mut self.0
+    }
+}
+
+/// Random ground with ladders and slopes.
+pub(crate) struct Terrain(Map<Passes>);
+
+impl Terrain {
+    pub(crate) const SIDE: i64 = 6;
+    pub(crate) const FLOOR: i64 = 8;
+
+    /// Makes the terrain from `(height, kind)` of every column.
+    pub(crate) fn new(columns: &[(u8, u8)]) -> Self {
+        let mut map = Map::default();
+        for (i, &(height, kind)) in columns.iter().enumerate() {
+            let (x, z) = (i as i64 % Self::SIDE, i as i64 / Self::SIDE);
+            let top = Self::FLOOR + height as i64;
+            for y in 0..top {
+                *map.get_mut(Point::from_absolute(x, y, z).unwrap()) = Pass::solid();
+            }
+
+            let top = Point::from_absolute(x, top, z).unwrap();
+            match kind {
+                0..=3 => {}
+                4 => {
+                    let mut pn = top;
+                    for _ in 0..4 {
+                        *map.get_mut(pn) = Pass::lift();
+                        pn = pn.to(Side::Up);
+                    }
+                }
+                _ => {
+                    let rotations = (0..4)
+                        .filter(|i| kind >> i & 1 != 0)
+                        .map(|i| Rotation::from_quarters(i).unwrap());
+                    *map.get_mut(top.to(Side::Down)) = Pass::ascent(rotations);
+                }
+            }
+        }
+
+        Self(map)
+    }
+
+    /// Makes the solid cells climbable.
+    pub(crate) fn climbable(mut self) -> Self {
+        for (x, z) in (0..Self::SIDE).flat_map(|x| (0..Self::SIDE).map(move |z| (x, z))) {
+            for y in 0..Self::FLOOR + 6 {
+                let pass = self.0.get_mut(Point::from_absolute(x, y, z).unwrap());
+                if pass.is_solid() {
+                    *pass = Pass::climbable();
+                }
+            }
+        }
+
+        self
+    }
+
+    /// Fills the empty cells with the liquid up to the level.
+    pub(crate) fn flooded(mut self, level: u8) -> Self {
+        for (x, z) in (0..Self::SIDE).flat_map(|x| (0..Self::SIDE).map(move |z| (x, z))) {
+            for y in Self::FLOOR..Self::FLOOR + level as i64 {
+                let pass = self.0.get_mut(Point::from_absolute(x, y, z).unwrap());
+                if !pass.is_solid() && !pass.is_lift() {
+                    *pass = Pass::liquid();
+                }
+            }
+        }
+
+        self
+    }
+
+    /// Makes the flat floor with the walls marked by `#`.
+    pub(crate) fn from_rows(rows: &[&str]) -> Self {
+        let mut map = Map::default();
+        for (z, row) in rows.iter().enumerate() {
+            for (x, cell) in row.chars().enumerate() {
+                let top = Self::FLOOR + if cell == '#' { 5 } else { 0 };
+                for y in 0..top {
+                    *map.get_mut(Point::from_absolute(x as i64, y, z as i64).unwrap()) =
+                        Pass::solid();
+                }
+            }
+        }
+
+        Self(map)
+    }
+
+    pub(crate) fn start(&self, columns: &[(u8, u8)]) -> Point {
+        Point::from_absolute(0, Self::FLOOR + columns[0].0 as i64, 0).unwrap()
+    }
+}
+
+impl Space for Terrain {
+    fn get(&self, pn: Point) -> Pass {
+        self.0.get(pn).copied().unwrap_or_else(Pass::empty)
+    }
+
+    fn column(&self, pn: Point, height: Height) -> Column<'_, Pass> {
+        const EMPTY: [Pass; Height::HEIGHT as usize] = [Pass::empty(); Height::HEIGHT as usize];
+
+        self.0
+            .column(pn, height)
+            .unwrap_or_else(|| Column(&EMPTY[..height.get() as usize], &[]))
+    }
+}
+
+pub(crate) struct Collect(pub(crate) Vec<(Action, Position)>);
+
+impl Close for Collect {
+    fn close(&mut self, action: Action, pos: Position) {
+        self.0.push((action, pos));
+    }
+}
+
+/// The most value left at every reachable point,
+/// found by relaxing all positions until nothing changes.
+fn reference<W, S>(pos: Position, walk: &W, space: &S) -> HashMap<Point, u32>
+where
+    W: Walk<S>,
+    S: Space,
+{
+    let mut best = HashMap::default();
+    best.insert(pos.pn, pos.value);
+    loop {
+        let mut changed = false;
+        for (pn, value) in best.clone() {
+            let mut collect = Collect(Vec::new());
+            walk.walk(
+                space,
+                Position {
+                    pn,
+                    value,
+                    heading: None,
+                },
+                &mut collect,
+            );
+            for (_, pos) in collect.0 {
+                let better = best.get(&pos.pn).is_none_or(|&old| pos.value > old);
+                if pos.value != 0 && better {
+                    best.insert(pos.pn, pos.value);
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            break best;
+        }
+    }
+}
+
+pub(crate) fn check<W, S>(pos: Position, walk: &W, space: &S) -> Result<(), TestCaseError>
+where
+    W: Walk<S>,
+    S: Space,
+{
+    let mut pf = PathFinder::new();
+    pf.find_cheapest(pos, walk, space);
+    let expected = reference(pos, walk, space);
+    let found: HashMap<_, _> = pf.closed().collect();
+    prop_assert_eq!(&found, &expected);
+
+    // Every path can be walked and costs what was found
+    for (&pn, &value) in &found {
+        let path: Vec<_> = pf.path().to(pn).collect();
+        let mut cur = pos;
+        for &(action, next) in path.iter().rev().skip(1) {
+            let mut collect = Collect(Vec::new());
+            walk.walk(space, cur, &mut collect);
+            let step = collect
+                .0
+                .into_iter()
+                .find(|&(a, pos)| a == action && pos.pn == next);
+            prop_assert!(step.is_some());
+            cur = step.unwrap().1;
+        }
+
+        prop_assert_eq!(
+            cur,
+            Position {
+                pn,
+                value,
+                heading: None
+            }
+        );
+    }
+
+    Ok(())
+}
+
+/// Random columns of the terrain.
+pub(crate) fn terrain() -> impl Strategy<Value = Vec<(u8, u8)>> {
+    let len = (Terrain::SIDE * Terrain::SIDE) as usize;
+    prop::collection::vec((0..6u8, 0..20u8), len)
+}
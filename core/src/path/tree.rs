@@ -1,6 +1,6 @@
 use std::ops;
 
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Copy, Clone, Eq, Ord, PartialEq, PartialOrd)]
 pub(crate) struct NodePtr(u32);
 
 impl NodePtr {
@@ -48,7 +48,7 @@ impl<T> Tree<T> {
         &self.nodes[ptr.0 as usize]
     }
 
-    pub fn list(&self, ptr: NodePtr) -> List<T> {
+    pub fn list(&self, ptr: NodePtr) -> List<'_, T> {
         List { tree: self, ptr }
     }
 
@@ -113,9 +113,9 @@ mod tests {
         let i1 = tree.push(i0, 1);
         let i2 = tree.push(i0, 2);
 
-        let vals: Vec<_> = tree.list(i1).into_iter().map(Node::value).collect();
+        let vals: Vec<_> = tree.list(i1).map(Node::value).collect();
         assert_eq!(vals, [&1, &0]);
-        let vals: Vec<_> = tree.list(i2).into_iter().map(Node::value).collect();
+        let vals: Vec<_> = tree.list(i2).map(Node::value).collect();
         assert_eq!(vals, [&2, &0]);
     }
 }
@@ -0,0 +1,123 @@
+use crate::{
+    path::{Action, Close, Pass, Pedestrian, Position, Space, Walk},
+    point::Point,
+    rotation::Rotation,
+    side::Side,
+};
+
+/// Climbs the walls, any solid face holds the agent
+/// unless `only_marked` is set, then only the climbable ones.
+pub struct Climber {
+    pub walk: Pedestrian,
+    pub only_marked: bool,
+}
+
+impl Climber {
+    fn grips(&self, pass: Pass) -> bool {
+        match self.only_marked {
+            true => pass.is_climbable(),
+            false => pass.is_solid(),
+        }
+    }
+
+    /// Checks there is a wall face to hold beside the point.
+    fn holds<S: Space>(&self, space: &S, pn: Point) -> bool {
+        [Rotation::Q0, Rotation::Q1, Rotation::Q2, Rotation::Q3]
+            .into_iter()
+            .any(|rotation| self.grips(space.get(pn.to(rotation))))
+    }
+}
+
+impl<S: Space> Walk<S> for Climber {
+    fn walk<C>(&self, space: &S, pos: Position, close: &mut C)
+    where
+        C: Close,
+    {
+        self.walk.walk(space, pos, close);
+
+        for side in Side::ENUM {
+            let action = Action::Climb(side);
+            if let Some(pos) = self.run(space, pos, action) {
+                close.close(action, pos);
+            }
+        }
+    }
+
+    fn run(&self, space: &S, pos: Position, action: Action) -> Option<Position> {
+        match action {
+            Action::Climb(side) => {
+                let value = pos.value.checked_sub(action.cost().unwrap())?;
+                let target = pos.pn.to(side);
+                let holds = match side {
+                    Side::Up => self.holds(space, pos.pn),
+                    Side::Down => self.holds(space, target),
+                    // Moves along the wall or hangs over its edge
+                    _ => self.holds(space, target) || self.grips(space.get(pos.pn.to(Side::Down))),
+                };
+
+                (holds
+                    && space
+                        .column(target, self.walk.height)
+                        .iter()
+                        .all(|pass| !pass.is_solid()))
+                .then_some(Position {
+                    pn: target,
+                    value,
+                    ..pos
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        height::Height,
+        path::testing::{check, terrain, Terrain},
+    };
+    use proptest::prelude::*;
+
+    #[test]
+    fn climb() {
+        let wall = Terrain::from_rows(&["###", "#.#", "###"]);
+        let climbable = Terrain::from_rows(&["###", "#.#", "###"]).climbable();
+        let pos = Position {
+            pn: Point::from_absolute(1, Terrain::FLOOR, 1).unwrap(),
+            value: u32::MAX,
+            heading: None,
+        };
+
+        let climber = |only_marked| Climber {
+            walk: Pedestrian {
+                height: Height::new(2).unwrap(),
+                jump_down: Height::new(4).unwrap(),
+            },
+            only_marked,
+        };
+
+        // Any wall holds, unless the climbable ones are asked for
+        let up = Action::Climb(Side::Up);
+        assert!(climber(false).run(&wall, pos, up).is_some());
+        assert!(climber(true).run(&wall, pos, up).is_none());
+        assert!(climber(true).run(&climbable, pos, up).is_some());
+    }
+
+    proptest! {
+        #[test]
+        fn cheapest_climber(columns in terrain(), value in 1..8u32) {
+            let space = Terrain::new(&columns);
+            let walk = Climber {
+                walk: Pedestrian {
+                    height: Height::new(2).unwrap(),
+                    jump_down: Height::new(4).unwrap(),
+                },
+                only_marked: false,
+            };
+
+            check(Position { pn: space.start(&columns), value, heading: None }, &walk, &space)?;
+        }
+    }
+}
@@ -66,9 +66,38 @@ impl<S: Space> Walk<S> for Flyer {
                     .column(target, self.walk.height)
                     .iter()
                     .all(|pass| !pass.is_solid())
-                    .then(|| Position { pn: target, value })
+                    .then_some(Position {
+                        pn: target,
+                        value,
+                        ..pos
+                    })
             }
             _ => None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        height::Height,
+        path::testing::{check, terrain, Terrain},
+    };
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn cheapest_flyer(columns in terrain(), value in 1..6u32) {
+            let space = Terrain::new(&columns);
+            let walk = Flyer {
+                walk: Pedestrian {
+                    height: Height::new(2).unwrap(),
+                    jump_down: Height::new(4).unwrap(),
+                },
+            };
+
+            check(Position { pn: space.start(&columns), value, heading: None }, &walk, &space)?;
+        }
+    }
+}
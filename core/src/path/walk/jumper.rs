@@ -52,7 +52,11 @@ impl<S: Space> Walk<S> for Jumper {
                 let cost = height + 1;
                 let value = pos.value.checked_sub(cost)?;
 
-                Some(Position { pn: target, value })
+                Some(Position {
+                    pn: target,
+                    value,
+                    ..pos
+                })
             }
             Action::JumpOver(rotation) => {
                 let value = pos.value.checked_sub(action.cost().unwrap())?;
@@ -70,7 +74,11 @@ impl<S: Space> Walk<S> for Jumper {
                         0 => !pass.is_solid(),
                         _ => pass.is_solid(),
                     }))
-                .then(|| Position { pn: target, value })
+                .then_some(Position {
+                    pn: target,
+                    value,
+                    ..pos
+                })
             }
             _ => None,
         }
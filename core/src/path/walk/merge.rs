@@ -0,0 +1,113 @@
+use crate::path::{Action, Close, Position, Space, Walk};
+
+/// Walks with every walker of the tuple.
+///
+/// When several actions lead to the same point,
+/// only the one with the most value left is closed.
+/// The final and the passing actions are kept apart,
+/// since only the final ones can end the path.
+pub struct Merge<T>(pub T);
+
+struct Collect(Vec<(Action, Position)>);
+
+impl Close for Collect {
+    fn close(&mut self, action: Action, pos: Position) {
+        let key = |action: Action, pos: Position| (pos.pn, pos.heading, action.is_final());
+        let same = |&(old_action, old_pos): &(Action, Position)| {
+            key(old_action, old_pos) == key(action, pos)
+        };
+
+        match self.0.iter_mut().find(|old| same(old)) {
+            Some(old) if old.1.value < pos.value => *old = (action, pos),
+            Some(_) => {}
+            None => self.0.push((action, pos)),
+        }
+    }
+}
+
+macro_rules! impl_merge {
+    ($($w:ident . $n:tt),+) => {
+        impl<S, $($w),+> Walk<S> for Merge<($($w,)+)>
+        where
+            S: Space,
+            $($w: Walk<S>,)+
+        {
+            fn walk<C>(&self, space: &S, pos: Position, close: &mut C)
+            where
+                C: Close,
+            {
+                let mut collect = Collect(Vec::new());
+                $(self.0.$n.walk(space, pos, &mut collect);)+
+                for (action, pos) in collect.0 {
+                    close.close(action, pos);
+                }
+            }
+
+            fn run(&self, space: &S, pos: Position, action: Action) -> Option<Position> {
+                [$(self.0.$n.run(space, pos, action)),+]
+                    .into_iter()
+                    .flatten()
+                    .max_by_key(|pos| pos.value)
+            }
+        }
+    };
+}
+
+impl_merge!(W0.0, W1.1);
+impl_merge!(W0.0, W1.1, W2.2);
+impl_merge!(W0.0, W1.1, W2.2, W3.3);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        path::testing::{Collect, Void},
+        point::Point,
+        rotation::Rotation,
+    };
+
+    /// Reaches the next point with the only action.
+    struct Fixed(Action);
+
+    impl Walk<Void> for Fixed {
+        fn walk<C: Close>(&self, space: &Void, pos: Position, close: &mut C) {
+            if let Some(pos) = self.run(space, pos, self.0) {
+                close.close(self.0, pos);
+            }
+        }
+
+        fn run(&self, _: &Void, pos: Position, action: Action) -> Option<Position> {
+            let value = pos.value.checked_sub(action.cost()?)?;
+            (action == self.0).then_some(Position {
+                pn: pos.pn.to(Rotation::Q0),
+                value,
+                ..pos
+            })
+        }
+    }
+
+    #[test]
+    fn merge_final() {
+        let pos = Position {
+            pn: Point::from_absolute(0, 0, 0).unwrap(),
+            value: 10,
+            heading: None,
+        };
+
+        // The cheaper lift can't end the path, so the jump is kept too
+        let walk = Merge((Fixed(Action::LiftUp), Fixed(Action::JumpDown(Rotation::Q0))));
+        let mut collect = Collect(Vec::new());
+        walk.walk(&Void, pos, &mut collect);
+        let actions: Vec<_> = collect.0.iter().map(|&(action, _)| action).collect();
+        assert_eq!(actions, [Action::LiftUp, Action::JumpDown(Rotation::Q0)]);
+
+        let walk = Merge((
+            Fixed(Action::JumpDown(Rotation::Q0)),
+            Fixed(Action::Step(Rotation::Q0)),
+        ));
+        let mut collect = Collect(Vec::new());
+        walk.walk(&Void, pos, &mut collect);
+        assert_eq!(collect.0.len(), 1);
+        assert_eq!(collect.0[0].0, Action::Step(Rotation::Q0));
+    }
+}
@@ -1,11 +1,21 @@
+mod climber;
 mod flyer;
 mod jumper;
+mod merge;
 mod pedestrian;
+mod profiled;
+mod swimmer;
 mod this;
+mod wide;
 
 pub use self::{
+    climber::Climber,
     flyer::Flyer,
     jumper::Jumper,
+    merge::Merge,
     pedestrian::Pedestrian,
+    profiled::Profiled,
+    swimmer::Swimmer,
     this::{Close, Position, Walk},
+    wide::{Footprint, Wide},
 };
@@ -109,10 +109,36 @@ impl<S: Space> Walk<S> for Pedestrian {
 
                 (target, value)
             }
-            Action::Fall(_) => todo!(),
             _ => return None,
         };
 
-        Some(Position { pn: target, value })
+        Some(Position {
+            pn: target,
+            value,
+            ..pos
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        height::Height,
+        path::testing::{check, terrain, Terrain},
+    };
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn cheapest_pedestrian(columns in terrain(), value in 1..12u32) {
+            let space = Terrain::new(&columns);
+            let walk = Pedestrian {
+                height: Height::new(2).unwrap(),
+                jump_down: Height::new(4).unwrap(),
+            };
+
+            check(Position { pn: space.start(&columns), value, heading: None }, &walk, &space)?;
+        }
     }
 }
@@ -0,0 +1,111 @@
+use crate::path::{Action, Close, CostProfile, Position, Space, Walk};
+
+/// Walks with the walker, but pays the costs of the profile.
+pub struct Profiled<W> {
+    pub walk: W,
+    pub profile: CostProfile,
+}
+
+impl<W> Profiled<W> {
+    /// Prices the position reached by the walker from the unlimited value.
+    fn price(&self, from: Position, action: Action, to: Position) -> Option<Position> {
+        let spent = u32::MAX - to.value;
+        let value = from.value.checked_sub(self.profile.cost(action, spent)?)?;
+        Some(Position { value, ..to })
+    }
+}
+
+struct ProfiledCloser<'a, W, C> {
+    walk: &'a Profiled<W>,
+    close: &'a mut C,
+    from: Position,
+}
+
+impl<W, C: Close> Close for ProfiledCloser<'_, W, C> {
+    fn close(&mut self, action: Action, pos: Position) {
+        if let Some(pos) = self.walk.price(self.from, action, pos) {
+            self.close.close(action, pos);
+        }
+    }
+}
+
+impl<S, W> Walk<S> for Profiled<W>
+where
+    S: Space,
+    W: Walk<S>,
+{
+    fn walk<C>(&self, space: &S, pos: Position, close: &mut C)
+    where
+        C: Close,
+    {
+        let mut close = ProfiledCloser {
+            walk: self,
+            close,
+            from: pos,
+        };
+
+        let unlimited = Position {
+            value: u32::MAX,
+            ..pos
+        };
+
+        self.walk.walk(space, unlimited, &mut close);
+    }
+
+    fn run(&self, space: &S, pos: Position, action: Action) -> Option<Position> {
+        let unlimited = Position {
+            value: u32::MAX,
+            ..pos
+        };
+
+        let to = self.walk.run(space, unlimited, action)?;
+        self.price(pos, action, to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        height::Height,
+        path::{
+            testing::{check, terrain, Terrain},
+            Climber, Jumper, Merge, Pedestrian,
+        },
+    };
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn cheapest_profiled(columns in terrain(), value in 1..10u32) {
+            let space = Terrain::new(&columns).climbable();
+            let pedestrian = || Pedestrian {
+                height: Height::new(2).unwrap(),
+                jump_down: Height::new(4).unwrap(),
+            };
+
+            // The heavy agent pays more for jumps and climbs
+            let walk = Profiled {
+                walk: Merge((
+                    Jumper {
+                        walk: pedestrian(),
+                        jump: Height::new(3).unwrap(),
+                    },
+                    Climber {
+                        walk: pedestrian(),
+                        only_marked: true,
+                    },
+                )),
+                profile: CostProfile {
+                    jump_up: Some(2),
+                    jump_down: Some(3),
+                    climb: Some(4),
+                    fall: None,
+                    ..CostProfile::default()
+                },
+            };
+
+            check(Position { pn: space.start(&columns), value, heading: None }, &walk, &space)?;
+        }
+    }
+}
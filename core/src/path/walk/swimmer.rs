@@ -0,0 +1,72 @@
+use crate::{
+    path::{Action, Close, Pedestrian, Position, Space, Walk},
+    side::Side,
+};
+
+pub struct Swimmer {
+    pub walk: Pedestrian,
+}
+
+impl<S: Space> Walk<S> for Swimmer {
+    fn walk<C>(&self, space: &S, pos: Position, close: &mut C)
+    where
+        C: Close,
+    {
+        self.walk.walk(space, pos, close);
+
+        for side in Side::ENUM {
+            let action = Action::Swim(side);
+            if let Some(pos) = self.run(space, pos, action) {
+                close.close(action, pos);
+            }
+        }
+    }
+
+    fn run(&self, space: &S, pos: Position, action: Action) -> Option<Position> {
+        match action {
+            Action::Swim(side) => {
+                let value = pos.value.checked_sub(action.cost().unwrap())?;
+                let target = pos.pn.to(side);
+
+                // Swims in the liquid or out of it
+                let liquid = space.get(pos.pn).is_liquid() || space.get(target).is_liquid();
+                (liquid
+                    && space
+                        .column(target, self.walk.height)
+                        .iter()
+                        .all(|pass| !pass.is_solid()))
+                .then_some(Position {
+                    pn: target,
+                    value,
+                    ..pos
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        height::Height,
+        path::testing::{check, terrain, Terrain},
+    };
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn cheapest_swimmer(columns in terrain(), level in 0..6u8, value in 1..8u32) {
+            let space = Terrain::new(&columns).flooded(level);
+            let walk = Swimmer {
+                walk: Pedestrian {
+                    height: Height::new(2).unwrap(),
+                    jump_down: Height::new(4).unwrap(),
+                },
+            };
+
+            check(Position { pn: space.start(&columns), value, heading: None }, &walk, &space)?;
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
     path::{Action, Space},
     point::Point,
+    rotation::Rotation,
 };
 
 pub trait Close {
@@ -19,4 +20,6 @@ pub trait Walk<S: Space> {
 pub struct Position {
     pub pn: Point,
     pub value: u32,
+    /// Where the agent faces, only the walkers turning the agent keep it.
+    pub heading: Option<Rotation>,
 }
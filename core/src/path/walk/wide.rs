@@ -0,0 +1,290 @@
+use crate::{
+    height::Height,
+    path::{Action, Close, Position, Space, Walk},
+    point::Point,
+    rotation::Rotation,
+    side::Side,
+};
+
+/// The cells an agent occupies on the horizontal axes.
+///
+/// The footprint is centred on the point of the agent,
+/// its length lies along the heading and its width across it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Footprint {
+    width: u8,
+    length: u8,
+}
+
+impl Footprint {
+    /// The longest side, the offsets of the cells fit into `i8`.
+    pub const MAX: u8 = i8::MAX as u8;
+
+    pub const fn new(width: u8, length: u8) -> Option<Self> {
+        match (width, length) {
+            (1..=Self::MAX, 1..=Self::MAX) => Some(Self { width, length }),
+            _ => None,
+        }
+    }
+
+    pub const fn width(self) -> u8 {
+        self.width
+    }
+
+    pub const fn length(self) -> u8 {
+        self.length
+    }
+
+    /// Iterates over the points of the footprint facing the rotation.
+    fn points(self, pn: Point, rotation: Rotation) -> impl Iterator<Item = Point> {
+        let forth = Side::from(rotation);
+        let across = rotation.rotate(Side::Left);
+        let (length, width) = (self.length, self.width);
+        offsets(length)
+            .flat_map(move |f| offsets(width).map(move |a| shift(shift(pn, forth, f), across, a)))
+    }
+
+    /// Iterates over the points the agent sweeps turning in place.
+    fn turn(self, pn: Point) -> impl Iterator<Item = Point> {
+        let side = self.width.max(self.length);
+        Self {
+            width: side,
+            length: side,
+        }
+        .points(pn, Rotation::Q0)
+    }
+
+    const fn is_square(self) -> bool {
+        self.width == self.length
+    }
+}
+
+fn offsets(len: u8) -> impl Iterator<Item = i8> {
+    let lo = -((len as i8 - 1) / 2);
+    lo..lo + len as i8
+}
+
+fn shift(mut pn: Point, side: Side, n: i8) -> Point {
+    let side = if n < 0 { side.opposite() } else { side };
+    for _ in 0..n.unsigned_abs() {
+        pn = pn.to(side);
+    }
+
+    pn
+}
+
+/// The walker for the agents larger than one cell.
+///
+/// Checks the clearance of every column the footprint occupies.
+/// The heading is kept in the position, the agent changing it
+/// turns in place and needs the room for that. The start has no heading,
+/// the agent faces where it goes first.
+pub struct Wide<W> {
+    pub walk: W,
+    pub footprint: Footprint,
+    pub height: Height,
+}
+
+impl<W> Wide<W> {
+    fn is_clear<S, I>(&self, space: &S, mut points: I) -> bool
+    where
+        S: Space,
+        I: Iterator<Item = Point>,
+    {
+        points.all(|pn| {
+            space
+                .column(pn, self.height)
+                .iter()
+                .all(|pass| !pass.is_solid())
+        })
+    }
+
+    fn fits<S: Space>(&self, space: &S, pn: Point, rotation: Rotation) -> bool {
+        self.is_clear(space, self.footprint.points(pn, rotation))
+    }
+
+    /// Checks the agent can make the move, returns the heading after it.
+    fn can<S: Space>(
+        &self,
+        space: &S,
+        from: Position,
+        action: Action,
+        to: Point,
+    ) -> Option<Option<Rotation>> {
+        let rotation = match (heading(action), from.heading) {
+            (Some(rotation), _) => rotation,
+            (None, Some(heading)) => return self.fits(space, to, heading).then_some(Some(heading)),
+            (None, None) => {
+                return (self.fits(space, to, Rotation::Q0) || self.fits(space, to, Rotation::Q1))
+                    .then_some(None)
+            }
+        };
+
+        // The square agent turns within its footprint, so it keeps no heading
+        let square = self.footprint.is_square();
+        let turns = !square && from.heading.is_some_and(|heading| heading != rotation);
+        (self.fits(space, from.pn, rotation)
+            && self.fits(space, to, rotation)
+            && (!turns || self.is_clear(space, self.footprint.turn(from.pn))))
+        .then_some((!square).then_some(rotation))
+    }
+}
+
+/// The rotation the agent faces making the action.
+fn heading(action: Action) -> Option<Rotation> {
+    match action {
+        Action::Step(rotation)
+        | Action::JumpUp(rotation)
+        | Action::JumpDown(rotation)
+        | Action::JumpOver(rotation)
+        | Action::Fall(rotation) => Some(rotation),
+        Action::Fly(side) | Action::Climb(side) | Action::Swim(side) => Rotation::from_side(side),
+        _ => None,
+    }
+}
+
+struct WideCloser<'a, S, W, C> {
+    walk: &'a Wide<W>,
+    space: &'a S,
+    close: &'a mut C,
+    from: Position,
+}
+
+impl<S, W, C> Close for WideCloser<'_, S, W, C>
+where
+    S: Space,
+    C: Close,
+{
+    fn close(&mut self, action: Action, pos: Position) {
+        if let Some(heading) = self.walk.can(self.space, self.from, action, pos.pn) {
+            self.close.close(action, Position { heading, ..pos });
+        }
+    }
+}
+
+impl<S, W> Walk<S> for Wide<W>
+where
+    S: Space,
+    W: Walk<S>,
+{
+    fn walk<C>(&self, space: &S, pos: Position, close: &mut C)
+    where
+        C: Close,
+    {
+        let mut close = WideCloser {
+            walk: self,
+            space,
+            close,
+            from: pos,
+        };
+
+        self.walk.walk(space, pos, &mut close);
+    }
+
+    fn run(&self, space: &S, pos: Position, action: Action) -> Option<Position> {
+        let to = self.walk.run(space, pos, action)?;
+        let heading = self.can(space, pos, action, to.pn)?;
+        Some(Position { heading, ..to })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path::{testing::Terrain, PathFinder, Pedestrian};
+
+    #[test]
+    fn wide() {
+        let corridor = Terrain::from_rows(&[
+            "#########",
+            "#########",
+            "#.......#",
+            "######.##",
+            "######.##",
+            "######.##",
+            "#########",
+        ]);
+
+        let room = Terrain::from_rows(&[
+            "#########",
+            "#####...#",
+            "#.......#",
+            "#####...#",
+            "######.##",
+            "######.##",
+            "#########",
+        ]);
+
+        let point = |x, z| Point::from_absolute(x, Terrain::FLOOR, z).unwrap();
+        let reaches = |space: &Terrain, width, length| {
+            let walk = Wide {
+                walk: Pedestrian {
+                    height: Height::new(2).unwrap(),
+                    jump_down: Height::new(4).unwrap(),
+                },
+                footprint: Footprint::new(width, length).unwrap(),
+                height: Height::new(2).unwrap(),
+            };
+
+            let pos = Position {
+                pn: point(2, 2),
+                value: u32::MAX,
+                heading: None,
+            };
+
+            PathFinder::new()
+                .find_to(pos, point(6, 4), &walk, space)
+                .is_some()
+        };
+
+        assert!(reaches(&corridor, 1, 1));
+        assert!(reaches(&room, 1, 1));
+
+        // Too wide for the corridor
+        assert!(!reaches(&corridor, 2, 1));
+        assert!(!reaches(&room, 2, 1));
+
+        // Too long to turn in the corridor
+        assert!(reaches(&room, 1, 3));
+        assert!(!reaches(&corridor, 1, 3));
+
+        let crossing = Terrain::from_rows(&[
+            "#########",
+            "######.##",
+            "######.##",
+            "#.......#",
+            "######.##",
+            "######.##",
+            "######.##",
+            "#########",
+        ]);
+
+        // Goes straight through the crossing, but can't turn there
+        let walk = Wide {
+            walk: Pedestrian {
+                height: Height::new(2).unwrap(),
+                jump_down: Height::new(4).unwrap(),
+            },
+            footprint: Footprint::new(1, 3).unwrap(),
+            height: Height::new(2).unwrap(),
+        };
+
+        let mut pf = PathFinder::new();
+        let pos = Position {
+            pn: point(6, 2),
+            value: u32::MAX,
+            heading: None,
+        };
+        assert!(pf.find_to(pos, point(6, 5), &walk, &crossing).is_some());
+
+        let pos = Position {
+            pn: point(2, 3),
+            ..pos
+        };
+        assert!(pf.find_to(pos, point(6, 5), &walk, &crossing).is_none());
+
+        assert_eq!(Footprint::new(0, 1), None);
+        assert_eq!(Footprint::new(1, 200), None);
+        assert!(Footprint::new(Footprint::MAX, 1).is_some());
+    }
+}
@@ -1,5 +1,5 @@
 use crate::side::Side;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use shr::cgm::Vec3;
 use std::{error, fmt, ops};
 
@@ -18,9 +18,10 @@ impl fmt::Display for ParseError {
 
 impl error::Error for ParseError {}
 
-#[derive(Deserialize, Copy, Clone, Eq, Hash, PartialEq)]
-#[serde(try_from = "u8")]
+#[derive(Default, Deserialize, Copy, Clone, Eq, Hash, PartialEq, Serialize)]
+#[serde(try_from = "u8", into = "u8")]
 pub enum Rotation {
+    #[default]
     Q0 = 0,
     Q1 = 1,
     Q2 = 2,
@@ -38,6 +39,17 @@ impl Rotation {
         }
     }
 
+    /// Returns the rotation facing the horizontal side.
+    pub const fn from_side(side: Side) -> Option<Self> {
+        match side {
+            Side::Forth => Some(Self::Q0),
+            Side::Left => Some(Self::Q1),
+            Side::Back => Some(Self::Q2),
+            Side::Right => Some(Self::Q3),
+            Side::Up | Side::Down => None,
+        }
+    }
+
     pub const fn opposite(self) -> Self {
         match self {
             Self::Q0 => Self::Q2,
@@ -106,9 +118,9 @@ impl TryFrom<u8> for Rotation {
     }
 }
 
-impl Default for Rotation {
-    fn default() -> Self {
-        Self::Q0
+impl From<Rotation> for u8 {
+    fn from(rotation: Rotation) -> Self {
+        rotation as u8
     }
 }
 
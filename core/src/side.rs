@@ -1,4 +1,4 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use shr::cgm::*;
 use std::{error, fmt, ops, str::FromStr};
 
@@ -21,8 +21,8 @@ impl fmt::Display for ParseError {
 
 impl error::Error for ParseError {}
 
-#[derive(Debug, Deserialize, Copy, Clone, Eq, Hash, PartialEq)]
-#[serde(try_from = "&str")]
+#[derive(Debug, Deserialize, Copy, Clone, Eq, Hash, PartialEq, Serialize)]
+#[serde(try_from = "&str", into = "&str")]
 pub enum Side {
     Left = 0,
     Right = 1,
@@ -126,6 +126,19 @@ impl TryFrom<&str> for Side {
     }
 }
 
+impl From<Side> for &str {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Left => "l",
+            Side::Right => "r",
+            Side::Up => "u",
+            Side::Down => "d",
+            Side::Forth => "f",
+            Side::Back => "b",
+        }
+    }
+}
+
 impl FromStr for Side {
     type Err = ParseError;
 
@@ -1,7 +1,9 @@
-use crate::{height::Height, load::load_tiles, path::Pass, prelude::Rotation};
+use crate::{auto::Rule, height::Height, load::load_tiles, path::Pass, prelude::Rotation};
+use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, fmt, rc::Rc};
 
-#[derive(Copy, Clone, Eq, Hash, PartialEq)]
+#[derive(Copy, Clone, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(try_from = "u16", into = "u16")]
 pub struct TileIndex(u16);
 
 impl TileIndex {
@@ -17,6 +19,20 @@ impl TileIndex {
     }
 }
 
+impl TryFrom<u16> for TileIndex {
+    type Error = u16;
+
+    fn try_from(idx: u16) -> Result<Self, Self::Error> {
+        Self::new(idx).ok_or(idx)
+    }
+}
+
+impl From<TileIndex> for u16 {
+    fn from(idx: TileIndex) -> Self {
+        idx.0
+    }
+}
+
 impl fmt::Display for TileIndex {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "tile: {}", self.0)
@@ -29,7 +45,7 @@ impl fmt::Debug for TileIndex {
     }
 }
 
-#[derive(Copy, Clone, Eq, Hash, PartialEq)]
+#[derive(Copy, Clone, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct VariantIndex(pub u8);
 
 impl VariantIndex {
@@ -50,17 +66,22 @@ impl fmt::Debug for VariantIndex {
     }
 }
 
+#[derive(Clone)]
 pub struct Variant {
     pub idx: VariantIndex,
     pub name: String,
     pub rotation: Rotation,
     pub passes: Vec<Pass>,
+    /// The rule to pick the variant automatically.
+    pub auto: Option<Rule>,
 }
 
 pub struct Tile {
     pub idx: TileIndex,
     pub name: Rc<str>,
     pub height: Height,
+    /// The name of the server behaviour of the tile.
+    pub behaviour: Rc<str>,
     pub variants: Vec<Variant>,
 }
 
@@ -68,6 +89,10 @@ impl Tile {
     pub fn variant(&self, idx: VariantIndex) -> &Variant {
         &self.variants[idx.0 as usize]
     }
+
+    pub fn has_variant(&self, idx: VariantIndex) -> bool {
+        (idx.0 as usize) < self.variants.len()
+    }
 }
 
 pub struct TileList {
@@ -84,6 +109,7 @@ impl TileList {
                 idx: TileIndex::new(1).unwrap(),
                 name: "".into(),
                 height: Height::new(1).unwrap(),
+                behaviour: "".into(),
                 variants: Vec::default(),
             }],
         };
@@ -92,9 +118,9 @@ impl TileList {
         list
     }
 
-    pub fn add<V>(&mut self, name: &str, height: Height, variants: V)
+    pub fn add<V>(&mut self, name: &str, height: Height, behaviour: &str, variants: V)
     where
-        V: IntoIterator<Item = (String, Rotation, Vec<Pass>)>,
+        V: IntoIterator<Item = (String, Rotation, Vec<Pass>, Option<Rule>)>,
     {
         let idx = self.vec.len();
         assert!(idx <= u16::MAX as usize);
@@ -105,10 +131,11 @@ impl TileList {
         self.vec.push(Tile {
             idx: tile_idx,
             name,
+            behaviour: behaviour.into(),
             variants: variants
                 .into_iter()
                 .enumerate()
-                .map(|(idx, (name, rotation, passes))| Variant {
+                .map(|(idx, (name, rotation, passes, auto))| Variant {
                     idx: {
                         assert!(idx <= u8::MAX as usize);
                         VariantIndex(idx as u8)
@@ -119,6 +146,7 @@ impl TileList {
                         assert_eq!(height.get() as usize, passes.len());
                         passes
                     },
+                    auto,
                 })
                 .collect(),
             height,
@@ -129,12 +157,18 @@ impl TileList {
         &self.vec[idx.0 as usize]
     }
 
+    /// Returns the tile of the index received from the outside, which could be unknown.
+    pub fn try_get(&self, idx: TileIndex) -> Option<&Tile> {
+        self.vec.get(idx.0 as usize)
+    }
+
     pub fn get_by_name(&self, name: &str) -> Option<&Tile> {
         let idx = self.map.get(name)?;
         Some(self.get(*idx))
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &Tile> {
-        self.vec.iter()
+        // Skip the placeholder, so the tiles start from 1
+        self.vec[1..].iter()
     }
 }
@@ -1,5 +1,6 @@
 use image::{DynamicImage, GenericImage, GenericImageView};
 use shr::cgm::*;
+use std::{error, fmt};
 
 #[derive(Debug)]
 pub enum Error {
@@ -7,6 +8,17 @@ pub enum Error {
     NoSprites,
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Dimensions(width, height) => write!(f, "wrong dimensions {width}x{height}"),
+            Self::NoSprites => write!(f, "no sprites"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
 #[derive(Copy, Clone)]
 pub struct Mapper {
     size: u32,
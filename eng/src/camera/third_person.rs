@@ -26,6 +26,10 @@ impl TpCamera {
         self.rot.get()
     }
 
+    pub fn look(&self) -> Pnt3 {
+        self.cam.look()
+    }
+
     pub fn distance(&self) -> f32 {
         self.distance
     }
@@ -52,6 +56,10 @@ impl TpCamera {
         self.distance = Self::MIN_DISTANCE.max(self.distance + delta);
     }
 
+    pub fn set_look(&mut self, look: Pnt3) {
+        self.cam.set_look(look);
+    }
+
     pub fn move_look(&mut self, delta: Vec3) {
         self.cam.set_look(self.cam.look() + delta);
     }
@@ -1,3 +1,4 @@
+use core::path::Trajectory;
 use ngl::{
     pass::{Color, Pass, Stage},
     Draw, Pipe, Pipeline,
@@ -6,6 +7,20 @@ use shr::cgm::*;
 
 pub(crate) struct Path(pub Vec<Vec3>);
 
+impl Path {
+    /// How many points of the trajectory are drawn in a unit of time.
+    const RATE: f32 = 8.;
+
+    pub fn sample(trajectory: &Trajectory) -> Self {
+        let n = (trajectory.duration() * Self::RATE).ceil() as usize;
+        Self(
+            (0..=n)
+                .filter_map(|i| trajectory.at(i as f32 / Self::RATE))
+                .collect(),
+        )
+    }
+}
+
 impl Draw<Color> for Path {
     fn draw<'a>(&self, pass: Pass<'a, Color>)
     where
@@ -0,0 +1,346 @@
+use crate::land::MeshData;
+use image::{DynamicImage, ImageOutputFormat};
+use serde_json::json;
+use std::{
+    error, fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Image(image::ImageError),
+    NotLoaded,
+    EmptyMesh,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Image(err) => write!(f, "image error: {err}"),
+            Self::NotLoaded => write!(f, "cluster isn't loaded"),
+            Self::EmptyMesh => write!(f, "empty mesh"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(err: image::ImageError) -> Self {
+        Self::Image(err)
+    }
+}
+
+/// Exports the mesh with the atlas to the `dir`.
+///
+/// Writes `name.obj` with `name.mtl` and `name.png` next to it,
+/// and the same scene as the binary glTF `name.glb`.
+pub(crate) fn export(
+    dir: &Path,
+    name: &str,
+    mesh: &MeshData,
+    atlas: &DynamicImage,
+) -> Result<(), Error> {
+    if mesh.indxs.is_empty() {
+        return Err(Error::EmptyMesh);
+    }
+
+    let texture = format!("{name}.png");
+    atlas.save(dir.join(&texture))?;
+
+    let mtl = format!("{name}.mtl");
+    let mut file = BufWriter::new(File::create(dir.join(&mtl))?);
+    write_mtl(&mut file, name, &texture)?;
+    file.flush()?;
+
+    let mut file = BufWriter::new(File::create(dir.join(format!("{name}.obj")))?);
+    write_obj(&mut file, mesh, &mtl, name)?;
+    file.flush()?;
+
+    let mut file = BufWriter::new(File::create(dir.join(format!("{name}.glb")))?);
+    write_glb(&mut file, mesh, atlas)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Writes the material with the `texture`.
+pub(crate) fn write_mtl<W>(writer: &mut W, material: &str, texture: &str) -> io::Result<()>
+where
+    W: Write,
+{
+    writeln!(writer, "newmtl {material}")?;
+    writeln!(writer, "Ka 1 1 1")?;
+    writeln!(writer, "Kd 1 1 1")?;
+    writeln!(writer, "Ks 0 0 0")?;
+    writeln!(writer, "illum 1")?;
+    writeln!(writer, "map_Kd {texture}")?;
+    writeln!(writer, "map_d {texture}")
+}
+
+/// Writes the mesh as the Wavefront OBJ.
+pub(crate) fn write_obj<W>(
+    writer: &mut W,
+    mesh: &MeshData,
+    mtl: &str,
+    material: &str,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    writeln!(writer, "mtllib {mtl}")?;
+    writeln!(writer, "usemtl {material}")?;
+    for vert in &mesh.verts {
+        writeln!(writer, "v {} {} {}", vert.co.x, vert.co.y, vert.co.z)?;
+    }
+
+    // OBJ counts the texture rows from the bottom
+    for vert in &mesh.verts {
+        writeln!(writer, "vt {} {}", vert.st.x, 1. - vert.st.y)?;
+    }
+
+    for vert in &mesh.verts {
+        writeln!(writer, "vn {} {} {}", vert.nm.x, vert.nm.y, vert.nm.z)?;
+    }
+
+    for face in mesh.indxs.chunks(3) {
+        write!(writer, "f")?;
+        for idx in face {
+            let idx = idx + 1;
+            write!(writer, " {idx}/{idx}/{idx}")?;
+        }
+
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the mesh with the embedded atlas as the binary glTF.
+pub(crate) fn write_glb<W>(
+    writer: &mut W,
+    mesh: &MeshData,
+    atlas: &DynamicImage,
+) -> Result<(), Error>
+where
+    W: Write,
+{
+    const ARRAY_BUFFER: u32 = 34962;
+    const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+    const FLOAT: u32 = 5126;
+    const UNSIGNED_INT: u32 = 5125;
+    const NEAREST: u32 = 9728;
+
+    if mesh.indxs.is_empty() {
+        return Err(Error::EmptyMesh);
+    }
+
+    let mut bin = Vec::new();
+    let mut views = Vec::new();
+    let mut view = |bin: &mut Vec<u8>, bytes: &[u8], target: Option<u32>| {
+        let offset = bin.len();
+        bin.extend_from_slice(bytes);
+        pad(bin, 0);
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": bytes.len(),
+        });
+
+        if let Some(target) = target {
+            view["target"] = target.into();
+        }
+
+        views.push(view);
+        views.len() - 1
+    };
+
+    let co = floats(mesh.verts.iter().flat_map(|v| [v.co.x, v.co.y, v.co.z]));
+    let nm = floats(mesh.verts.iter().flat_map(|v| [v.nm.x, v.nm.y, v.nm.z]));
+    let st = floats(mesh.verts.iter().flat_map(|v| [v.st.x, v.st.y]));
+    let indxs: Vec<_> = mesh
+        .indxs
+        .iter()
+        .flat_map(|idx| idx.to_le_bytes())
+        .collect();
+    let mut png = Vec::new();
+    atlas.write_to(&mut png, ImageOutputFormat::Png)?;
+
+    let co_view = view(&mut bin, &co, Some(ARRAY_BUFFER));
+    let nm_view = view(&mut bin, &nm, Some(ARRAY_BUFFER));
+    let st_view = view(&mut bin, &st, Some(ARRAY_BUFFER));
+    let indxs_view = view(&mut bin, &indxs, Some(ELEMENT_ARRAY_BUFFER));
+    let png_view = view(&mut bin, &png, None);
+
+    // The positions must have the bounds
+    let (min, max) = mesh.verts.iter().fold(
+        ([f32::MAX; 3], [f32::MIN; 3]),
+        |(mut min, mut max), vert| {
+            for (i, co) in [vert.co.x, vert.co.y, vert.co.z].into_iter().enumerate() {
+                min[i] = min[i].min(co);
+                max[i] = max[i].max(co);
+            }
+            (min, max)
+        },
+    );
+
+    let count = mesh.verts.len();
+    let gltf = json!({
+        "asset": { "version": "2.0", "generator": "arkipelago" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0 }],
+        "meshes": [{
+            "primitives": [{
+                "attributes": { "POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 2 },
+                "indices": 3,
+                "material": 0,
+            }],
+        }],
+        "materials": [{
+            "pbrMetallicRoughness": {
+                "baseColorTexture": { "index": 0 },
+                "metallicFactor": 0.,
+            },
+            "alphaMode": "MASK",
+        }],
+        "textures": [{ "source": 0, "sampler": 0 }],
+        "samplers": [{ "magFilter": NEAREST, "minFilter": NEAREST }],
+        "images": [{ "bufferView": png_view, "mimeType": "image/png" }],
+        "accessors": [
+            {
+                "bufferView": co_view,
+                "componentType": FLOAT,
+                "count": count,
+                "type": "VEC3",
+                "min": min,
+                "max": max,
+            },
+            { "bufferView": nm_view, "componentType": FLOAT, "count": count, "type": "VEC3" },
+            { "bufferView": st_view, "componentType": FLOAT, "count": count, "type": "VEC2" },
+            {
+                "bufferView": indxs_view,
+                "componentType": UNSIGNED_INT,
+                "count": mesh.indxs.len(),
+                "type": "SCALAR",
+            },
+        ],
+        "bufferViews": views,
+        "buffers": [{ "byteLength": bin.len() }],
+    });
+
+    let mut json = serde_json::to_vec(&gltf).expect("serialize gltf");
+    pad(&mut json, b' ');
+
+    let len = 12 + 8 + json.len() + 8 + bin.len();
+    writer.write_all(b"glTF")?;
+    writer.write_all(&2u32.to_le_bytes())?;
+    writer.write_all(&(len as u32).to_le_bytes())?;
+    for (chunk, kind) in [(&json, b"JSON"), (&bin, b"BIN\0")] {
+        writer.write_all(&(chunk.len() as u32).to_le_bytes())?;
+        writer.write_all(kind)?;
+        writer.write_all(chunk)?;
+    }
+
+    Ok(())
+}
+
+fn floats<I>(values: I) -> Vec<u8>
+where
+    I: IntoIterator<Item = f32>,
+{
+    values.into_iter().flat_map(f32::to_le_bytes).collect()
+}
+
+/// Pads the bytes to the 4 bytes alignment.
+fn pad(bytes: &mut Vec<u8>, byte: u8) {
+    while !bytes.len().is_multiple_of(4) {
+        bytes.push(byte);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vert;
+    use shr::cgm::*;
+
+    fn quad() -> MeshData {
+        let vert = |x, z, s, t| Vert {
+            co: Vec3::new(x, 0., z),
+            nm: Vec3::new(0., 1., 0.),
+            st: Vec2::new(s, t),
+            sp: Vec4::zero(),
+        };
+
+        MeshData {
+            verts: vec![
+                vert(0., 0., 0., 0.),
+                vert(1., 0., 0.5, 0.),
+                vert(1., 1., 0.5, 0.5),
+                vert(0., 1., 0., 0.5),
+            ],
+            indxs: vec![0, 1, 2, 0, 2, 3],
+        }
+    }
+
+    #[test]
+    fn obj() {
+        let mut buf = Vec::new();
+        write_obj(&mut buf, &quad(), "quad.mtl", "quad").unwrap();
+        let obj = String::from_utf8(buf).unwrap();
+        let lines: Vec<_> = obj.lines().collect();
+        assert_eq!(lines[..2], ["mtllib quad.mtl", "usemtl quad"]);
+        assert_eq!(
+            lines.iter().filter(|line| line.starts_with("v ")).count(),
+            4
+        );
+        assert!(lines.contains(&"v 1 0 1"));
+        assert!(lines.contains(&"vt 0.5 0.5"));
+        assert!(lines.contains(&"vn 0 1 0"));
+        assert_eq!(
+            lines[lines.len() - 2..],
+            ["f 1/1/1 2/2/2 3/3/3", "f 1/1/1 3/3/3 4/4/4"]
+        );
+    }
+
+    #[test]
+    fn glb() {
+        let mut buf = Vec::new();
+        let atlas = DynamicImage::new_rgba8(2, 2);
+        write_glb(&mut buf, &quad(), &atlas).unwrap();
+        assert_eq!(&buf[..4], b"glTF");
+        assert_eq!(
+            u32::from_le_bytes(buf[8..12].try_into().unwrap()) as usize,
+            buf.len()
+        );
+        assert!(buf.len().is_multiple_of(4));
+
+        let json_len = u32::from_le_bytes(buf[12..16].try_into().unwrap()) as usize;
+        assert_eq!(&buf[16..20], b"JSON");
+        let gltf: serde_json::Value = serde_json::from_slice(&buf[20..20 + json_len]).unwrap();
+        assert_eq!(gltf["accessors"][0]["count"], 4);
+        assert_eq!(gltf["accessors"][0]["max"], json!([1., 0., 1.]));
+        assert_eq!(gltf["accessors"][3]["count"], 6);
+
+        let bin = &buf[20 + json_len..];
+        assert_eq!(&bin[4..8], b"BIN\0");
+        let bin_len = u32::from_le_bytes(bin[..4].try_into().unwrap());
+        assert_eq!(gltf["buffers"][0]["byteLength"], bin_len);
+
+        let empty = MeshData::default();
+        assert!(matches!(
+            write_glb(&mut Vec::new(), &empty, &atlas),
+            Err(Error::EmptyMesh)
+        ));
+    }
+}
@@ -2,25 +2,34 @@ use crate::{
     atlas::Atlas,
     camera::TpCamera,
     draw::{cell::Cell, path::Path},
-    land::{variant::VariantSet, ClusterView, Factory},
+    export,
+    land::{variant::VariantSet, ClusterView, Factory, Mesher},
     loader::Loader,
-    Render, Texture, Vert,
+    ExportError, Render, Texture, Vert,
 };
 use core::{
-    path::{Flyer, PathFinder, Pedestrian, Position},
+    chunk,
+    net::TileSlab,
+    path::{self, Action, Flyer, PathFinder, Pedestrian, PlayerWalk, Position, Trajectory, Walk},
+    point::ChunkPoints,
     prelude::*,
+    rotation::Rotation,
     tile::TileList,
 };
+use image::DynamicImage;
 use ngl::{
     mesh::Indexed,
     pass::{Pass, Solid, Stage},
     Draw, Pipe, Pipeline,
 };
 use shr::cgm::*;
-use std::{collections::HashMap, time::Instant};
+use std::{
+    collections::{HashMap, HashSet},
+    thread,
+};
 
 struct Data {
-    pub mesh: Indexed<Vert>,
+    pub meshes: HashMap<ClusterPoint, Indexed<Vert>>,
     pub map: Texture,
 }
 
@@ -31,7 +40,9 @@ impl Draw<Solid> for Data {
     {
         pass.set_model(&Mat4::identity());
         pass.set_texture(&self.map);
-        pass.draw_indexed_mesh(&self.mesh);
+        for mesh in self.meshes.values() {
+            pass.draw_indexed_mesh(mesh);
+        }
     }
 }
 
@@ -52,11 +63,21 @@ pub enum Control {
 }
 
 pub struct Game {
+    tiles: TileList,
+    view: ClusterView,
+    mesher: Mesher,
+    /// The clusters received from the server.
+    loaded: HashSet<ClusterPoint>,
+    /// The atlas image, kept for the export.
+    atlas: DynamicImage,
     data: Data,
     cells: Vec<Cell>,
     pathes: Vec<Path>,
     cam: TpCamera,
     aspect: f32,
+    /// The position of the player, told by the server.
+    player: Option<Point>,
+    walk: PlayerWalk,
 }
 
 impl Game {
@@ -93,8 +114,8 @@ impl Game {
         };
 
         let atlas = Atlas::new(sprites.iter()).unwrap();
-        let (map, mapper) = atlas.map();
-        let map = ren.make_texture(&map);
+        let (image, mapper) = atlas.map();
+        let map = ren.make_texture(&image);
         let mut factory = Factory::new(mapper);
 
         let mut variant_set = VariantSet::new();
@@ -115,111 +136,138 @@ impl Game {
             variant_set.add(key, variant);
         }
 
-        let mut view = ClusterView::new(variant_set, polygons);
-        for (name, (x, y, z), variant) in [
-            ("dirt", (0, -2, 0), 0),
-            ("dirt", (0, 0, 0), 0),
-            ("grass", (0, 1, 0), 0),
-            ("dirt", (1, 0, 0), 0),
-            ("grass", (1, 1, 0), 1),
-            ("dirt", (2, 0, 0), 2),
-            ("dirt", (4, 0, 0), 4),
-            ("dirt", (4, 0, 1), 4),
-            ("dirt", (4, 0, 2), 4),
-            ("stone", (0, 0, 1), 0),
-            ("rocks", (0, 2, 1), 0),
-            ("stone", (1, 0, 1), 0),
-            ("stone", (0, 0, 2), 0),
-            ("stone", (1, 0, 2), 0),
-            ("stone", (2, 0, 2), 0),
-            ("stone", (3, 0, 2), 0),
-            ("stone", (3, 1, 1), 0),
-            ("stone", (3, 2, 0), 0),
-            ("stone", (4, 3, 0), 0),
-            ("dirt", (5, 4, 0), 0),
-            ("dirt", (6, 4, 0), 2),
-            ("ladder", (6, 1, 3), 0),
-            ("dirt", (5, 4, 2), 0),
-            ("dirt", (6, 4, 2), 0),
-            ("dirt", (5, 5, 2), 0),
-            ("dirt", (5, 6, 2), 0),
-            ("dirt", (5, 7, 2), 0),
-            ("dirt", (0, 0, 3), 0),
-            ("dirt", (1, 0, 3), 0),
-            ("dirt", (0, 0, 4), 0),
-            ("dirt", (1, 0, 4), 0),
-            ("grass", (0, 1, 3), 0),
-            ("grass", (0, 1, 4), 1),
-            ("rocks", (0, 1, 5), 0),
-            ("dirt", (0, 0, 5), 0),
-            ("dirt", (1, 0, 5), 0),
-            ("dirt", (2, 0, 5), 0),
-            ("dirt", (1, 1, 5), 0),
-            ("dirt", (1, 3, 5), 0),
-            ("dirt", (2, 1, 5), 0),
-            ("dirt", (2, 2, 5), 0),
-            ("bricks", (1, 2, 6), 0),
-            ("bricks", (1, 4, 6), 0),
-            ("grass", (2, 3, 5), 0),
-            ("dirt", (0, 0, 6), 1),
-            ("dirt", (2, 0, 6), 1),
-            ("bricks", (2, 0, 3), 0),
-            ("bricks", (3, 0, 3), 0),
-            ("bricks", (4, 0, 3), 0),
-            ("bricks", (4, 1, 4), 0),
-            ("bricks", (5, 2, 4), 0),
-            ("bricks", (5, 0, 4), 0),
-            ("bricks", (6, 0, 4), 0),
-            ("box", (3, 0, 4), 0),
-            ("box", (3, 2, 4), 0),
-            ("box", (3, 1, 5), 0),
-            ("box", (3, 3, 5), 0),
-            ("box", (3, 0, 6), 0),
-            ("box", (3, 4, 6), 0),
-            ("grass", (5, 1, 0), 0),
-            ("grass", (5, 1, 2), 1),
-            ("dirt", (5, 0, 0), 0),
-            ("dirt", (5, 0, 1), 0),
-            ("dirt", (5, 0, 2), 0),
-            ("dirt", (5, 0, 3), 0),
-            ("dirt", (6, 0, 0), 2),
-            ("dirt", (6, 0, 1), 2),
-            ("dirt", (6, 0, 2), 2),
-            ("dirt", (6, 0, 3), 2),
-            ("stone", (5, 0, 5), 3),
-            ("stone", (5, 0, 6), 1),
-            ("stone", (6, 0, 5), 3),
-            ("stone", (6, 0, 6), 1),
-            ("stone", (6, 2, 5), 7),
-            ("stone", (6, 2, 6), 5),
-            ("bricks", (7, 0, 7), 0),
-            ("bricks", (7, 0, 6), 0),
-            ("bricks", (5, 1, 7), 0),
-            ("bricks", (5, 3, 7), 0),
-            ("bricks", (7, 0, 7), 0),
-            ("bricks", (9, 0, 5), 0),
-            ("bricks", (9, 2, 5), 0),
-            ("bricks", (9, 4, 5), 0),
-            ("bricks", (10, 0, 4), 0),
-            ("bricks", (10, 2, 4), 0),
-            ("bricks", (10, 4, 4), 0),
-            ("bricks", (10, 0, 5), 0),
-            ("bricks", (9, 0, 4), 0),
-            ("bricks", (9, 2, 4), 0),
-            ("bricks", (9, 0, 2), 0),
-            ("bricks", (9, 2, 2), 0),
-            ("bricks", (9, 0, 0), 0),
-            ("bricks", (9, 2, 0), 0),
-            ("bricks", (11, 0, 2), 0),
-            ("bricks", (11, 2, 2), 0),
-            ("bricks", (11, 4, 2), 0),
-        ] {
-            view.place(
-                Point::from_absolute(x, y, z).unwrap(),
-                tiles.get_by_name(name).unwrap(),
-                VariantIndex(variant),
-            );
+        // Leave a core for the main thread
+        let workers = thread::available_parallelism().map_or(1, |n| n.get() - 1);
+        let view = ClusterView::new(variant_set, polygons);
+        let mesher = view.mesher(Some(mapper.multiplier()), workers, 64);
+
+        Self {
+            tiles,
+            view,
+            mesher,
+            loaded: HashSet::default(),
+            atlas: image,
+            data: Data {
+                meshes: HashMap::default(),
+                map,
+            },
+            cells: Vec::default(),
+            pathes: Vec::default(),
+            cam: TpCamera::new(1., Pnt3::new(3., 0., 3.)),
+            aspect: 1.,
+            player: None,
+            walk: path::player(),
         }
+    }
+
+    /// The point the camera looks at.
+    pub fn focus(&self) -> Option<Point> {
+        let look = self.cam.look();
+        Point::from_absolute(
+            look.x.floor() as i64,
+            (look.y * 2.).floor() as i64,
+            look.z.floor() as i64,
+        )
+        .ok()
+    }
+
+    /// Replaces the tiles of the cluster with the encoded slabs and re-meshes it.
+    pub fn load_chunk(&mut self, ren: &Render, cl: ClusterPoint, slabs: &[u8]) {
+        let slabs: Chunk<TileSlab> = match chunk::decode(slabs) {
+            Ok(slabs) => slabs,
+            Err(err) => {
+                println!("Broken chunk {cl}: {err}");
+                return;
+            }
+        };
+
+        self.view.clear(cl);
+        self.loaded.insert(cl);
+        for ch in ChunkPoints::new() {
+            // The trunks are placed with their bases
+            let (tile, variant) = match *slabs.get(ch) {
+                TileSlab::Base(tile, variant) => (tile, variant),
+                _ => continue,
+            };
+
+            let tile = match self.tiles.try_get(tile) {
+                Some(tile) if tile.has_variant(variant) => tile,
+                _ => {
+                    println!("Unknown {tile} in {cl}");
+                    continue;
+                }
+            };
+
+            self.view.place(Point::new(ch, cl), tile, Some(variant));
+        }
+
+        self.remesh(ren);
+        self.find_pathes();
+    }
+
+    /// Places the tile and re-meshes the changed clusters.
+    pub fn place_tile(&mut self, ren: &Render, pn: Point, tile: TileIndex, variant: VariantIndex) {
+        let tile = match self.tiles.try_get(tile) {
+            Some(tile) if tile.has_variant(variant) => tile,
+            _ => {
+                println!("Unknown {tile} at {pn}");
+                return;
+            }
+        };
+
+        self.view.place(pn, tile, Some(variant));
+        self.view
+            .retile(pn, tile.height, |idx| self.tiles.try_get(idx));
+        self.remesh(ren);
+        self.find_pathes();
+    }
+
+    /// Removes the tile and re-meshes the changed clusters.
+    pub fn remove_tile(&mut self, ren: &Render, pn: Point) {
+        if let Some((base, height)) = self.view.remove(pn) {
+            self.view
+                .retile(base, height, |idx| self.tiles.try_get(idx));
+            self.remesh(ren);
+            self.find_pathes();
+        }
+    }
+
+    pub fn unload_chunk(&mut self, cl: ClusterPoint) {
+        self.view.clear(cl);
+        self.loaded.remove(&cl);
+        self.mesher.cancel(cl);
+        self.data.meshes.remove(&cl);
+    }
+
+    /// Exports the mesh of the cluster with the atlas to the `dir`.
+    pub fn export(&mut self, cl: ClusterPoint, dir: &std::path::Path) -> Result<(), ExportError> {
+        // The faces aren't merged, the formats can't repeat a sprite of the atlas
+        let offset = Point::new(ChunkPoint::new(0, 0, 0).unwrap(), cl).into();
+        let mesh = self.view.mesh(offset, cl).ok_or(ExportError::NotLoaded)?;
+
+        let (x, y, z) = cl.into();
+        let name = format!("cluster_{x}_{y}_{z}");
+        export::export(dir, &name, &mesh, &self.atlas)
+    }
+
+    /// Queues the changed clusters to the mesher and uploads the finished meshes.
+    ///
+    /// Only the loaded clusters are kept,
+    /// the others are unloaded or not yet received.
+    fn remesh(&mut self, ren: &Render) {
+        self.view.spawn_dirty(&mut self.mesher);
+        for (cl, mesh) in self.mesher.finished() {
+            if self.loaded.contains(&cl) {
+                self.data.meshes.insert(cl, mesh.upload(ren));
+            }
+        }
+    }
+
+    fn find_pathes(&mut self) {
+        let pn = match self.focus() {
+            Some(pn) => pn,
+            None => return,
+        };
 
         let mut pf = PathFinder::new();
         let walk = Flyer {
@@ -229,43 +277,35 @@ impl Game {
             },
         };
 
-        let start = Instant::now();
-        pf.find(
+        pf.find_cheapest(
             Position {
-                pn: Point::from_absolute(3, 2, 3).unwrap(),
+                pn,
                 value: 5,
+                heading: None,
             },
             &walk,
-            &view,
+            &self.view,
         );
-        let end = Instant::now();
-        let elapsed = end.duration_since(start).as_millis();
-        println!("elapsed: {elapsed} ms");
-
         let path = pf.path();
-        let cells = path.points().map(|pn| Cell(pn.into())).collect();
-
-        let mut pathes: Vec<_> = path
+        self.cells = path.points().map(|pn| Cell(pn.into())).collect();
+        self.pathes = path
             .points()
-            .map(|pn| Path(path.to(pn).map(|(_, pn)| pn.into()).collect()))
+            .map(|pn| {
+                let mut moves: Vec<_> = path.to(pn).collect();
+                moves.reverse();
+                let mut trajectory = Trajectory::new(moves, 1.);
+                trajectory.smooth(walk.walk.height, &self.view);
+                Path::sample(&trajectory)
+            })
             .collect();
-        pathes.sort_by_key(|path| path.0.len());
-
-        Self {
-            data: Data {
-                mesh: view.mesh(ren, Vec3::zero(), ClusterPoint::new(0, 0, 0).unwrap()),
-                map,
-            },
-            cells,
-            pathes,
-            cam: TpCamera::new(1., Pnt3::new(3., 0., 3.)),
-            aspect: 1.,
-        }
+        self.pathes.sort_by_key(|path| path.0.len());
     }
 
     pub fn draw(&mut self, ren: &mut Render, _: f32) {
         const DRAW_CELLS: bool = false;
 
+        self.remesh(ren);
+
         ren.set_proj(self.cam.proj(self.aspect));
         ren.set_view(self.cam.view());
 
@@ -281,16 +321,54 @@ impl Game {
         self.aspect = width as f32 / height as f32;
     }
 
-    pub fn input(&mut self, control: Control) {
+    /// Applies the control, returns the action of the player to send to the server.
+    pub fn input(&mut self, control: Control) -> Option<Action> {
         const SENSITIVITY: f32 = 0.01;
 
         match control {
             Control::Look(x, y) => self.cam.rotate(Vec2::new(x, y) * SENSITIVITY),
             Control::Scroll(_, y) => self.cam.move_to(y),
-            Control::Forward => self.cam.move_look(Vec3::new(0., 0., -1.)),
-            Control::Back => self.cam.move_look(Vec3::new(0., 0., 1.)),
-            Control::Left => self.cam.move_look(Vec3::new(-1., 0., 0.)),
-            Control::Right => self.cam.move_look(Vec3::new(1., 0., 0.)),
+            Control::Forward => return self.step(Side::Back),
+            Control::Back => return self.step(Side::Forth),
+            Control::Left => return self.step(Side::Right),
+            Control::Right => return self.step(Side::Left),
         }
+
+        None
+    }
+
+    /// Places the player where the server tells, the camera follows it.
+    pub fn place_player(&mut self, pn: Point) {
+        self.player = Some(pn);
+        let co: Vec3 = pn.into();
+        self.cam
+            .set_look(Pnt3::from_vec(co + Vec3::new(0.5, 0., 0.5)));
+        self.find_pathes();
+    }
+
+    /// Moves the player to the `side`.
+    ///
+    /// The move is predicted by the same walker the server validates it with,
+    /// if the server disagrees, it corrects the player.
+    fn step(&mut self, side: Side) -> Option<Action> {
+        let pn = self.player?;
+        let rotation = Rotation::from_side(side)?;
+        let pos = Position {
+            pn,
+            value: u32::MAX,
+            heading: None,
+        };
+
+        let (action, pos) = [
+            Action::Step(rotation),
+            Action::JumpUp(rotation),
+            Action::JumpDown(rotation),
+            Action::Fall(rotation),
+        ]
+        .into_iter()
+        .find_map(|action| Some((action, self.walk.run(&self.view, pos, action)?)))?;
+
+        self.place_player(pos.pn);
+        Some(action)
     }
 }
@@ -1,4 +1,21 @@
 use crate::{land::vec_map::Map, IndexedMesh, Render, Vert};
+use std::mem;
+
+/// The mesh on the CPU side.
+///
+/// Made without the render, so it can be tested or exported
+/// and uploaded later.
+#[derive(Default)]
+pub(crate) struct MeshData {
+    pub verts: Vec<Vert>,
+    pub indxs: Vec<u32>,
+}
+
+impl MeshData {
+    pub fn upload(&self, ren: &Render) -> IndexedMesh {
+        ren.make_mesh(&self.verts, &self.indxs)
+    }
+}
 
 pub(crate) struct VertexData {
     pub face: [u32; 3],
@@ -45,13 +62,12 @@ impl Builder {
         }
     }
 
-    pub fn mesh(&self, ren: &Render) -> IndexedMesh {
-        ren.make_mesh(&self.verts, &self.indxs)
-    }
-
-    pub fn clear(&mut self) {
-        self.verts.clear();
-        self.indxs.clear();
+    /// Takes the built mesh and clears the builder.
+    pub fn take(&mut self) -> MeshData {
         self.added.clear();
+        MeshData {
+            verts: mem::take(&mut self.verts),
+            indxs: mem::take(&mut self.indxs),
+        }
     }
 }
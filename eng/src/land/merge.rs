@@ -0,0 +1,510 @@
+use crate::{
+    land::{shape::SHIFT, MeshData},
+    Vert,
+};
+use shr::cgm::*;
+use std::collections::{hash_map::Entry, BTreeMap, HashMap, HashSet};
+
+/// The precision of the compared coordinates.
+const EPS: f32 = 0.001;
+
+/// Rounds the coordinate to compare it as a key.
+fn round(v: f32) -> i64 {
+    (v * 1024.).round() as i64
+}
+
+/// The faces that can be merged together.
+///
+/// They lie in the same plane, show the same sprite the same way
+/// and are placed on the same grid.
+#[derive(Copy, Clone, Eq, Ord, PartialEq, PartialOrd)]
+struct Plane {
+    axis: usize,
+    positive: bool,
+    depth: i64,
+    sprite: (i64, i64),
+    size: (i64, i64),
+    phase: (i64, i64),
+    /// The sprite axes follow the swapped plane axes.
+    swap: bool,
+    /// The sprite axes go against the plane axes.
+    flip: (bool, bool),
+    /// The triangles turn the same way as the plane axes.
+    ccw: bool,
+}
+
+/// The quad fully covered by a sprite.
+struct Face {
+    plane: Plane,
+    cell: (i64, i64),
+    /// The lower corner in the plane coordinates.
+    min: Vec2,
+    /// The size in the plane coordinates.
+    size: Vec2,
+    depth: f32,
+    nm: Vec3,
+    origin: Vec2,
+    indxs: [u32; 6],
+}
+
+/// The plane axes of the `axis` normal, they turn counterclockwise around it.
+fn plane_axes(axis: usize) -> (usize, usize) {
+    ((axis + 1) % 3, (axis + 2) % 3)
+}
+
+/// Recognizes the full face made of two triangles.
+fn face(mesh: &MeshData, indxs: [u32; 6], sprite: f32) -> Option<Face> {
+    let mut corners = Vec::with_capacity(4);
+    for idx in indxs {
+        if !corners.contains(&idx) {
+            corners.push(idx);
+        }
+    }
+
+    if corners.len() != 4 {
+        return None;
+    }
+
+    let verts: Vec<&Vert> = corners
+        .iter()
+        .map(|&idx| &mesh.verts[idx as usize])
+        .collect();
+    let nm = verts[0].nm;
+    if verts
+        .iter()
+        .any(|vert| vert.sp != Vec4::zero() || (vert.nm - nm).magnitude() > EPS)
+    {
+        return None;
+    }
+
+    // The face must look along an axis
+    let axis = (0..3).find(|&axis| (nm[axis].abs() - 1.).abs() < EPS)?;
+    let (b, c) = plane_axes(axis);
+    let depth = verts[0].co[axis];
+    if verts.iter().any(|vert| (vert.co[axis] - depth).abs() > EPS) {
+        return None;
+    }
+
+    let min = verts
+        .iter()
+        .fold(Vec2::new(f32::MAX, f32::MAX), |min, vert| {
+            Vec2::new(min.x.min(vert.co[b]), min.y.min(vert.co[c]))
+        });
+    let max = verts
+        .iter()
+        .fold(Vec2::new(f32::MIN, f32::MIN), |max, vert| {
+            Vec2::new(max.x.max(vert.co[b]), max.y.max(vert.co[c]))
+        });
+
+    let size = max - min;
+    if size.x < EPS || size.y < EPS {
+        return None;
+    }
+
+    // The corners in the face and in the sprite, both must be 0 or 1
+    let unit = |v: f32| match v {
+        _ if v.abs() < EPS => Some(false),
+        _ if (v - 1.).abs() < EPS => Some(true),
+        _ => None,
+    };
+
+    let centroid = verts.iter().map(|vert| vert.st).sum::<Vec2>() / 4.;
+    let origin = Vec2::new(
+        (centroid.x / sprite).floor() * sprite,
+        (centroid.y / sprite).floor() * sprite,
+    );
+
+    let mut pairs = Vec::with_capacity(4);
+    for vert in &verts {
+        let t = (Vec2::new(vert.co[b], vert.co[c]) - min).div_element_wise(size);
+        let l = ((vert.st - origin) / sprite - Vec2::new(SHIFT, SHIFT)) / (1. - 2. * SHIFT);
+        pairs.push(((unit(t.x)?, unit(t.y)?), (unit(l.x)?, unit(l.y)?)));
+    }
+
+    // Every corner of the face is the corner of the sprite
+    let orientation = [false, true].into_iter().find_map(|swap| {
+        [(false, false), (false, true), (true, false), (true, true)]
+            .into_iter()
+            .find(|&flip| {
+                let mut seen = HashSet::new();
+                pairs.iter().all(|&(t, l)| {
+                    let t = if swap { (t.1, t.0) } else { t };
+                    seen.insert(t) && l == (t.0 != flip.0, t.1 != flip.1)
+                })
+            })
+            .map(|flip| (swap, flip))
+    })?;
+
+    let (swap, flip) = orientation;
+    let at = |idx: u32| mesh.verts[idx as usize].co;
+    let area = (at(indxs[1]) - at(indxs[0])).cross(at(indxs[2]) - at(indxs[0]));
+    let cell = |min: f32, size: f32| {
+        let cell = (min / size + EPS).floor();
+        (cell as i64, round(min - cell * size))
+    };
+
+    let (i, phase_i) = cell(min.x, size.x);
+    let (j, phase_j) = cell(min.y, size.y);
+    let plane = Plane {
+        axis,
+        positive: nm[axis] > 0.,
+        depth: round(depth),
+        sprite: (round(origin.x / sprite), round(origin.y / sprite)),
+        size: (round(size.x), round(size.y)),
+        phase: (phase_i, phase_j),
+        swap,
+        flip,
+        ccw: area[axis] > 0.,
+    };
+
+    Some(Face {
+        plane,
+        cell: (i, j),
+        min,
+        size,
+        depth,
+        nm,
+        origin,
+        indxs,
+    })
+}
+
+/// Merges the coplanar adjacent faces covered by the same sprite into larger quads.
+///
+/// The merged quads keep the sprite coordinates in the `st`
+/// and the sprite rect in the `sp`, so the sprite repeats on every cell
+/// and the mesh looks the same. `sprite` is the size of a sprite in the atlas.
+/// Other triangles are kept as they are.
+pub(crate) fn merge(mesh: &MeshData, sprite: f32) -> MeshData {
+    let mut out = MeshData::default();
+    let mut remap = vec![u32::MAX; mesh.verts.len()];
+    let mut keep = |out: &mut MeshData, idx: u32| {
+        let new = &mut remap[idx as usize];
+        if *new == u32::MAX {
+            *new = out.verts.len() as u32;
+            out.verts.push(mesh.verts[idx as usize]);
+        }
+
+        out.indxs.push(*new);
+    };
+
+    let mut planes: BTreeMap<Plane, Vec<Face>> = BTreeMap::new();
+    let tris: Vec<_> = mesh.indxs.chunks_exact(3).collect();
+    let mut i = 0;
+    while i < tris.len() {
+        let face = tris.get(i + 1).and_then(|next| {
+            let indxs = [
+                tris[i][0], tris[i][1], tris[i][2], next[0], next[1], next[2],
+            ];
+            face(mesh, indxs, sprite)
+        });
+
+        match face {
+            Some(face) => {
+                planes.entry(face.plane).or_default().push(face);
+                i += 2;
+            }
+            None => {
+                for &idx in tris[i] {
+                    keep(&mut out, idx);
+                }
+
+                i += 1;
+            }
+        }
+    }
+
+    for (plane, faces) in planes {
+        let mut cells = HashMap::with_capacity(faces.len());
+        for (n, face) in faces.iter().enumerate() {
+            // The overlapping faces are kept as they are
+            match cells.entry(face.cell) {
+                Entry::Vacant(en) => {
+                    en.insert(n);
+                }
+                Entry::Occupied(_) => {
+                    for idx in face.indxs {
+                        keep(&mut out, idx);
+                    }
+                }
+            }
+        }
+
+        let mut order: Vec<_> = cells.keys().copied().collect();
+        order.sort_unstable_by_key(|&(i, j)| (j, i));
+
+        let mut used = HashSet::with_capacity(cells.len());
+        let free = |used: &HashSet<_>, cell| cells.contains_key(&cell) && !used.contains(&cell);
+        for (i, j) in order {
+            if used.contains(&(i, j)) {
+                continue;
+            }
+
+            // Grow the row, then grow the rows while they're full
+            let mut n = 1;
+            while free(&used, (i + n, j)) {
+                n += 1;
+            }
+
+            let mut m = 1;
+            while (i..i + n).all(|i| free(&used, (i, j + m))) {
+                m += 1;
+            }
+
+            for j in j..j + m {
+                for i in i..i + n {
+                    used.insert((i, j));
+                }
+            }
+
+            let face = &faces[cells[&(i, j)]];
+            if n == 1 && m == 1 {
+                for idx in face.indxs {
+                    keep(&mut out, idx);
+                }
+
+                continue;
+            }
+
+            quad(&mut out, plane, face, (n, m), sprite);
+        }
+    }
+
+    out
+}
+
+/// Adds the quad of `span` cells starting from the `face`.
+fn quad(out: &mut MeshData, plane: Plane, face: &Face, span: (i64, i64), sprite: f32) {
+    let (b, c) = plane_axes(plane.axis);
+    let (n, m) = (span.0 as f32, span.1 as f32);
+    let inset = SHIFT * sprite;
+    let rect = 1. - 2. * SHIFT;
+    let sp = Vec4::new(
+        face.origin.x + inset,
+        face.origin.y + inset,
+        rect * sprite,
+        rect * sprite,
+    );
+
+    let offset = out.verts.len() as u32;
+    for (cu, cv) in [(0., 0.), (1., 0.), (1., 1.), (0., 1.)] {
+        let mut co = Vec3::new(0., 0., 0.);
+        co[plane.axis] = face.depth;
+        co[b] = face.min.x + cu * n * face.size.x;
+        co[c] = face.min.y + cv * m * face.size.y;
+
+        // The sprite coordinates count the cells
+        let (t, span) = match plane.swap {
+            false => (Vec2::new(cu * n, cv * m), Vec2::new(n, m)),
+            true => (Vec2::new(cv * m, cu * n), Vec2::new(m, n)),
+        };
+
+        let st = Vec2::new(
+            if plane.flip.0 { span.x - t.x } else { t.x },
+            if plane.flip.1 { span.y - t.y } else { t.y },
+        );
+
+        out.verts.push(Vert {
+            co,
+            nm: face.nm,
+            st,
+            sp,
+        });
+    }
+
+    let tris = match plane.ccw {
+        true => [0, 1, 2, 0, 2, 3],
+        false => [0, 2, 1, 0, 3, 2],
+    };
+
+    out.indxs.extend(tris.map(|idx| idx + offset));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPRITE: f32 = 0.5;
+
+    /// The atlas coordinates of the `l` point of the sprite.
+    fn st(sprite: (u32, u32), l: Vec2) -> Vec2 {
+        let shift = Vec2::new(SHIFT, SHIFT);
+        (l * (1. - 2. * SHIFT) + shift) * SPRITE
+            + Vec2::new(sprite.0 as f32, sprite.1 as f32) * SPRITE
+    }
+
+    /// Adds the quad of `corners` with the sprite corners in order.
+    fn add(mesh: &mut MeshData, corners: [Vec3; 4], nm: Vec3, sprite: (u32, u32)) {
+        let offset = mesh.verts.len() as u32;
+        let ls = [(0., 0.), (1., 0.), (1., 1.), (0., 1.)];
+        for (co, (s, t)) in corners.into_iter().zip(ls) {
+            mesh.verts.push(Vert {
+                co,
+                nm,
+                st: st(sprite, Vec2::new(s, t)),
+                sp: Vec4::zero(),
+            });
+        }
+
+        mesh.indxs
+            .extend([0, 1, 2, 0, 2, 3].map(|idx| idx + offset));
+    }
+
+    fn top(mesh: &mut MeshData, x: f32, y: f32, z: f32, sprite: (u32, u32)) {
+        let corners = [
+            Vec3::new(x, y, z + 1.),
+            Vec3::new(x + 1., y, z + 1.),
+            Vec3::new(x + 1., y, z),
+            Vec3::new(x, y, z),
+        ];
+
+        add(mesh, corners, Vec3::new(0., 1., 0.), sprite);
+    }
+
+    /// The half high side looking to +x, the sprite goes down.
+    fn side(mesh: &mut MeshData, x: f32, y: f32, z: f32, sprite: (u32, u32)) {
+        let corners = [
+            Vec3::new(x, y + 0.5, z + 1.),
+            Vec3::new(x, y + 0.5, z),
+            Vec3::new(x, y, z),
+            Vec3::new(x, y, z + 1.),
+        ];
+
+        add(mesh, corners, Vec3::new(1., 0., 0.), sprite);
+    }
+
+    /// The atlas coordinates the point gets in the shader.
+    fn sample(mesh: &MeshData, pn: Vec3, nm: Vec3) -> Option<Vec2> {
+        let fract = |v: Vec2| Vec2::new(v.x - v.x.floor(), v.y - v.y.floor());
+        mesh.indxs.chunks_exact(3).find_map(|tri| {
+            let [a, b, c] = [tri[0], tri[1], tri[2]].map(|idx| &mesh.verts[idx as usize]);
+            if (a.nm - nm).magnitude() > EPS {
+                return None;
+            }
+
+            let (v0, v1, v2) = (b.co - a.co, c.co - a.co, pn - a.co);
+            let normal = v0.cross(v1).normalize();
+            if v2.dot(normal).abs() > EPS {
+                return None;
+            }
+
+            let (d00, d01, d11) = (v0.dot(v0), v0.dot(v1), v1.dot(v1));
+            let (d20, d21) = (v2.dot(v0), v2.dot(v1));
+            let denom = d00 * d11 - d01 * d01;
+            let v = (d11 * d20 - d01 * d21) / denom;
+            let w = (d00 * d21 - d01 * d20) / denom;
+            let u = 1. - v - w;
+            if u < -EPS || v < -EPS || w < -EPS {
+                return None;
+            }
+
+            let st = a.st * u + b.st * v + c.st * w;
+            let sp = a.sp * u + b.sp * v + c.sp * w;
+            Some(match sp.z > 0. {
+                true => {
+                    sp.truncate().truncate() + fract(st).mul_element_wise(Vec2::new(sp.z, sp.w))
+                }
+                false => st,
+            })
+        })
+    }
+
+    /// Checks the merged mesh looks the same in the middle of every triangle.
+    fn same(mesh: &MeshData, merged: &MeshData) {
+        for tri in mesh.indxs.chunks_exact(3) {
+            let [a, b, c] = [tri[0], tri[1], tri[2]].map(|idx| &mesh.verts[idx as usize]);
+            let pn = (a.co + b.co + c.co) / 3.;
+            let expected = sample(mesh, pn, a.nm).unwrap();
+            let actual = sample(merged, pn, a.nm).unwrap();
+            assert!(
+                (expected - actual).magnitude() < EPS,
+                "{pn:?}: {expected:?} != {actual:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn flat() {
+        let mut mesh = MeshData::default();
+        for x in 0..4 {
+            for z in 0..4 {
+                top(&mut mesh, x as f32, 2.5, z as f32, (1, 0));
+            }
+        }
+
+        // The other sprite isn't merged with the field
+        top(&mut mesh, 4., 2.5, 0., (0, 1));
+
+        // The sides are merged up and along
+        for z in 0..3 {
+            side(&mut mesh, 5., 1.5, z as f32, (1, 1));
+            side(&mut mesh, 5., 2., z as f32, (1, 1));
+        }
+
+        assert_eq!(mesh.verts.len(), 23 * 4);
+        let merged = merge(&mesh, SPRITE);
+        assert_eq!(merged.verts.len(), 3 * 4);
+        assert_eq!(merged.indxs.len(), 3 * 6);
+        same(&mesh, &merged);
+    }
+
+    #[test]
+    fn partial() {
+        let mut mesh = MeshData::default();
+        top(&mut mesh, 0., 0., 0., (0, 0));
+        top(&mut mesh, 1., 0., 0., (0, 0));
+
+        // The overlapping face stays
+        top(&mut mesh, 0., 0., 0., (0, 0));
+
+        // The face covered by a part of the sprite can't repeat it
+        let corners = [
+            Vec3::new(2., 0., 1.),
+            Vec3::new(3., 0., 1.),
+            Vec3::new(3., 0., 0.),
+            Vec3::new(2., 0., 0.),
+        ];
+
+        let offset = mesh.verts.len() as u32;
+        for (co, (s, t)) in corners
+            .into_iter()
+            .zip([(0., 0.), (0.5, 0.), (0.5, 1.), (0., 1.)])
+        {
+            mesh.verts.push(Vert {
+                co,
+                nm: Vec3::new(0., 1., 0.),
+                st: st((0, 0), Vec2::new(s, t)),
+                sp: Vec4::zero(),
+            });
+        }
+
+        mesh.indxs
+            .extend([0, 1, 2, 0, 2, 3].map(|idx| idx + offset));
+
+        // The lonely triangle stays
+        let offset = mesh.verts.len() as u32;
+        for co in [
+            Vec3::new(0., 1., 0.),
+            Vec3::new(1., 1., 0.),
+            Vec3::new(0., 1., 1.),
+        ] {
+            mesh.verts.push(Vert {
+                co,
+                nm: Vec3::new(0., 1., 0.),
+                st: Vec2::new(0., 0.),
+                sp: Vec4::zero(),
+            });
+        }
+
+        mesh.indxs.extend([offset, offset + 1, offset + 2]);
+
+        let merged = merge(&mesh, SPRITE);
+        assert_eq!(merged.verts.len(), 4 + 4 + 4 + 3);
+        assert_eq!(merged.indxs.len(), 6 + 6 + 6 + 3);
+        same(&mesh, &merged);
+
+        // Nothing to merge twice
+        let again = merge(&merged, SPRITE);
+        assert_eq!(again.verts.len(), merged.verts.len());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::land::{
+    merge, polygon::Polygons, variant::VariantSet, view::Snapshot, Builder, MeshData,
+};
+use core::prelude::*;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+struct Job {
+    snapshot: Snapshot,
+    cancelled: Arc<AtomicBool>,
+}
+
+struct Done {
+    cl: ClusterPoint,
+    mesh: MeshData,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Meshes the clusters on the worker threads.
+///
+/// The clusters are queued as snapshots, so the workers don't touch the view.
+/// The queue is bounded, when it's full the cluster has to be queued later.
+/// With the sprite size given to `merge`, the coplanar faces are merged.
+pub(crate) struct Mesher {
+    jobs: Option<SyncSender<Job>>,
+    done: Receiver<Done>,
+    /// The flags of the queued jobs to cancel them.
+    queued: HashMap<ClusterPoint, Arc<AtomicBool>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Mesher {
+    pub fn new(
+        variant_set: Arc<VariantSet>,
+        polygons: Arc<Polygons>,
+        merge: Option<f32>,
+        workers: usize,
+        capacity: usize,
+    ) -> Self {
+        let (jobs, receiver) = mpsc::sync_channel::<Job>(capacity);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let (sender, done) = mpsc::channel();
+        let workers = (0..workers.max(1))
+            .map(|i| {
+                let receiver = Arc::clone(&receiver);
+                let sender = sender.clone();
+                let variant_set = Arc::clone(&variant_set);
+                let polygons = Arc::clone(&polygons);
+                thread::Builder::new()
+                    .name(format!("mesher {i}"))
+                    .spawn(move || {
+                        let mut builder = Builder::with_capacity(64);
+                        loop {
+                            // The lock is released before the meshing
+                            let job = match receiver.lock().unwrap().recv() {
+                                Ok(job) => job,
+                                Err(_) => break,
+                            };
+
+                            if job.cancelled.load(Ordering::Relaxed) {
+                                continue;
+                            }
+
+                            // The empty mesh is sent too, so the job is done
+                            let cl = job.snapshot.cl();
+                            let mesh = job
+                                .snapshot
+                                .build(&variant_set, &polygons, &mut builder)
+                                .unwrap_or_default();
+
+                            let mesh = match merge {
+                                Some(sprite) => merge::merge(&mesh, sprite),
+                                None => mesh,
+                            };
+
+                            let done = Done {
+                                cl,
+                                mesh,
+                                cancelled: job.cancelled,
+                            };
+
+                            if sender.send(done).is_err() {
+                                break;
+                            }
+                        }
+                    })
+                    .expect("spawn mesher thread")
+            })
+            .collect();
+
+        Self {
+            jobs: Some(jobs),
+            done,
+            queued: HashMap::default(),
+            workers,
+        }
+    }
+
+    /// Queues the snapshot and cancels the previous job of its cluster.
+    ///
+    /// Returns the snapshot back if the queue is full.
+    pub fn submit(&mut self, snapshot: Snapshot) -> Result<(), Snapshot> {
+        let cl = snapshot.cl();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let job = Job {
+            snapshot,
+            cancelled: Arc::clone(&cancelled),
+        };
+
+        match self.jobs.as_ref().expect("jobs sender").try_send(job) {
+            Ok(()) => {}
+            Err(TrySendError::Full(job) | TrySendError::Disconnected(job)) => {
+                return Err(job.snapshot)
+            }
+        }
+
+        self.cancel(cl);
+        self.queued.insert(cl, cancelled);
+        Ok(())
+    }
+
+    /// Cancels the job of the cluster, its mesh won't be returned.
+    pub fn cancel(&mut self, cl: ClusterPoint) {
+        if let Some(cancelled) = self.queued.remove(&cl) {
+            cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the finished meshes, the cancelled ones are skipped.
+    pub fn finished(&mut self) -> Vec<(ClusterPoint, MeshData)> {
+        let mut meshes = Vec::new();
+        while let Ok(done) = self.done.try_recv() {
+            if done.cancelled.load(Ordering::Relaxed) {
+                continue;
+            }
+
+            self.queued.remove(&done.cl);
+            meshes.push((done.cl, done.mesh));
+        }
+
+        meshes
+    }
+}
+
+impl Drop for Mesher {
+    fn drop(&mut self) {
+        for cancelled in self.queued.values() {
+            cancelled.store(true, Ordering::Relaxed);
+        }
+
+        // Workers stop when the sender is dropped
+        self.jobs.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
@@ -1,4 +1,6 @@
 mod builder;
+mod merge;
+mod mesher;
 mod overlay;
 pub(crate) mod polygon;
 mod shape;
@@ -7,7 +9,8 @@ mod vec_map;
 mod view;
 
 pub(crate) use self::{
-    builder::Builder,
+    builder::{Builder, MeshData},
+    mesher::Mesher,
     overlay::{Connections, Overlay},
     shape::{Factory, Parameters},
     view::ClusterView,
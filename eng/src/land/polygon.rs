@@ -91,11 +91,11 @@ impl Polygon {
         }
     }
 
-    pub fn flipped_x(&self) -> FlippedX {
+    pub fn flipped_x(&self) -> FlippedX<'_> {
         FlippedX(self)
     }
 
-    pub fn flipped_y(&self) -> FlippedY {
+    pub fn flipped_y(&self) -> FlippedY<'_> {
         FlippedY(self)
     }
 
@@ -7,7 +7,7 @@ use core::prelude::*;
 use shr::cgm::Vec2;
 use std::{
     collections::{hash_map::Entry, BTreeSet, HashMap, HashSet},
-    rc::Rc,
+    sync::Arc,
 };
 
 pub(crate) struct Parameters<'a> {
@@ -24,8 +24,11 @@ struct Key {
     discard: BTreeSet<u32>,
 }
 
+/// The inset of the sprite coordinates, so the faces don't catch the neighbour sprites.
+pub(crate) const SHIFT: f32 = 0.00001;
+
 pub(crate) struct Factory {
-    shapes: HashMap<Key, Rc<Shape>>,
+    shapes: HashMap<Key, Arc<Shape>>,
     mapper: Mapper,
 }
 
@@ -37,9 +40,7 @@ impl Factory {
         }
     }
 
-    pub fn make(&mut self, params: Parameters) -> Rc<Shape> {
-        const SHIFT: f32 = 0.00001;
-
+    pub fn make(&mut self, params: Parameters) -> Arc<Shape> {
         let Parameters {
             mesh,
             rotation,
@@ -53,12 +54,12 @@ impl Factory {
             discard: mesh
                 .slots()
                 .values()
-                .filter_map(|(slot, index)| discard.contains(slot).then(|| index))
+                .filter_map(|(slot, index)| discard.contains(slot).then_some(index))
                 .collect(),
         };
 
         match self.shapes.entry(key) {
-            Entry::Occupied(en) => Rc::clone(en.get()),
+            Entry::Occupied(en) => Arc::clone(en.get()),
             Entry::Vacant(en) => {
                 let shape = Shape::new(
                     mesh,
@@ -75,7 +76,7 @@ impl Factory {
                             * self.mapper.multiplier()
                     },
                 );
-                Rc::clone(en.insert(Rc::new(shape)))
+                Arc::clone(en.insert(Arc::new(shape)))
             }
         }
     }
@@ -142,6 +143,7 @@ impl Shape {
                     co: rotation.transform_vec(vert.co),
                     nm: rotation.transform_vec(vert.nm),
                     st: transform_st(vert.st),
+                    sp: vert.sp,
                 })
                 .collect(),
             slotted: slotted.into_boxed_slice(),
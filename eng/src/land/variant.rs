@@ -4,7 +4,7 @@ use crate::{
 };
 use core::prelude::*;
 use shr::cgm::*;
-use std::{collections::HashMap, error, fmt, rc::Rc};
+use std::{collections::HashMap, error, fmt, sync::Arc};
 
 #[derive(Debug)]
 pub(crate) enum Error {
@@ -22,7 +22,7 @@ impl fmt::Display for Error {
 impl error::Error for Error {}
 
 pub(crate) struct Mesh {
-    pub shape: Rc<Shape>,
+    pub shape: Arc<Shape>,
     pub sprites_st: Box<[Vec2]>,
     pub height: Height,
 }
@@ -84,6 +84,7 @@ impl Variant {
                             u32::MAX => self.sprite_st,
                             _ => mesh.sprites_st[slot as usize],
                         },
+                    sp: vert.sp,
                 },
                 builder,
             );
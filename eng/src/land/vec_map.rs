@@ -14,7 +14,7 @@ impl<T: Copy> Map<T> {
             let key = key as usize;
             if key >= self.map.len() {
                 let additional = key - self.map.len() + 1;
-                self.map.extend(std::iter::repeat(None).take(additional));
+                self.map.extend(std::iter::repeat_n(None, additional));
             }
 
             debug_assert!(key < self.map.len());
@@ -1,26 +1,21 @@
-use crate::{
-    land::{
-        polygon::{Axis, Polygons},
-        variant::VariantSet,
-        Builder, Connections,
-    },
-    IndexedMesh, Render,
+use crate::land::{
+    polygon::{Axis, Polygons},
+    variant::VariantSet,
+    Builder, Connections, MeshData, Mesher,
 };
 use core::{
+    auto,
     map::{Column, Map},
+    net::TileSlab as Slab,
     path::{Pass, Space},
+    point::ChunkPoints,
     prelude::*,
     tile,
 };
 use shr::cgm::Vec3;
+use std::{collections::HashSet, sync::Arc};
 
-#[derive(Copy, Clone)]
-enum Slab {
-    Empty,
-    Base(TileIndex, VariantIndex),
-    Trunk(u8),
-}
-
+#[derive(Clone)]
 struct Data {
     keys: Chunk<Slab>,
     connections: Chunk<Connections>,
@@ -43,12 +38,6 @@ impl Default for Data {
     }
 }
 
-impl From<(TileIndex, VariantIndex)> for Slab {
-    fn from((tile, variant): (TileIndex, VariantIndex)) -> Self {
-        Self::Base(tile, variant)
-    }
-}
-
 impl AsRef<Chunk<Slab>> for Data {
     fn as_ref(&self) -> &Chunk<Slab> {
         &self.keys
@@ -87,23 +76,30 @@ impl AsMut<Chunk<Pass>> for Data {
 
 pub(crate) struct ClusterView {
     map: Map<Data>,
-    variant_set: VariantSet,
-    polygons: Polygons,
+    variant_set: Arc<VariantSet>,
+    polygons: Arc<Polygons>,
     builder: Builder,
+    /// The clusters whose meshes are out of date.
+    dirty: HashSet<ClusterPoint>,
 }
 
 impl ClusterView {
     pub fn new(variant_set: VariantSet, polygons: Polygons) -> Self {
         Self {
             map: Map::default(),
-            variant_set,
-            polygons,
+            variant_set: Arc::new(variant_set),
+            polygons: Arc::new(polygons),
             builder: Builder::with_capacity(64),
+            dirty: HashSet::default(),
         }
     }
 
-    pub fn place(&mut self, pn: Point, tile: &tile::Tile, variant: VariantIndex) {
+    /// Places the tile, the variant is picked by the tile rules if it's `None`.
+    pub fn place(&mut self, pn: Point, tile: &tile::Tile, variant: Option<VariantIndex>) {
         let height = tile.height;
+        let variant = variant
+            .or_else(|| auto::pick(&tile.variants, pn, height, &self.map))
+            .unwrap_or(VariantIndex(0));
         let mut column = self.map.column_mut(pn, height);
         let key = (tile.idx, variant);
         *column.get_mut(0) = key.into();
@@ -122,144 +118,387 @@ impl ClusterView {
         for (dst, src) in column.iter_mut().zip(variant.connections()) {
             *dst = *src;
         }
+
+        self.mark(pn, height);
     }
 
-    pub fn mesh(&mut self, ren: &Render, offset: Vec3, cl: ClusterPoint) -> IndexedMesh {
-        let builder = &mut self.builder;
-        let mut vicinity = self.map.vicinity(cl).unwrap();
-        for (slab, ch) in self.map.iter(cl).unwrap() {
-            let key = match *slab {
-                Slab::Base(tile, variant) => (tile, variant),
-                _ => continue,
+    /// Removes the tile at the point.
+    ///
+    /// Returns the base and the height of the removed tile
+    /// or `None` if there is no tile.
+    pub fn remove(&mut self, pn: Point) -> Option<(Point, Height)> {
+        let base = self.base(pn)?;
+
+        // The trunk levels go up from the base
+        let mut height = 1;
+        let mut curr = base.to(Side::Up);
+        while self.map.get::<Slab>(curr) == Some(&Slab::Trunk(height - 1)) {
+            height += 1;
+            curr = curr.to(Side::Up);
+        }
+
+        let height = Height::new(height).unwrap();
+        for slab in self.map.column_mut(base, height).iter_mut() {
+            *slab = Slab::Empty;
+        }
+
+        for conn in self.map.column_mut(base, height).iter_mut() {
+            *conn = Connections::new();
+        }
+
+        for pass in self.map.column_mut(base, height).iter_mut() {
+            *pass = Pass::empty();
+        }
+
+        self.mark(base, height);
+        Some((base, height))
+    }
+
+    /// Picks again the variants of the tiles around the column,
+    /// like the server does when the tile is placed or removed.
+    pub fn retile<'t, F>(&mut self, pn: Point, height: Height, tiles: F)
+    where
+        F: Fn(TileIndex) -> Option<&'t tile::Tile>,
+    {
+        let bases: HashSet<_> = auto::neighbours(pn, height)
+            .into_iter()
+            .filter_map(|pn| self.base(pn))
+            .collect();
+
+        for base in bases {
+            let (tile, variant) = match self.map.get::<Slab>(base) {
+                Some(&Slab::Base(tile, variant)) => (tile, variant),
+                _ => unreachable!(),
             };
 
-            let local_offset: Vec3 = ch.into();
-            let variant = self.variant_set.get(key);
-            let variant_height = variant.height();
-            let connections = variant.connections();
-
-            variant.build(
-                offset + local_offset,
-                |level, shape_height| {
-                    let mut sides = Sides::empty();
-                    let ch = ch.to(Side::Up, level).unwrap();
-
-                    if level + shape_height == variant_height {
-                        match ch.to(Side::Up, shape_height) {
-                            Ok(hi) => {
-                                let other = vicinity.center().connection(hi);
+            let tile = match tiles(tile) {
+                Some(tile) => tile,
+                None => continue,
+            };
+
+            match auto::pick(&tile.variants, base, tile.height, &self.map) {
+                Some(picked) if picked != variant => self.place(base, tile, Some(picked)),
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the point of the base of the tile at the point.
+    fn base(&self, pn: Point) -> Option<Point> {
+        match *self.map.get::<Slab>(pn)? {
+            Slab::Empty => None,
+            Slab::Base(..) => Some(pn),
+            Slab::Trunk(level) => Some(pn - Height::new(level + 1).unwrap()),
+        }
+    }
+
+    /// Marks the clusters whose meshes depend on the column.
+    ///
+    /// The faces of the tile are built in the cluster of its base,
+    /// so the tiles next to the column mark the clusters of their bases.
+    /// It covers the tiles reaching into the upper cluster.
+    fn mark(&mut self, pn: Point, height: Height) {
+        self.dirty.insert(pn.cluster_point());
+        let mut curr = pn;
+        for _ in 0..height.get() {
+            for side in Side::ENUM {
+                if let Some(base) = self.base(curr.to(side)) {
+                    self.dirty.insert(base.cluster_point());
+                }
+            }
+
+            curr = curr.to(Side::Up);
+        }
+    }
+
+    /// Removes the tiles of the cluster.
+    ///
+    /// Tiles that reach into the cluster from below are kept,
+    /// the trunks of its tiles reaching into the upper cluster are removed.
+    pub fn clear(&mut self, cl: ClusterPoint) {
+        // The neighbours cull their faces by the cluster,
+        // the lower ones can reach it with their tiles
+        self.dirty.insert(cl);
+        let down = cl.to(Side::Down);
+        let neighbours = Side::ENUM
+            .into_iter()
+            .map(|side| cl.to(side))
+            .chain([Side::Left, Side::Right, Side::Forth, Side::Back].map(|side| down.to(side)));
+
+        for cl in neighbours {
+            if self.map.chunk(cl).is_some() {
+                self.dirty.insert(cl);
+            }
+        }
+
+        let old = self.map.remove(cl);
+        let data = self.map.chunk_mut(cl);
+        let old = match old {
+            Some(old) => old,
+            None => return,
+        };
+
+        let mut from_below = false;
+        for ch in ChunkPoints::new() {
+            from_below = matches!(old.keys.get(ch), Slab::Trunk(_)) && (ch.y() == 0 || from_below);
+            if from_below {
+                *data.keys.get_mut(ch) = *old.keys.get(ch);
+                *data.connections.get_mut(ch) = *old.connections.get(ch);
+                *data.passes.get_mut(ch) = *old.passes.get(ch);
+            }
+        }
+
+        let up = cl.to(Side::Up);
+        if self.map.chunk(up).is_none() {
+            return;
+        }
+
+        let data = self.map.chunk_mut(up);
+        let mut from_below = false;
+        for ch in ChunkPoints::new() {
+            from_below = matches!(data.keys.get(ch), Slab::Trunk(_)) && (ch.y() == 0 || from_below);
+            if from_below {
+                *data.keys.get_mut(ch) = Slab::Empty;
+                *data.connections.get_mut(ch) = Connections::new();
+                *data.passes.get_mut(ch) = Pass::empty();
+            }
+        }
+    }
+
+    /// Queues the changed clusters to the `mesher`.
+    ///
+    /// The clusters that don't fit into the queue stay dirty,
+    /// the unloaded ones cancel their pending jobs.
+    pub fn spawn_dirty(&mut self, mesher: &mut Mesher) {
+        let dirty: Vec<_> = self.dirty.drain().collect();
+        let mut full = false;
+        for cl in dirty {
+            if full {
+                self.dirty.insert(cl);
+                continue;
+            }
+
+            match self.snapshot(cl) {
+                Some(snapshot) => {
+                    if mesher.submit(snapshot).is_err() {
+                        full = true;
+                        self.dirty.insert(cl);
+                    }
+                }
+                None => mesher.cancel(cl),
+            }
+        }
+    }
+
+    /// Makes the mesher sharing the variants of the view.
+    pub fn mesher(&self, merge: Option<f32>, workers: usize, capacity: usize) -> Mesher {
+        Mesher::new(
+            Arc::clone(&self.variant_set),
+            Arc::clone(&self.polygons),
+            merge,
+            workers,
+            capacity,
+        )
+    }
+
+    /// Copies the cluster with the neighbours its mesh depends on.
+    pub fn snapshot(&self, cl: ClusterPoint) -> Option<Snapshot> {
+        let mut map = Map::default();
+        *map.chunk_mut(cl) = self.map.chunk(cl)?.clone();
+
+        // The vicinity looks into the upper neighbours too
+        let up = cl.to(Side::Up);
+        let neighbours = Side::ENUM
+            .into_iter()
+            .map(|side| cl.to(side))
+            .chain([Side::Left, Side::Right, Side::Forth, Side::Back].map(|side| up.to(side)));
+
+        for cl in neighbours {
+            if let Some(data) = self.map.chunk(cl) {
+                *map.chunk_mut(cl) = data.clone();
+            }
+        }
+
+        Some(Snapshot { cl, map })
+    }
+
+    /// Builds the mesh of the cluster, `None` if it isn't loaded.
+    pub fn mesh(&mut self, offset: Vec3, cl: ClusterPoint) -> Option<MeshData> {
+        let builder = &mut self.builder;
+        build(
+            &self.map,
+            &self.variant_set,
+            &self.polygons,
+            builder,
+            offset,
+            cl,
+        )
+    }
+}
+
+/// The copy of the cluster to mesh it off the main thread.
+pub(crate) struct Snapshot {
+    cl: ClusterPoint,
+    map: Map<Data>,
+}
+
+impl Snapshot {
+    pub fn cl(&self) -> ClusterPoint {
+        self.cl
+    }
+
+    /// Builds the mesh of the cluster at its world position.
+    pub fn build(
+        &self,
+        variant_set: &VariantSet,
+        polygons: &Polygons,
+        builder: &mut Builder,
+    ) -> Option<MeshData> {
+        let offset = Point::new(ChunkPoint::new(0, 0, 0).unwrap(), self.cl).into();
+        build(&self.map, variant_set, polygons, builder, offset, self.cl)
+    }
+}
+
+/// Builds the mesh of the cluster from the map.
+fn build(
+    map: &Map<Data>,
+    variant_set: &VariantSet,
+    polygons: &Polygons,
+    builder: &mut Builder,
+    offset: Vec3,
+    cl: ClusterPoint,
+) -> Option<MeshData> {
+    let mut vicinity = map.vicinity(cl)?;
+    for (slab, ch) in map.iter(cl)? {
+        let key = match *slab {
+            Slab::Base(tile, variant) => (tile, variant),
+            _ => continue,
+        };
+
+        let local_offset: Vec3 = ch.into();
+        let variant = variant_set.get(key);
+        let variant_height = variant.height();
+        let connections = variant.connections();
+
+        variant.build(
+            offset + local_offset,
+            |level, shape_height| {
+                let mut sides = Sides::empty();
+                let ch = ch.to(Side::Up, level).unwrap();
+
+                if level + shape_height == variant_height {
+                    match ch.to(Side::Up, shape_height) {
+                        Ok(hi) => {
+                            let other = vicinity.center().connection(hi);
+                            if !other.overlaps(
+                                connections.last().unwrap(),
+                                Side::Down,
+                                polygons,
+                                Axis::Y,
+                            ) {
+                                sides |= Side::Up;
+                            }
+                        }
+                        Err(hi) => match vicinity.from(Side::Up) {
+                            Some(from) => {
+                                let other = from.connection(hi);
                                 if !other.overlaps(
                                     connections.last().unwrap(),
                                     Side::Down,
-                                    &self.polygons,
+                                    polygons,
                                     Axis::Y,
                                 ) {
                                     sides |= Side::Up;
                                 }
                             }
-                            Err(hi) => match vicinity.from(Side::Up) {
-                                Some(from) => {
-                                    let other = from.connection(hi);
-                                    if !other.overlaps(
-                                        connections.last().unwrap(),
-                                        Side::Down,
-                                        &self.polygons,
-                                        Axis::Y,
-                                    ) {
-                                        sides |= Side::Up;
-                                    }
-                                }
-                                None => sides |= Side::Up,
-                            },
-                        }
-                    } else {
-                        sides |= Side::Up;
+                            None => sides |= Side::Up,
+                        },
                     }
+                } else {
+                    sides |= Side::Up;
+                }
 
-                    if level == 0 {
-                        match ch.to(Side::Down, 1) {
-                            Ok(lo) => {
-                                let other = vicinity.center().connection(lo);
+                if level == 0 {
+                    match ch.to(Side::Down, 1) {
+                        Ok(lo) => {
+                            let other = vicinity.center().connection(lo);
+                            if !other.overlaps(
+                                connections.first().unwrap(),
+                                Side::Up,
+                                polygons,
+                                Axis::Y,
+                            ) {
+                                sides |= Side::Down;
+                            }
+                        }
+                        Err(lo) => match vicinity.from(Side::Down) {
+                            Some(from) => {
+                                let other = from.connection(lo);
                                 if !other.overlaps(
                                     connections.first().unwrap(),
                                     Side::Up,
-                                    &self.polygons,
+                                    polygons,
                                     Axis::Y,
                                 ) {
                                     sides |= Side::Down;
                                 }
                             }
-                            Err(lo) => match vicinity.from(Side::Down) {
-                                Some(from) => {
-                                    let other = from.connection(lo);
-                                    if !other.overlaps(
-                                        connections.first().unwrap(),
-                                        Side::Up,
-                                        &self.polygons,
-                                        Axis::Y,
-                                    ) {
-                                        sides |= Side::Down;
-                                    }
+                            None => sides |= Side::Down,
+                        },
+                    }
+                } else {
+                    sides |= Side::Down;
+                }
+
+                for side in [Side::Left, Side::Right, Side::Forth, Side::Back] {
+                    let (mut curr, mut other) = match ch.to(side, 1) {
+                        Ok(curr) => (curr, vicinity.center()),
+                        Err(curr) => (
+                            curr,
+                            match vicinity.from(side) {
+                                Some(other) => other,
+                                None => {
+                                    sides |= side;
+                                    continue;
                                 }
-                                None => sides |= Side::Down,
                             },
+                        ),
+                    };
+
+                    for conn in &connections[level as usize..(level + shape_height) as usize] {
+                        if !other.connection(curr).overlaps(
+                            conn,
+                            side.opposite(),
+                            polygons,
+                            Axis::X,
+                        ) {
+                            sides |= side;
+                            break;
                         }
-                    } else {
-                        sides |= Side::Down;
-                    }
 
-                    for side in [Side::Left, Side::Right, Side::Forth, Side::Back] {
-                        let (mut curr, mut other) = match ch.to(side, 1) {
-                            Ok(curr) => (curr, vicinity.center()),
-                            Err(curr) => (
-                                curr,
-                                match vicinity.from(side) {
+                        curr = match curr.to(Side::Up, 1) {
+                            Ok(next) => next,
+                            Err(next) => {
+                                other = match vicinity.from_upper(side) {
                                     Some(other) => other,
                                     None => {
                                         sides |= side;
-                                        continue;
+                                        break;
                                     }
-                                },
-                            ),
-                        };
-
-                        for conn in &connections[level as usize..(level + shape_height) as usize] {
-                            if !other.connection(curr).overlaps(
-                                conn,
-                                side.opposite(),
-                                &self.polygons,
-                                Axis::X,
-                            ) {
-                                sides |= side;
-                                break;
+                                };
+                                next
                             }
-
-                            curr = match curr.to(Side::Up, 1) {
-                                Ok(next) => next,
-                                Err(next) => {
-                                    other = match vicinity.from_upper(side) {
-                                        Some(other) => other,
-                                        None => {
-                                            sides |= side;
-                                            break;
-                                        }
-                                    };
-                                    next
-                                }
-                            };
-                        }
+                        };
                     }
+                }
 
-                    sides
-                },
-                builder,
-            );
-        }
-
-        let mesh = builder.mesh(ren);
-        builder.clear();
-        mesh
+                sides
+            },
+            builder,
+        );
     }
+
+    Some(builder.take())
 }
 
 impl Space for ClusterView {
@@ -267,7 +506,7 @@ impl Space for ClusterView {
         self.map.get(pn).copied().unwrap_or_else(Pass::empty)
     }
 
-    fn column(&self, pn: Point, height: Height) -> Column<Pass> {
+    fn column(&self, pn: Point, height: Height) -> Column<'_, Pass> {
         const HEIGHT: usize = Height::HEIGHT as usize;
         const EMPTY: [Pass; HEIGHT] = [Pass::empty(); HEIGHT];
 
@@ -276,3 +515,349 @@ impl Space for ClusterView {
             .unwrap_or_else(|| Column(&EMPTY[..height.get() as usize], &[]))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        land::{
+            overlay::Overlay,
+            shape::Shape,
+            variant::{Mesh, Variant},
+        },
+        mesh::Slots,
+        Vert,
+    };
+    use shr::cgm::{Vec2, Vec4, Zero};
+    use std::sync::Arc;
+
+    fn view() -> (ClusterView, tile::Tile, tile::Tile) {
+        let info = |idx, height| tile::Tile {
+            idx: TileIndex::new(idx).unwrap(),
+            name: "test".into(),
+            height: Height::new(height).unwrap(),
+            behaviour: "base".into(),
+            variants: vec![tile::Variant {
+                idx: VariantIndex(0),
+                name: "test".into(),
+                rotation: Rotation::Q0,
+                passes: vec![Pass::solid(); height as usize],
+                auto: None,
+            }],
+        };
+
+        let (short, tall) = (info(1, 1), info(2, 4));
+        let mut variant_set = VariantSet::new();
+        for info in [&short, &tall] {
+            let meshes = Vec::<(Mesh, Vec<Connections>)>::new();
+            let variant = Variant::new(meshes, Vec2::new(0., 0.)).unwrap();
+            variant_set.add((info.idx, VariantIndex(0)), variant);
+        }
+
+        let view = ClusterView::new(variant_set, Polygons::with_capacity(0));
+        (view, short, tall)
+    }
+
+    fn dirty(view: &mut ClusterView) -> HashSet<ClusterPoint> {
+        view.dirty.drain().collect()
+    }
+
+    fn point(x: i64, y: i64, z: i64) -> Point {
+        Point::from_absolute(x, y, z).unwrap()
+    }
+
+    fn cluster(x: i32, y: i32, z: i32) -> ClusterPoint {
+        ClusterPoint::new(x, y, z).unwrap()
+    }
+
+    /// The cube with a face on every side, the faces are full.
+    fn cube() -> Variant {
+        let mut verts = Vec::new();
+        for side in Side::ENUM {
+            for shift in [0., 0.1, 0.2] {
+                verts.push(Vert {
+                    co: side.to_vec() * 0.5 + Vec3::new(shift, 0., 0.),
+                    nm: side.to_vec(),
+                    st: Vec2::new(0., 0.),
+                    sp: Vec4::zero(),
+                });
+            }
+        }
+
+        let indxs = (0..verts.len() as u32).collect();
+        let slots = Slots::new(
+            Side::ENUM
+                .into_iter()
+                .enumerate()
+                .map(|(i, side)| (side.to_string(), [i as u32].into())),
+        );
+
+        let mesh = crate::Mesh::new(verts, indxs, slots).unwrap();
+        let shape = Shape::new(
+            &mesh,
+            Rotation::Q0,
+            |slot| slot.parse::<Side>().ok().map(Into::into),
+            |st| st,
+        );
+
+        let mut connections = Connections::new();
+        connections.set(Sides::all(), Overlay::new_full());
+        let mesh = Mesh {
+            shape: Arc::new(shape),
+            sprites_st: [Vec2::new(0., 0.); 6].into(),
+            height: Height::new(1).unwrap(),
+        };
+
+        Variant::new([(mesh, [connections])], Vec2::new(0., 0.)).unwrap()
+    }
+
+    #[test]
+    fn culling() {
+        let (_, short, _) = view();
+        let mut variant_set = VariantSet::new();
+        variant_set.add((short.idx, VariantIndex(0)), cube());
+        let mut view = ClusterView::new(variant_set, Polygons::with_capacity(0));
+        let faces = |view: &mut ClusterView, cl| {
+            let mesh = view.mesh(Vec3::new(0., 0., 0.), cl).unwrap();
+            assert_eq!(mesh.indxs.len() % 3, 0);
+            mesh.indxs.len() / 3
+        };
+
+        let variant = Some(VariantIndex(0));
+        view.place(point(5, 5, 5), &short, variant);
+        assert_eq!(faces(&mut view, cluster(0, 0, 0)), 6);
+
+        // The faces between the cubes are culled
+        view.place(point(6, 5, 5), &short, variant);
+        assert_eq!(faces(&mut view, cluster(0, 0, 0)), 10);
+        view.place(point(5, 6, 5), &short, variant);
+        assert_eq!(faces(&mut view, cluster(0, 0, 0)), 14);
+
+        // Across the clusters too
+        view.place(point(15, 5, 5), &short, variant);
+        assert_eq!(faces(&mut view, cluster(0, 0, 0)), 20);
+        view.place(point(16, 5, 5), &short, variant);
+        assert_eq!(faces(&mut view, cluster(0, 0, 0)), 19);
+        assert_eq!(faces(&mut view, cluster(1, 0, 0)), 5);
+
+        // The faces come back when the cube is removed
+        view.remove(point(6, 5, 5));
+        assert_eq!(faces(&mut view, cluster(0, 0, 0)), 15);
+    }
+
+    #[test]
+    fn background() {
+        use std::time::{Duration, Instant};
+
+        let (_, short, _) = view();
+        let mut variant_set = VariantSet::new();
+        variant_set.add((short.idx, VariantIndex(0)), cube());
+        let mut view = ClusterView::new(variant_set, Polygons::with_capacity(0));
+        let mut mesher = view.mesher(None, 2, 4);
+        let finish = |mesher: &mut Mesher, count| {
+            let start = Instant::now();
+            let mut meshes = Vec::new();
+            while meshes.len() < count && start.elapsed() < Duration::from_secs(5) {
+                meshes.extend(mesher.finished());
+            }
+
+            // Nothing else shows up
+            std::thread::sleep(Duration::from_millis(50));
+            meshes.extend(mesher.finished());
+            meshes
+        };
+
+        let variant = Some(VariantIndex(0));
+        view.place(point(5, 5, 5), &short, variant);
+        dirty(&mut view);
+
+        // The latest snapshot wins
+        mesher
+            .submit(view.snapshot(cluster(0, 0, 0)).unwrap())
+            .ok()
+            .unwrap();
+        view.place(point(6, 5, 5), &short, variant);
+        mesher
+            .submit(view.snapshot(cluster(0, 0, 0)).unwrap())
+            .ok()
+            .unwrap();
+        let meshes = finish(&mut mesher, 1);
+        assert_eq!(meshes.len(), 1);
+        let (cl, mesh) = &meshes[0];
+        assert_eq!(*cl, cluster(0, 0, 0));
+        assert_eq!(mesh.indxs.len() / 3, 10);
+
+        // The same mesh as on the main thread, at the world position
+        let sync = view.mesh(Vec3::new(0., 0., 0.), cluster(0, 0, 0)).unwrap();
+        assert_eq!(mesh.indxs, sync.indxs);
+
+        // The dirty clusters are queued, the snapshots cull across the clusters
+        view.place(point(15, 5, 5), &short, variant);
+        view.place(point(16, 5, 5), &short, variant);
+        view.spawn_dirty(&mut mesher);
+        assert!(view.dirty.is_empty());
+        let mut meshes = finish(&mut mesher, 2);
+        meshes.sort_by_key(|(cl, _)| <(i32, i32, i32)>::from(*cl));
+        let faces: Vec<_> = meshes
+            .iter()
+            .map(|(cl, mesh)| (*cl, mesh.indxs.len() / 3))
+            .collect();
+        assert_eq!(faces, [(cluster(0, 0, 0), 15), (cluster(1, 0, 0), 5)]);
+
+        // The cluster without data has nothing to mesh
+        assert!(view.snapshot(cluster(5, 0, 0)).is_none());
+
+        // The snapshot without its cluster is done with the empty mesh
+        let snapshot = Snapshot {
+            cl: cluster(5, 0, 0),
+            map: Map::default(),
+        };
+        mesher.submit(snapshot).ok().unwrap();
+        let meshes = finish(&mut mesher, 1);
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].0, cluster(5, 0, 0));
+        assert!(meshes[0].1.indxs.is_empty());
+    }
+
+    #[test]
+    fn mark() {
+        let (mut view, short, tall) = view();
+        let variant = Some(VariantIndex(0));
+        view.place(point(5, 5, 5), &short, variant);
+        assert_eq!(dirty(&mut view), HashSet::from([cluster(0, 0, 0)]));
+
+        // The tile on the border marks the neighbour
+        view.place(point(16, 5, 5), &short, variant);
+        assert_eq!(dirty(&mut view), HashSet::from([cluster(1, 0, 0)]));
+        view.place(point(15, 5, 5), &short, variant);
+        assert_eq!(
+            dirty(&mut view),
+            HashSet::from([cluster(0, 0, 0), cluster(1, 0, 0)])
+        );
+
+        // The tall tile reaches into the upper cluster
+        view.place(point(15, 30, 3), &tall, variant);
+        assert_eq!(dirty(&mut view), HashSet::from([cluster(0, 0, 0)]));
+
+        // Its faces depend on the side of the upper cluster
+        view.place(point(16, 32, 3), &short, variant);
+        assert_eq!(
+            dirty(&mut view),
+            HashSet::from([cluster(1, 1, 0), cluster(0, 0, 0)])
+        );
+
+        // The tile is removed by its trunk
+        assert!(view.remove(point(15, 33, 3)).is_some());
+        assert_eq!(
+            dirty(&mut view),
+            HashSet::from([cluster(0, 0, 0), cluster(1, 1, 0)])
+        );
+        assert!(view.remove(point(15, 30, 3)).is_none());
+        assert!(dirty(&mut view).is_empty());
+        assert_eq!(view.map.get::<Slab>(point(15, 33, 3)), Some(&Slab::Empty));
+
+        // The tile of the upper cluster doesn't depend on the removed one anymore
+        view.place(point(15, 32, 3), &short, variant);
+        assert_eq!(
+            dirty(&mut view),
+            HashSet::from([cluster(0, 1, 0), cluster(1, 1, 0)])
+        );
+    }
+
+    #[test]
+    fn retile() {
+        let (_, short, _) = view();
+        let rule = |solid: &str| auto::Rule {
+            solid: solid.parse().unwrap(),
+            open: Default::default(),
+        };
+
+        // The grass turns into the other variant under the tile
+        let grass = tile::Tile {
+            idx: TileIndex::new(3).unwrap(),
+            name: "grass".into(),
+            height: Height::new(1).unwrap(),
+            behaviour: "base".into(),
+            variants: ["", "u"]
+                .into_iter()
+                .enumerate()
+                .map(|(idx, solid)| tile::Variant {
+                    idx: VariantIndex(idx as u8),
+                    name: "grass".into(),
+                    rotation: Rotation::Q0,
+                    passes: vec![Pass::solid()],
+                    auto: Some(rule(solid)),
+                })
+                .collect(),
+        };
+
+        let mut variant_set = VariantSet::new();
+        for key in [
+            (short.idx, VariantIndex(0)),
+            (grass.idx, VariantIndex(0)),
+            (grass.idx, VariantIndex(1)),
+        ] {
+            let meshes = Vec::<(Mesh, Vec<Connections>)>::new();
+            variant_set.add(key, Variant::new(meshes, Vec2::new(0., 0.)).unwrap());
+        }
+
+        let mut view = ClusterView::new(variant_set, Polygons::with_capacity(0));
+        let tiles = |idx| [&short, &grass].into_iter().find(|tile| tile.idx == idx);
+        let (pn, up) = (point(15, 31, 3), point(15, 32, 3));
+        view.place(pn, &grass, None);
+        assert_eq!(
+            view.map.get::<Slab>(pn),
+            Some(&Slab::Base(grass.idx, VariantIndex(0)))
+        );
+
+        view.place(up, &short, Some(VariantIndex(0)));
+        dirty(&mut view);
+        view.retile(up, short.height, tiles);
+        assert_eq!(
+            view.map.get::<Slab>(pn),
+            Some(&Slab::Base(grass.idx, VariantIndex(1)))
+        );
+        assert!(dirty(&mut view).contains(&cluster(0, 0, 0)));
+
+        let (base, height) = view.remove(up).unwrap();
+        view.retile(base, height, tiles);
+        assert_eq!(
+            view.map.get::<Slab>(pn),
+            Some(&Slab::Base(grass.idx, VariantIndex(0)))
+        );
+    }
+
+    #[test]
+    fn clear_upper() {
+        let (mut view, short, tall) = view();
+        let variant = Some(VariantIndex(0));
+        view.place(point(15, 30, 3), &tall, variant);
+        view.place(point(15, 40, 3), &short, variant);
+
+        // The trunk left in the upper cluster would be a phantom wall
+        view.clear(cluster(0, 0, 0));
+        for y in 32..34 {
+            assert_eq!(view.map.get::<Slab>(point(15, y, 3)), Some(&Slab::Empty));
+            assert!(!view.get(point(15, y, 3)).is_solid());
+        }
+
+        assert!(view.base(point(15, 40, 3)).is_some());
+        assert!(dirty(&mut view).contains(&cluster(0, 1, 0)));
+    }
+
+    #[test]
+    fn clear() {
+        let (mut view, short, _) = view();
+        let variant = Some(VariantIndex(0));
+        for pn in [point(5, 5, 5), point(20, 5, 5), point(5, -5, 20)] {
+            view.place(pn, &short, variant);
+        }
+
+        dirty(&mut view);
+        view.clear(cluster(0, 0, 0));
+        assert_eq!(
+            dirty(&mut view),
+            HashSet::from([cluster(0, 0, 0), cluster(1, 0, 0), cluster(0, -1, 1)])
+        );
+    }
+}
@@ -2,6 +2,7 @@ mod atlas;
 #[allow(dead_code)]
 mod camera;
 mod draw;
+mod export;
 mod game;
 mod land;
 mod loader;
@@ -9,6 +10,7 @@ mod mesh;
 mod render;
 
 pub use self::{
+    export::Error as ExportError,
     game::{Control, Game},
     render::Render,
 };
@@ -4,6 +4,7 @@ use crate::{
     Mesh, Vert,
 };
 use serde::Deserialize;
+use shr::cgm::*;
 use std::collections::HashMap;
 
 #[derive(Deserialize)]
@@ -35,6 +36,7 @@ fn load(mesh: RawMesh) -> Result<Mesh, mesh::Error> {
                 co: raw.c.into(),
                 nm: raw.n.into(),
                 st: raw.t.into(),
+                sp: Vec4::zero(),
             })
             .collect(),
         indxs,
@@ -110,9 +110,9 @@ impl ToVariant {
                         .sprites
                         .as_ref()
                         .map(|sprites| match sprites {
-                            Sprites::One(sprite) => std::iter::repeat(st(Some(sprite)))
-                                .take(mesh.slots().len())
-                                .collect(),
+                            Sprites::One(sprite) => {
+                                std::iter::repeat_n(st(Some(sprite)), mesh.slots().len()).collect()
+                            }
                             Sprites::Many(map) => mesh
                                 .slots()
                                 .ordered_keys()
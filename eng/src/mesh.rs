@@ -79,7 +79,7 @@ pub struct Mesh<V> {
 impl<V> Mesh<V> {
     pub fn new(verts: Vec<V>, indxs: Vec<u32>, slots: Slots) -> Result<Self, Error> {
         // Vertices len must be a multiple of 3
-        if indxs.len() % 3 != 0 {
+        if !indxs.len().is_multiple_of(3) {
             return Err(Error::IndxsLen(indxs.len()));
         }
 
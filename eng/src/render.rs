@@ -22,7 +22,7 @@ impl Render {
         use std::cell::Cell;
 
         thread_local! {
-            static INIT: Cell<bool> = Cell::new(false);
+            static INIT: Cell<bool> = const { Cell::new(false) };
         }
 
         INIT.with(|initialized| {
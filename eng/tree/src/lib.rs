@@ -134,19 +134,19 @@ impl<V> Tree<V> {
         false
     }
 
-    pub fn nodes(&self) -> Nodes<V> {
+    pub fn nodes(&self) -> Nodes<'_, V> {
         Nodes::new(&self.0)
     }
 
-    pub fn nodes_mut(&mut self) -> NodesMut<V> {
+    pub fn nodes_mut(&mut self) -> NodesMut<'_, V> {
         NodesMut::new(&mut self.0)
     }
 
-    pub fn edges(&self) -> Edges<V> {
+    pub fn edges(&self) -> Edges<'_, V> {
         Edges::new(&self.0)
     }
 
-    pub fn edges_mut(&mut self) -> EdgesMut<V> {
+    pub fn edges_mut(&mut self) -> EdgesMut<'_, V> {
         EdgesMut::new(&mut self.0)
     }
 }
@@ -102,7 +102,7 @@ impl<'a> Pipeline<'a> {
     }
 
     fn ensure_empty<'b>(self) -> Pipeline<'b> {
-        fn safe_capacity<'a, 'b, S>(mut v: Vec<&'a dyn Draw<S>>) -> Vec<&'b dyn Draw<S>> {
+        fn safe_capacity<'b, S>(mut v: Vec<&dyn Draw<S>>) -> Vec<&'b dyn Draw<S>> {
             unsafe {
                 assert!(v.is_empty());
                 let ptr = v.as_mut_ptr() as *mut *const dyn Draw<S>;
@@ -18,7 +18,7 @@ impl<M> ElementBuffer<M> {
             ctx.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(nat));
 
             let len = indxs.len();
-            let size = len * std::mem::size_of::<u32>();
+            let size = std::mem::size_of_val(indxs);
             ctx.buffer_data_u8_slice(
                 glow::ELEMENT_ARRAY_BUFFER,
                 std::slice::from_raw_parts(indxs.as_ptr().cast(), size),
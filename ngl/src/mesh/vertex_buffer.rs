@@ -95,10 +95,7 @@ impl<L, A> VertexBuffer<L, (Dynamic, A)> {
             ctx.buffer_sub_data_u8_slice(
                 glow::ARRAY_BUFFER,
                 0,
-                std::slice::from_raw_parts(
-                    verts.as_ptr().cast(),
-                    verts.len() * std::mem::size_of::<L>(),
-                ),
+                std::slice::from_raw_parts(verts.as_ptr().cast(), std::mem::size_of_val(verts)),
             )
         }
     }
@@ -13,7 +13,7 @@ pub(crate) struct Program {
 
 impl Program {
     thread_local! {
-        static USED: Cell<Option<NativeProgram>> = Cell::new(None);
+        static USED: Cell<Option<NativeProgram>> = const { Cell::new(None) };
     }
 
     pub fn new<'a, S>(ctx: Rc<Context>, shaders: S) -> Self
@@ -77,7 +77,7 @@ impl Program {
         }));
     }
 
-    pub fn locations(&self) -> Locations {
+    pub fn locations(&self) -> Locations<'_> {
         unsafe {
             let n = self.ctx.get_active_uniforms(self.nat);
             let mut locs = Locations::with_capacity(n as _, self);
@@ -13,13 +13,15 @@ def_shader! {
         fog_near: f32,
         fog_far: f32,
     },
-    fn: (fs_co: Vec3, fs_st: Vec2) -> (frag: Vec4),
+    fn: (fs_co: Vec3, fs_st: Vec2, fs_sp: Vec4) -> (frag: Vec4),
     impl: {
         const float NEAR = $NEAR;
         const float FAR = $FAR;
 
         void main() {
-            vec4 cl = texture(t0, fs_st);
+            // The tiled faces repeat the sprite across the rect
+            vec2 st = fs_sp.z > 0.0 ? fs_sp.xy + fract(fs_st) * fs_sp.zw : fs_st;
+            vec4 cl = texture(t0, st);
             if (cl.a < 0.9) {
                 discard;
             }
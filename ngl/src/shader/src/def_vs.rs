@@ -9,10 +9,11 @@ def_shader! {
         view: Mat4,
         proj: Mat4,
     },
-    fn: () -> (fs_co: Vec3, fs_st: Vec2),
+    fn: () -> (fs_co: Vec3, fs_st: Vec2, fs_sp: Vec4),
     impl: {
         void main() {
             fs_st = st;
+            fs_sp = sp;
 
             vec4 res = proj * view * model * vec4(co, 1.0);
             fs_co = vec3(res);
@@ -12,6 +12,8 @@ pub struct Vertex {
     pub co: Vec3,
     pub nm: Vec3,
     pub st: Vec2,
+    /// The sprite rect the `st` repeats in, zero if the `st` isn't tiled.
+    pub sp: Vec4,
 }
 
 #[derive(Copy, Clone, Layout)]
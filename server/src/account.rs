@@ -0,0 +1,373 @@
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use core::net::LoginResult;
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error, fmt, fs, io,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const NAME_MAX_LEN: usize = 32;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Serde(serde_json::Error),
+    Hash(argon2::password_hash::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error {err}"),
+            Self::Serde(err) => write!(f, "serde error {err}"),
+            Self::Hash(err) => write!(f, "hash error {err}"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Serde(err)
+    }
+}
+
+impl From<argon2::password_hash::Error> for Error {
+    fn from(err: argon2::password_hash::Error) -> Self {
+        Self::Hash(err)
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Settings {
+    /// Failed logins in a row before the account is locked.
+    pub max_failures: u32,
+    pub lockout: Duration,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Account {
+    /// Salted password hash in the PHC string format.
+    hash: String,
+    #[serde(default)]
+    failures: u32,
+    /// Unix time in seconds until which the account is locked.
+    #[serde(default)]
+    locked_until: u64,
+}
+
+struct Inner {
+    path: PathBuf,
+    accounts: HashMap<String, Account>,
+    settings: Settings,
+    /// Verified for the unknown names, so they take as long as the known ones.
+    dummy: String,
+}
+
+impl Inner {
+    fn save(&self) -> Result<(), Error> {
+        let content = serde_json::to_string_pretty(&self.accounts)?;
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, content)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// Returns the result if the name can't be registered.
+    fn refuse(&self, name: &str) -> Option<LoginResult> {
+        if !is_valid_name(name) {
+            return Some(LoginResult::InvalidName);
+        }
+
+        self.accounts
+            .contains_key(name)
+            .then_some(LoginResult::NameTaken)
+    }
+
+    fn register(&mut self, name: &str, hash: String) -> Result<LoginResult, Error> {
+        // The name could be taken while the password was hashed
+        if let Some(result) = self.refuse(name) {
+            return Ok(result);
+        }
+
+        let account = Account {
+            hash,
+            failures: 0,
+            locked_until: 0,
+        };
+
+        self.accounts.insert(name.into(), account);
+        self.save()?;
+        Ok(LoginResult::Accepted)
+    }
+
+    /// Returns the hash to verify the password against and whether the account exists,
+    /// or the result if the account is locked.
+    fn hash(&self, name: &str, now: u64) -> Result<(String, bool), LoginResult> {
+        match self.accounts.get(name) {
+            Some(account) if account.locked_until > now => Err(LoginResult::Locked {
+                secs: account.locked_until - now,
+            }),
+            Some(account) => Ok((account.hash.clone(), true)),
+            None => Ok((self.dummy.clone(), false)),
+        }
+    }
+
+    /// Counts the failures of the verified login.
+    fn verified(&mut self, name: &str, ok: bool, now: u64) -> Result<LoginResult, Error> {
+        let account = match self.accounts.get_mut(name) {
+            Some(account) => account,
+            None => return Ok(LoginResult::Rejected),
+        };
+
+        // Could be locked by the other logins while verifying
+        if account.locked_until > now {
+            let secs = account.locked_until - now;
+            return Ok(LoginResult::Locked { secs });
+        }
+
+        let result = match ok {
+            true if account.failures == 0 => return Ok(LoginResult::Accepted),
+            true => {
+                account.failures = 0;
+                LoginResult::Accepted
+            }
+            false => {
+                account.failures += 1;
+                if account.failures >= self.settings.max_failures {
+                    account.failures = 0;
+                    account.locked_until = now + self.settings.lockout.as_secs();
+                }
+
+                LoginResult::Rejected
+            }
+        };
+
+        self.save()?;
+        Ok(result)
+    }
+}
+
+/// The file-backed account database.
+///
+/// Cheap to clone, all clones share the same accounts.
+#[derive(Clone)]
+pub struct Accounts(Arc<Mutex<Inner>>);
+
+impl Accounts {
+    /// Loads accounts from the `path`.
+    /// If the file doesn't exist, the database is empty.
+    pub fn load<P>(path: P, settings: Settings) -> Result<Self, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        let accounts = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => HashMap::default(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self(Arc::new(Mutex::new(Inner {
+            path,
+            accounts,
+            settings,
+            dummy: hash("")?,
+        }))))
+    }
+
+    #[cfg(test)]
+    pub fn path(&self) -> PathBuf {
+        let inner = self.0.lock().unwrap();
+        inner.path.clone()
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        let inner = self.0.lock().unwrap();
+        inner.accounts.len()
+    }
+
+    /// Creates a new account.
+    ///
+    /// Hashing is slow by design, so this blocks the current thread.
+    /// The accounts aren't locked meanwhile.
+    pub fn register(&self, name: &str, pass: &str) -> Result<LoginResult, Error> {
+        if let Some(result) = self.0.lock().unwrap().refuse(name) {
+            return Ok(result);
+        }
+
+        let hash = hash(pass)?;
+        let mut inner = self.0.lock().unwrap();
+        inner.register(name, hash)
+    }
+
+    /// Checks the password of the account.
+    ///
+    /// Hashing is slow by design, so this blocks the current thread.
+    /// The accounts aren't locked meanwhile.
+    pub fn login(&self, name: &str, pass: &str) -> Result<LoginResult, Error> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|dur| dur.as_secs())
+            .unwrap_or_default();
+
+        self.login_at(name, pass, now)
+    }
+
+    fn login_at(&self, name: &str, pass: &str, now: u64) -> Result<LoginResult, Error> {
+        let (hash, exists) = match self.0.lock().unwrap().hash(name, now) {
+            Ok(hash) => hash,
+            Err(result) => return Ok(result),
+        };
+
+        // The unknown names are verified too, so the time doesn't tell them
+        let ok = verify(&hash, pass)?;
+        if !exists {
+            return Ok(LoginResult::Rejected);
+        }
+
+        let mut inner = self.0.lock().unwrap();
+        inner.verified(name, ok, now)
+    }
+}
+
+fn hash(pass: &str) -> Result<String, Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default().hash_password(pass.as_bytes(), &salt)?;
+    Ok(hash.to_string())
+}
+
+fn verify(hash: &str, pass: &str) -> Result<bool, Error> {
+    let hash = PasswordHash::new(hash)?;
+    match Argon2::default().verify_password(pass.as_bytes(), &hash) {
+        Ok(()) => Ok(true),
+        Err(argon2::password_hash::Error::Password) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    (1..=NAME_MAX_LEN).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accounts(name: &str) -> Accounts {
+        let path =
+            std::env::temp_dir().join(format!("arkipelago_{name}_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let settings = Settings {
+            max_failures: 3,
+            lockout: Duration::from_secs(60),
+        };
+        Accounts::load(path, settings).unwrap()
+    }
+
+    #[test]
+    fn register() {
+        let accounts = accounts("register");
+        assert_eq!(
+            accounts.register("nano", "secret").unwrap(),
+            LoginResult::Accepted
+        );
+        assert_eq!(
+            accounts.register("nano", "other").unwrap(),
+            LoginResult::NameTaken
+        );
+        assert_eq!(
+            accounts.register("na no", "secret").unwrap(),
+            LoginResult::InvalidName
+        );
+        assert_eq!(
+            accounts.register("", "secret").unwrap(),
+            LoginResult::InvalidName
+        );
+
+        let content = fs::read_to_string(accounts.path()).unwrap();
+        assert!(!content.contains("secret"));
+
+        let settings = accounts.0.lock().unwrap().settings;
+        let loaded = Accounts::load(accounts.path(), settings).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(
+            loaded.login("nano", "secret").unwrap(),
+            LoginResult::Accepted
+        );
+        fs::remove_file(accounts.path()).unwrap();
+    }
+
+    #[test]
+    fn login() {
+        let accounts = accounts("login");
+        accounts.register("nano", "secret").unwrap();
+        assert_eq!(
+            accounts.login("nano", "secret").unwrap(),
+            LoginResult::Accepted
+        );
+        assert_eq!(
+            accounts.login("nano", "other").unwrap(),
+            LoginResult::Rejected
+        );
+        assert_eq!(
+            accounts.login("anon", "secret").unwrap(),
+            LoginResult::Rejected
+        );
+        fs::remove_file(accounts.path()).unwrap();
+    }
+
+    #[test]
+    fn lockout() {
+        let accounts = accounts("lockout");
+        accounts.register("nano", "secret").unwrap();
+
+        assert_eq!(
+            accounts.login_at("nano", "0", 100).unwrap(),
+            LoginResult::Rejected
+        );
+        assert_eq!(
+            accounts.login_at("nano", "0", 100).unwrap(),
+            LoginResult::Rejected
+        );
+        assert_eq!(
+            accounts.login_at("nano", "secret", 100).unwrap(),
+            LoginResult::Accepted
+        );
+
+        for _ in 0..3 {
+            assert_eq!(
+                accounts.login_at("nano", "0", 100).unwrap(),
+                LoginResult::Rejected
+            );
+        }
+
+        assert_eq!(
+            accounts.login_at("nano", "secret", 110).unwrap(),
+            LoginResult::Locked { secs: 50 }
+        );
+        assert_eq!(
+            accounts.login_at("nano", "secret", 160).unwrap(),
+            LoginResult::Accepted
+        );
+        fs::remove_file(accounts.path()).unwrap();
+    }
+}
@@ -1,47 +1,64 @@
-use crate::{layout::*, slab::*, tile::*};
+use crate::{
+    layout::*,
+    region::{self, Regions, SavedChunk},
+    slab::*,
+    state::{State, Storage},
+    tile::*,
+};
 use core::{
+    auto::{self, Passes},
+    chunk,
     map::{Column, Map},
+    net::TileSlab,
+    path::{self, Pass},
     point::ChunkPoints,
     prelude::*,
 };
-use std::{any::Any, cell::RefCell, rc::Rc};
+use std::{collections::HashSet, rc::Rc};
 
-#[derive(Clone)]
-struct Storage {
-    data: Rc<RefCell<Vec<Rc<dyn Any>>>>,
+struct SlabChunk {
+    slabs: Chunk<Slab>,
+    storage: Storage,
+    /// The passes of the tiles, derived from the slabs.
+    passes: Chunk<Pass>,
 }
 
-impl Storage {
+impl SlabChunk {
     fn new() -> Self {
         Self {
-            data: Rc::new(RefCell::new(Vec::default())),
+            slabs: Chunk::filled(Empty.into()),
+            storage: Storage::new(),
+            passes: Chunk::filled(Pass::empty()),
         }
     }
 
-    fn get(&self, idx: u16) -> Rc<dyn Any> {
-        let data = self.data.borrow();
-        Rc::clone(&data[idx as usize])
+    fn save(&self) -> SavedChunk {
+        SavedChunk {
+            slabs: chunk::encode(&self.slabs),
+            states: self.storage.slots().to_vec(),
+        }
     }
 
-    fn add(&self, obj: Rc<dyn Any>) -> u16 {
-        let mut data = self.data.borrow_mut();
-        let idx = data.len();
-        data.push(obj);
-        idx as u16
+    fn load(saved: &SavedChunk) -> Result<Self, chunk::Error> {
+        Ok(Self {
+            slabs: chunk::decode(&saved.slabs)?,
+            storage: Storage::from_slots(saved.states.clone()),
+            passes: Chunk::filled(Pass::empty()),
+        })
     }
-}
 
-struct SlabChunk {
-    slabs: Chunk<Slab>,
-    storage: Storage,
-}
+    /// Iterates over the storage indices of the trunks with a state.
+    fn states(&self) -> impl Iterator<Item = (TileIndex, u16)> + '_ {
+        ChunkPoints::new().filter_map(|ch| match self.slabs.get(ch).typed() {
+            Typed::Trunk(trunk) if trunk.is_state() => Some((trunk.tile(), trunk.data())),
+            _ => None,
+        })
+    }
 
-impl SlabChunk {
-    fn new() -> Self {
-        Self {
-            slabs: Chunk::filled(Empty.into()),
-            storage: Storage::new(),
-        }
+    /// Removes the states of removed tiles.
+    fn collect(&mut self) {
+        let referenced: Vec<_> = self.states().map(|(_, idx)| idx).collect();
+        self.storage.collect(referenced);
     }
 }
 
@@ -63,6 +80,18 @@ impl AsMut<Chunk<Slab>> for SlabChunk {
     }
 }
 
+impl AsRef<Chunk<Pass>> for SlabChunk {
+    fn as_ref(&self) -> &Chunk<Pass> {
+        &self.passes
+    }
+}
+
+impl AsMut<Chunk<Pass>> for SlabChunk {
+    fn as_mut(&mut self) -> &mut Chunk<Pass> {
+        &mut self.passes
+    }
+}
+
 pub struct ClusterSlice<'a> {
     column: Column<'a, Slab>,
     chunks: (&'a SlabChunk, Option<&'a SlabChunk>),
@@ -76,6 +105,10 @@ impl ClusterSlice<'_> {
         }
     }
 
+    pub fn height(&self) -> Height {
+        Height::new(self.column.len() as u8).unwrap()
+    }
+
     pub fn data(&self, level: u8) -> Data {
         let level = level as usize;
         let slab = self.column.get(level);
@@ -84,14 +117,14 @@ impl ClusterSlice<'_> {
             Typed::Base(_) => Data::None,
             Typed::Trunk(trunk) => {
                 let data = trunk.data();
-                if trunk.is_obj() {
-                    let chunk = if level < self.column.len() {
+                if trunk.is_state() {
+                    let chunk = if level < self.column.0.len() {
                         self.chunks.0
                     } else {
                         self.chunks.1.unwrap()
                     };
 
-                    Data::Obj(chunk.storage.get(data))
+                    Data::State(chunk.storage.get(data).expect("state").clone())
                 } else {
                     Data::Num(Num::new(data).unwrap())
                 }
@@ -105,58 +138,234 @@ pub struct Placed {
     pub height: Height,
 }
 
+/// The tile taken out of the cluster.
+pub struct Removed {
+    /// The point of the base.
+    pub pn: Point,
+    pub tile: TileIndex,
+    pub variant: VariantIndex,
+    pub height: Height,
+    pub states: Vec<State>,
+    slabs: Vec<Slab>,
+}
+
 pub struct Cluster {
     map: Map<SlabChunk>,
     tile_set: Rc<TileSet>,
+    regions: Option<Regions>,
+    dirty: HashSet<ClusterPoint>,
 }
 
 impl Cluster {
+    /// Creates the cluster kept only in memory.
     pub fn new(tile_set: Rc<TileSet>) -> Self {
         Self {
             map: Map::default(),
             tile_set,
+            regions: None,
+            dirty: HashSet::default(),
         }
     }
 
-    pub fn tiles(&self, cl: ClusterPoint) -> Option<Tiles> {
-        Some(Tiles {
+    /// Creates the cluster stored in the regions.
+    pub fn with_regions(tile_set: Rc<TileSet>, regions: Regions) -> Self {
+        Self {
+            regions: Some(regions),
+            ..Self::new(tile_set)
+        }
+    }
+
+    pub fn tile_set(&self) -> &TileSet {
+        &self.tile_set
+    }
+
+    /// Loads the chunk from the regions if it's absent.
+    ///
+    /// A broken chunk stays unloaded, so it isn't overwritten on save.
+    fn load(&mut self, cl: ClusterPoint) -> Result<(), region::Error> {
+        if self.map.chunk(cl).is_some() {
+            return Ok(());
+        }
+
+        let regions = match &mut self.regions {
+            Some(regions) => regions,
+            None => return Ok(()),
+        };
+
+        let saved = match regions.get(cl)? {
+            Some(saved) => saved,
+            None => return Ok(()),
+        };
+
+        let chunk = SlabChunk::load(saved)?;
+        for (tile, idx) in chunk.states() {
+            let schema = self.tile_set.get(tile).and_then(|tile| tile.schema());
+            let state = chunk.storage.get(idx).map(State::schema);
+            if schema.is_none() || schema != state {
+                println!("Wrong state {idx} of {tile} in {cl}: {state:?}, expected {schema:?}");
+            }
+        }
+
+        *self.map.chunk_mut(cl) = chunk;
+
+        // The columns can cross the chunks, so the passes of the upper one
+        // can depend on the loaded chunk
+        self.update_passes(cl);
+        if self.map.chunk(cl.to(Side::Up)).is_some() {
+            self.update_passes(cl.to(Side::Up));
+        }
+
+        Ok(())
+    }
+
+    /// Derives the passes of the chunk from its slabs.
+    fn update_passes(&mut self, cl: ClusterPoint) {
+        for ch in ChunkPoints::new() {
+            self.update_pass(Point::new(ch, cl));
+        }
+    }
+
+    fn update_pass(&mut self, pn: Point) {
+        let pass = self.pass(pn).unwrap_or_else(Pass::empty);
+        *self.map.get_mut(pn) = pass;
+    }
+
+    /// Derives the passes of the column from its slabs.
+    fn update_column(&mut self, pn: Point, height: Height) {
+        let mut pn = pn;
+        for _ in 0..height.get() {
+            self.update_pass(pn);
+            pn = pn.to(Side::Up);
+        }
+    }
+
+    /// Loads the chunk with its neighbours on the vertical axis,
+    /// since the columns of tiles can cross them.
+    fn load_column(&mut self, cl: ClusterPoint) -> Result<(), region::Error> {
+        self.load(cl.to(Side::Down))?;
+        self.load(cl)?;
+        self.load(cl.to(Side::Up))
+    }
+
+    /// Loads the chunks of the tiles around the cluster,
+    /// their variants can depend on each other.
+    pub fn load_around(&mut self, cl: ClusterPoint) -> Result<(), region::Error> {
+        self.load_column(cl)?;
+        for side in [Side::Left, Side::Right, Side::Forth, Side::Back] {
+            self.load_column(cl.to(side))?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the changed chunks to the regions.
+    ///
+    /// The chunks that failed to be written stay changed.
+    pub fn save(&mut self) -> Result<(), region::Error> {
+        let dirty: Vec<_> = self.dirty.iter().copied().collect();
+        for cl in dirty {
+            let chunk = self.map.chunk_mut(cl);
+            chunk.collect();
+            if let Some(regions) = &mut self.regions {
+                regions.put(cl, chunk.save())?;
+            }
+
+            self.dirty.remove(&cl);
+        }
+
+        match &mut self.regions {
+            Some(regions) => regions.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the state stored in the trunk at the point.
+    pub fn state_mut(&mut self, pn: Point) -> Result<Option<&mut State>, region::Error> {
+        let cl = pn.cluster_point();
+        self.load(cl)?;
+        let idx = match self.map.get::<Slab>(pn).map(|slab| slab.typed()) {
+            Some(Typed::Trunk(trunk)) if trunk.is_state() => trunk.data(),
+            _ => return Ok(None),
+        };
+
+        self.dirty.insert(cl);
+        Ok(self.map.chunk_mut(cl).storage.get_mut(idx))
+    }
+
+    pub fn tiles(&mut self, cl: ClusterPoint) -> Result<Option<Tiles<'_>>, region::Error> {
+        self.load_column(cl)?;
+        if self.map.chunk(cl).is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(Tiles {
             cluster: self,
             points: ChunkPoints::new(),
             cl,
-        })
+        }))
     }
 
-    pub fn get(&self, mut pn: Point) -> Option<(ClusterSlice, u8)> {
+    /// Returns the slabs of the chunk as the client sees them.
+    pub fn tile_slabs(
+        &mut self,
+        cl: ClusterPoint,
+    ) -> Result<Option<Chunk<TileSlab>>, region::Error> {
+        self.load(cl)?;
+        let chunk = match self.map.chunk(cl) {
+            Some(chunk) => chunk,
+            None => return Ok(None),
+        };
+
+        let mut slabs = Chunk::filled(TileSlab::Empty);
+        for ch in ChunkPoints::new() {
+            *slabs.get_mut(ch) = match chunk.slabs.get(ch).typed() {
+                Typed::Empty(_) => TileSlab::Empty,
+                Typed::Base(base) => TileSlab::Base(base.tile(), base.variant()),
+                Typed::Trunk(trunk) => TileSlab::Trunk(trunk.level() - 1),
+            };
+        }
+
+        Ok(Some(slabs))
+    }
+
+    pub fn get(&mut self, pn: Point) -> Result<Option<(ClusterSlice<'_>, u8)>, region::Error> {
+        self.load_column(pn.cluster_point())?;
+        Ok(self.slice(pn))
+    }
+
+    /// Returns the tile at the point without loading its chunks.
+    pub fn slice(&self, pn: Point) -> Option<(ClusterSlice<'_>, u8)> {
+        let (pn, slab, level) = self.base(pn)?;
+
+        // The column can continue in the upper chunk
+        let cl = pn.cluster_point();
+        let chunks = (self.map.chunk(cl)?, self.map.chunk(cl.to(Side::Up)));
+        let column = self.map.column(pn, slab.height())?;
+        let slice = ClusterSlice { column, chunks };
+        debug_assert!(!slice.column.iter().copied().any(Slab::is_empty));
+        Some((slice, level))
+    }
+
+    /// Resolves the slab at the point to the base of its tile.
+    fn base(&self, pn: Point) -> Option<(Point, Base, u8)> {
         let ch = pn.chunk_point();
         let cl = pn.cluster_point();
-        let chunks;
-        let (slab, level) = match self.map.get(pn)?.typed() {
-            Typed::Empty(_) => return None,
-            Typed::Base(slab) => {
-                chunks = (self.map.chunk(cl)?, None);
-                (slab, 0)
-            }
+        match self.map.get::<Slab>(pn)?.typed() {
+            Typed::Empty(_) => None,
+            Typed::Base(slab) => Some((pn, slab, 0)),
             Typed::Trunk(slab) => {
                 let level = slab.level();
-                let dw = cl.to(Side::Down);
-                pn = match ch.to(Side::Down, level) {
+                let pn = match ch.to(Side::Down, level) {
                     Ok(ch) => Point::new(ch, cl),
-                    Err(ch) => Point::new(ch, dw),
+                    Err(ch) => Point::new(ch, cl.to(Side::Down)),
                 };
-                let cl = pn.cluster_point();
-                chunks = (self.map.chunk(cl)?, self.map.chunk(dw));
 
-                match self.map.get(pn)?.typed() {
-                    Typed::Base(slab) => (slab, level),
+                match self.map.get::<Slab>(pn)?.typed() {
+                    Typed::Base(slab) => Some((pn, slab, level)),
                     _ => unreachable!(),
                 }
             }
-        };
-
-        let column = self.map.column(pn, slab.height())?;
-        let slice = ClusterSlice { column, chunks };
-        debug_assert!(!slice.column.iter().copied().any(Slab::is_empty));
-        Some((slice, level))
+        }
     }
 
     pub fn is_empty(&self, pn: Point, height: Height) -> bool {
@@ -166,7 +375,17 @@ impl Cluster {
         }
     }
 
-    pub fn place(&mut self, pn: Point, tile_idx: TileIndex) -> Option<Placed> {
+    pub fn place(
+        &mut self,
+        pn: Point,
+        tile_idx: TileIndex,
+    ) -> Result<Option<Placed>, region::Error> {
+        self.load_around(pn.cluster_point())?;
+        Ok(self.place_loaded(pn, tile_idx))
+    }
+
+    /// Places the tile when the chunks around are already loaded.
+    fn place_loaded(&mut self, pn: Point, tile_idx: TileIndex) -> Option<Placed> {
         let tile_set = Rc::clone(&self.tile_set);
         let tile = tile_set.get(tile_idx).unwrap();
         let height = Height::new(tile.height()).unwrap();
@@ -182,37 +401,250 @@ impl Cluster {
             data: placement.data,
         };
 
-        self.map.column_mut(pn, height);
-        let cl = pn.cluster_point();
-        let storages = (
-            self.map.chunk(cl).unwrap().storage.clone(),
-            self.map
-                .chunk(cl.to(Side::Up))
-                .map(|chunk| chunk.storage.clone()),
-        );
+        let trunks = layout.trunks().map(|(trunk, state)| {
+            if let Some(state) = state {
+                assert_eq!(tile.schema(), Some(state.schema()));
+            }
 
-        let mut column = self.map.column_mut(pn, height);
-        *column.get_mut(0) = layout.base().into();
+            (trunk, state.cloned())
+        });
+        self.put(pn, layout.base(), trunks)?;
+        Some(Placed {
+            variant: placement.variant,
+            height,
+        })
+    }
+
+    /// Removes the tile occupying the point.
+    ///
+    /// The point can be any slab of the tile, the whole column is cleared
+    /// and the states of the tile are freed.
+    pub fn remove(&mut self, pn: Point) -> Result<Option<Removed>, region::Error> {
+        self.load_around(pn.cluster_point())?;
+        Ok(self.remove_loaded(pn))
+    }
+
+    /// Removes the tile when the chunks around are already loaded.
+    fn remove_loaded(&mut self, pn: Point) -> Option<Removed> {
+        let (pn, base, _) = self.base(pn)?;
+        let cl = pn.cluster_point();
+        let height = base.height();
+        let slabs: Vec<_> = self
+            .map
+            .column::<Slab>(pn, height)?
+            .iter()
+            .copied()
+            .collect();
+        self.mark_dirty(pn, height);
+        let mut column = self.map.column_mut::<Slab>(pn, height);
         let len = column.0.len();
-        for ((i, slab), (mut trunk, obj)) in
-            column.iter_mut().enumerate().skip(1).zip(layout.trunks())
-        {
-            if let Some(obj) = obj {
-                trunk.set_data(if i < len {
-                    storages.0.add(obj)
-                } else {
-                    storages.1.as_ref().unwrap().add(obj)
-                })
-            }
+        for slab in column.iter_mut() {
+            *slab = Empty.into();
+        }
 
-            *slab = trunk.into();
+        let mut states = Vec::new();
+        for (i, slab) in slabs.iter().enumerate().skip(1) {
+            match slab.typed() {
+                Typed::Trunk(trunk) if trunk.is_state() => {
+                    let cl = if i < len { cl } else { cl.to(Side::Up) };
+                    let storage = &mut self.map.chunk_mut(cl).storage;
+                    states.push(storage.remove(trunk.data()).expect("state"));
+                }
+                _ => {}
+            }
         }
 
-        Some(Placed {
-            variant: placement.variant,
+        self.update_column(pn, height);
+        self.retile(pn, height);
+        Some(Removed {
+            pn,
+            tile: base.tile(),
+            variant: base.variant(),
             height,
+            states,
+            slabs,
         })
     }
+
+    /// Replaces the tile occupying the point with the new one.
+    ///
+    /// Nothing is changed if there is no tile
+    /// or the new tile doesn't fit in place of the old one.
+    pub fn replace(
+        &mut self,
+        pn: Point,
+        tile_idx: TileIndex,
+    ) -> Result<Option<(Removed, Placed)>, region::Error> {
+        let height = Height::new(self.tile_set.get(tile_idx).unwrap().height()).unwrap();
+        self.load_column(pn.cluster_point())?;
+        let (pn, base, _) = match self.base(pn) {
+            Some(base) => base,
+            None => return Ok(None),
+        };
+
+        // The part of the new column above the old tile must be free
+        let old = base.height().get() as usize;
+        if let Some(column) = self.map.column(pn, height) {
+            if !column.iter().skip(old).copied().all(Slab::is_empty) {
+                return Ok(None);
+            }
+        }
+
+        self.load_around(pn.cluster_point())?;
+        let removed = match self.remove_loaded(pn) {
+            Some(removed) => removed,
+            None => return Ok(None),
+        };
+
+        match self.place_loaded(pn, tile_idx) {
+            Some(placed) => Ok(Some((removed, placed))),
+            None => {
+                self.restore(&removed);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Puts the removed tile back.
+    fn restore(&mut self, removed: &Removed) {
+        let base = match removed.slabs[0].typed() {
+            Typed::Base(base) => base,
+            _ => unreachable!(),
+        };
+
+        let mut states = removed.states.iter().cloned();
+        let trunks = removed.slabs[1..].iter().map(|slab| match slab.typed() {
+            Typed::Trunk(trunk) if trunk.is_state() => (trunk, states.next()),
+            Typed::Trunk(trunk) => (trunk, None),
+            _ => unreachable!(),
+        });
+
+        // The freed slots are still free, so it can't fail
+        self.put(removed.pn, base, trunks).expect("restore");
+    }
+
+    /// Writes the tile to the empty column, adding the states to the storages.
+    ///
+    /// Returns `None` and changes nothing if a storage is full.
+    fn put<I>(&mut self, pn: Point, base: Base, trunks: I) -> Option<()>
+    where
+        I: IntoIterator<Item = (Trunk, Option<State>)>,
+    {
+        let cl = pn.cluster_point();
+        let height = base.height();
+        let len = self.map.column_mut::<Slab>(pn, height).0.len();
+        let mut added = Vec::new();
+        let mut slabs = Vec::with_capacity(height.get() as usize - 1);
+        for (i, (mut trunk, state)) in (1..).zip(trunks) {
+            if let Some(state) = state {
+                let cl = if i < len { cl } else { cl.to(Side::Up) };
+                let storage = &mut self.map.chunk_mut(cl).storage;
+                match storage.add(state) {
+                    Some(idx) => {
+                        trunk.set_data(idx);
+                        added.push((cl, idx));
+                    }
+                    None => {
+                        for (cl, idx) in added {
+                            self.map.chunk_mut(cl).storage.remove(idx);
+                        }
+
+                        return None;
+                    }
+                }
+            }
+
+            slabs.push(Slab::from(trunk));
+        }
+
+        self.mark_dirty(pn, height);
+        let mut column = self.map.column_mut(pn, height);
+        *column.get_mut(0) = base.into();
+        for (slab, trunk) in column.iter_mut().skip(1).zip(slabs) {
+            *slab = trunk;
+        }
+
+        self.update_column(pn, height);
+        self.retile(pn, height);
+        Some(())
+    }
+
+    /// Picks again the variants of the tiles around the column.
+    fn retile(&mut self, pn: Point, height: Height) {
+        let tile_set = Rc::clone(&self.tile_set);
+        let bases: HashSet<_> = auto::neighbours(pn, height)
+            .into_iter()
+            .filter_map(|pn| self.base(pn))
+            .map(|(pn, _, _)| pn)
+            .collect();
+
+        for pn in bases {
+            let base = match self.map.get::<Slab>(pn).unwrap().typed() {
+                Typed::Base(base) => base,
+                _ => unreachable!(),
+            };
+
+            let tile = tile_set.get(base.tile()).unwrap();
+            let variant = match auto::pick(tile.variants(), pn, base.height(), self) {
+                Some(variant) if variant != base.variant() => variant,
+                _ => continue,
+            };
+
+            *self.map.get_mut::<Slab>(pn) = Base::new(base.tile(), variant, base.height()).into();
+            self.dirty.insert(pn.cluster_point());
+            self.update_column(pn, base.height());
+        }
+    }
+
+    fn mark_dirty(&mut self, pn: Point, height: Height) {
+        let cl = pn.cluster_point();
+        self.dirty.insert(cl);
+        if pn.chunk_point().to(Side::Up, height.get() - 1).is_err() {
+            self.dirty.insert(cl.to(Side::Up));
+        }
+    }
+}
+
+impl path::Space for Cluster {
+    fn get(&self, pn: Point) -> Pass {
+        self.map.get(pn).copied().unwrap_or_else(Pass::empty)
+    }
+
+    fn column(&self, pn: Point, height: Height) -> Column<'_, Pass> {
+        const HEIGHT: usize = Height::HEIGHT as usize;
+        const EMPTY: [Pass; HEIGHT] = [Pass::empty(); HEIGHT];
+
+        if let Some(column) = self.map.column(pn, height) {
+            return column;
+        }
+
+        // Some chunk of the column isn't loaded, so its part is empty
+        let ch = pn.chunk_point();
+        let cl = pn.cluster_point();
+        let lh = height.get().min(Height::HEIGHT - ch.y());
+        let hh = height.get() - lh;
+        let lo = match self.map.chunk(cl) {
+            Some(chunk) => chunk.passes.slice(ch, lh),
+            None => &EMPTY[..lh as usize],
+        };
+
+        let (x, _, z) = ch.axes();
+        let hi = match self.map.chunk(cl.to(Side::Up)) {
+            Some(chunk) if hh > 0 => chunk.passes.slice(ChunkPoint::new(x, 0, z).unwrap(), hh),
+            _ => &EMPTY[..hh as usize],
+        };
+
+        Column(lo, hi)
+    }
+}
+
+impl Passes for Cluster {
+    fn pass(&self, pn: Point) -> Option<Pass> {
+        let (_, base, level) = self.base(pn)?;
+        let tile = self.tile_set.get(base.tile())?;
+        let variant = tile.variants().get(base.variant().get() as usize)?;
+        variant.passes.get(level as usize).copied()
+    }
 }
 
 pub struct Tiles<'a> {
@@ -228,9 +660,10 @@ impl<'a> Iterator for Tiles<'a> {
         loop {
             let ch = self.points.next()?;
             let gl = Point::new(ch, self.cl);
-            match self.cluster.get(gl) {
-                Some((slice, level)) => {
-                    debug_assert_eq!(level, 0);
+            match self.cluster.slice(gl) {
+                // The tile is placed in the chunk below
+                Some((_, level)) if level != 0 => continue,
+                Some((slice, _)) => {
                     for _ in 0..slice.column.0.len() - 1 {
                         self.points.next();
                     }
@@ -246,6 +679,7 @@ impl<'a> Iterator for Tiles<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::state::{Door, Schema};
 
     struct TestTile {
         data: &'static [Data],
@@ -256,19 +690,32 @@ mod tests {
             4
         }
 
-        fn variants(&self) -> &[&'static str] {
-            unreachable!()
+        fn variants(&self) -> &[core::tile::Variant] {
+            &[]
         }
 
-        fn place(&self, _: &mut Cluster, _: Point) -> Placement {
+        fn schema(&self) -> Option<Schema> {
+            Some(Schema::Door)
+        }
+
+        fn place(&self, _: &mut Cluster, _: Point) -> Placement<'_> {
             Placement {
                 variant: VariantIndex(0),
-                data: &self.data,
+                data: self.data,
             }
         }
     }
 
+    fn door(open: bool) -> State {
+        State::Door(Door { open })
+    }
+
     fn cluster() -> (Cluster, TileIndex) {
+        let (tile_set, index) = tile_set();
+        (Cluster::new(tile_set), index)
+    }
+
+    fn tile_set() -> (Rc<TileSet>, TileIndex) {
         let mut tile_set = TileSet::new([]);
         let index = tile_set.add(
             "test",
@@ -276,28 +723,28 @@ mod tests {
                 data: Box::leak(Box::new([
                     Data::None,
                     Data::Num(Num::new(2).unwrap()),
-                    Data::Obj(Rc::new(2)),
+                    Data::State(door(true)),
                 ])),
             }),
         );
-        (Cluster::new(Rc::new(tile_set)), index)
+        (Rc::new(tile_set), index)
     }
 
     #[test]
     fn place() {
         let (mut cluster, index) = cluster();
         let point = Point::from_absolute(0, 0, 0).unwrap();
-        cluster.place(point, index);
+        cluster.place(point, index).unwrap();
 
-        let (slice, level) = cluster.get(point).unwrap();
+        let (slice, level) = cluster.get(point).unwrap().unwrap();
         assert_eq!(slice.column.len(), 4);
         assert_eq!(slice.index(), (index, VariantIndex(0)));
         assert_eq!(level, 0);
 
         let point = Point::from_absolute(0, 31, 0).unwrap();
-        cluster.place(point, index);
+        cluster.place(point, index).unwrap();
 
-        let (slice, level) = cluster.get(point).unwrap();
+        let (slice, level) = cluster.get(point).unwrap().unwrap();
         assert_eq!(slice.column.len(), 4);
         assert_eq!(slice.index(), (index, VariantIndex(0)));
         assert_eq!(level, 0);
@@ -306,10 +753,15 @@ mod tests {
     #[test]
     fn get() {
         let (mut cluster, index) = cluster();
-        cluster.place(Point::from_absolute(0, 0, 0).unwrap(), index);
+        cluster
+            .place(Point::from_absolute(0, 0, 0).unwrap(), index)
+            .unwrap();
 
         for i in 0..4 {
-            let (slice, level) = cluster.get(Point::from_absolute(0, i, 0).unwrap()).unwrap();
+            let (slice, level) = cluster
+                .get(Point::from_absolute(0, i, 0).unwrap())
+                .unwrap()
+                .unwrap();
             assert_eq!(slice.column.len(), 4);
             assert_eq!(slice.index(), (index, VariantIndex(0)));
             assert_eq!(level, i as u8);
@@ -317,6 +769,7 @@ mod tests {
 
         assert!(cluster
             .get(Point::from_absolute(1, 0, 0).unwrap())
+            .unwrap()
             .is_none());
     }
 
@@ -324,13 +777,202 @@ mod tests {
     fn data() {
         let (mut cluster, index) = cluster();
         let point = Point::from_absolute(0, 0, 0).unwrap();
-        cluster.place(point, index);
+        cluster.place(point, index).unwrap();
 
-        let (slice, _) = cluster.get(point).unwrap();
+        let (slice, _) = cluster.get(point).unwrap().unwrap();
         assert_eq!(slice.column.len(), 4);
         assert!(matches!(slice.data(0), Data::None));
         assert_eq!(slice.data(1).as_num().get(), 0);
         assert_eq!(slice.data(2).as_num().get(), 2);
-        assert_eq!(slice.data(3).as_obj().downcast_ref::<i32>(), Some(&2));
+        assert_eq!(slice.data(3).as_state(), door(true));
+
+        *cluster
+            .state_mut(Point::from_absolute(0, 3, 0).unwrap())
+            .unwrap()
+            .unwrap() = door(false);
+        let (slice, _) = cluster.get(point).unwrap().unwrap();
+        assert_eq!(slice.data(3).as_state(), door(false));
+        assert!(cluster.state_mut(point).unwrap().is_none());
+    }
+
+    #[test]
+    fn collect() {
+        let (mut cluster, index) = cluster();
+        let points = [(0, 0, 0), (1, 0, 0), (2, 0, 0)].map(|pn| Point::try_from(pn).unwrap());
+        for pn in points {
+            cluster.place(pn, index).unwrap().unwrap();
+        }
+
+        let cl = points[0].cluster_point();
+        assert_eq!(cluster.map.chunk(cl).unwrap().storage.len(), 3);
+
+        // Remove the tile in the middle by hand
+        let mut column = cluster
+            .map
+            .column_mut::<Slab>(points[1], Height::new(4).unwrap());
+        for slab in column.iter_mut() {
+            *slab = Empty.into();
+        }
+
+        cluster.save().unwrap();
+        let chunk = cluster.map.chunk(cl).unwrap();
+        assert_eq!(chunk.storage.len(), 2);
+        assert_eq!(chunk.states().count(), 2);
+        let (slice, _) = cluster.get(points[2]).unwrap().unwrap();
+        assert_eq!(slice.data(3).as_state(), door(true));
+    }
+
+    #[test]
+    fn remove() {
+        let (mut cluster, index) = cluster();
+
+        // The column crosses the chunk boundary, remove it by the upper trunk
+        let point = Point::from_absolute(0, 30, 0).unwrap();
+        cluster.place(point, index).unwrap().unwrap();
+        let upper = point.cluster_point().to(Side::Up);
+        assert_eq!(cluster.map.chunk(upper).unwrap().storage.len(), 1);
+
+        let removed = cluster
+            .remove(Point::from_absolute(0, 33, 0).unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(removed.pn, point);
+        assert_eq!(removed.tile, index);
+        assert_eq!(removed.height.get(), 4);
+        assert_eq!(removed.states, [door(true)]);
+        assert!(cluster.is_empty(point, removed.height));
+        assert!(cluster.map.chunk(upper).unwrap().storage.is_empty());
+        assert!(cluster.dirty.contains(&upper));
+        for y in 30..34 {
+            assert!(cluster
+                .remove(Point::from_absolute(0, y, 0).unwrap())
+                .unwrap()
+                .is_none());
+        }
+
+        // The freed slot is reused
+        cluster.place(point, index).unwrap().unwrap();
+        assert_eq!(cluster.map.chunk(upper).unwrap().storage.slots().len(), 1);
+        let (slice, _) = cluster.get(point).unwrap().unwrap();
+        assert_eq!(slice.data(3).as_state(), door(true));
+    }
+
+    #[test]
+    fn replace() {
+        let (tile_set, index) = tile_set();
+        let mut tile_set = Rc::try_unwrap(tile_set).ok().unwrap();
+        let cube = tile_set.add("cube", Box::new(crate::tiles::Base::new(2, Vec::new())));
+        let mut cluster = Cluster::new(Rc::new(tile_set));
+
+        // The new tile is lower and stays in the lower chunk
+        let point = Point::from_absolute(0, 30, 0).unwrap();
+        cluster.place(point, index).unwrap().unwrap();
+        let (removed, placed) = cluster
+            .replace(Point::from_absolute(0, 32, 0).unwrap(), cube)
+            .unwrap()
+            .unwrap();
+        assert_eq!(removed.tile, index);
+        assert_eq!(placed.height.get(), 2);
+        let (slice, _) = cluster.get(point).unwrap().unwrap();
+        assert_eq!(slice.index(), (cube, VariantIndex(0)));
+        assert!(cluster
+            .get(Point::from_absolute(0, 32, 0).unwrap())
+            .unwrap()
+            .is_none());
+
+        // The new tile is higher and crosses the boundary
+        let (removed, _) = cluster.replace(point, index).unwrap().unwrap();
+        assert_eq!(removed.tile, cube);
+        let (slice, level) = cluster
+            .get(Point::from_absolute(0, 33, 0).unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!((slice.index().0, level), (index, 3));
+        assert_eq!(slice.data(3).as_state(), door(true));
+
+        // Doesn't fit, the old tile stays
+        let point = Point::from_absolute(1, 30, 0).unwrap();
+        cluster.place(point, cube).unwrap().unwrap();
+        cluster
+            .place(Point::from_absolute(1, 33, 0).unwrap(), cube)
+            .unwrap()
+            .unwrap();
+        assert!(cluster.replace(point, index).unwrap().is_none());
+        let (slice, _) = cluster.get(point).unwrap().unwrap();
+        assert_eq!(slice.index(), (cube, VariantIndex(0)));
+        assert!(cluster
+            .replace(Point::from_absolute(2, 30, 0).unwrap(), index)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn save() {
+        let dir = std::env::temp_dir().join(format!("arkipelago_cluster_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (tile_set, index) = tile_set();
+        let mut cluster = Cluster::with_regions(Rc::clone(&tile_set), Regions::open(&dir).unwrap());
+
+        // The column crosses the cluster boundary
+        let point = Point::from_absolute(0, 30, 0).unwrap();
+        cluster.place(point, index).unwrap().unwrap();
+        cluster.save().unwrap();
+        assert!(cluster.dirty.is_empty());
+
+        let mut cluster = Cluster::with_regions(tile_set, Regions::open(&dir).unwrap());
+        let (slice, level) = cluster.get(point.to(Side::Up)).unwrap().unwrap();
+        assert_eq!(level, 1);
+        assert_eq!(slice.index(), (index, VariantIndex(0)));
+        assert_eq!(slice.data(2).as_num().get(), 2);
+        assert_eq!(slice.data(3).as_state(), door(true));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn save_retry() {
+        let dir =
+            std::env::temp_dir().join(format!("arkipelago_cluster_retry_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (tile_set, index) = tile_set();
+        let mut cluster = Cluster::with_regions(Rc::clone(&tile_set), Regions::open(&dir).unwrap());
+        let point = Point::from_absolute(0, 30, 0).unwrap();
+        cluster.place(point, index).unwrap().unwrap();
+
+        // The region can't be written while the temporary file is blocked
+        let tmp = dir.join("0.0.0.tmp");
+        std::fs::create_dir(&tmp).unwrap();
+        assert!(cluster.save().is_err());
+
+        // The edits aren't lost, the next save writes them
+        std::fs::remove_dir(&tmp).unwrap();
+        cluster.save().unwrap();
+
+        let mut cluster = Cluster::with_regions(tile_set, Regions::open(&dir).unwrap());
+        let (slice, _) = cluster.get(point).unwrap().unwrap();
+        assert_eq!(slice.index(), (index, VariantIndex(0)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn load_broken() {
+        let dir =
+            std::env::temp_dir().join(format!("arkipelago_cluster_broken_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (tile_set, index) = tile_set();
+        let mut cluster = Cluster::with_regions(Rc::clone(&tile_set), Regions::open(&dir).unwrap());
+        let point = Point::from_absolute(0, 0, 0).unwrap();
+        cluster.place(point, index).unwrap().unwrap();
+        cluster.save().unwrap();
+
+        let region = dir.join("0.0.0.region");
+        std::fs::write(&region, b"broken").unwrap();
+        let mut cluster = Cluster::with_regions(tile_set, Regions::open(&dir).unwrap());
+        assert!(cluster.get(point).is_err());
+        assert!(cluster.place(point.to(Side::Right), index).is_err());
+
+        // The broken region isn't overwritten
+        cluster.save().unwrap();
+        assert_eq!(std::fs::read(&region).unwrap(), b"broken");
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
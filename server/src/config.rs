@@ -1,4 +1,9 @@
+use crate::{account, session::Settings, world};
 use serde::Deserialize;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use tokio::net::ToSocketAddrs;
 
 const PATH: &str = "Server.toml";
@@ -9,9 +14,33 @@ struct Tcp {
     port: u16,
 }
 
+#[derive(Deserialize)]
+struct Session {
+    idle_timeout: u64,
+}
+
+#[derive(Deserialize)]
+struct Accounts {
+    path: String,
+    max_failures: u32,
+    lockout: u64,
+}
+
+#[derive(Deserialize)]
+struct World {
+    path: String,
+    save_interval: u64,
+    view_distance: u8,
+    view_height: u8,
+    action_time: u64,
+}
+
 #[derive(Deserialize)]
 pub struct Config {
     tcp: Tcp,
+    session: Session,
+    accounts: Accounts,
+    world: World,
 }
 
 impl Config {
@@ -23,4 +52,41 @@ impl Config {
     pub fn socket_addr(&self) -> impl ToSocketAddrs + '_ {
         (self.tcp.host.as_str(), self.tcp.port)
     }
+
+    pub fn session_settings(&self) -> Settings {
+        Settings {
+            idle_timeout: Duration::from_secs(self.session.idle_timeout),
+        }
+    }
+
+    /// Returns the accounts path relative to the config file.
+    pub fn accounts_path(&self) -> PathBuf {
+        dir().join(&self.accounts.path)
+    }
+
+    pub fn account_settings(&self) -> account::Settings {
+        account::Settings {
+            max_failures: self.accounts.max_failures,
+            lockout: Duration::from_secs(self.accounts.lockout),
+        }
+    }
+
+    /// Returns the world directory relative to the config file.
+    pub fn world_path(&self) -> PathBuf {
+        dir().join(&self.world.path)
+    }
+
+    pub fn world_settings(&self) -> world::Settings {
+        world::Settings {
+            view_distance: self.world.view_distance,
+            view_height: self.world.view_height,
+            save_interval: Duration::from_secs(self.world.save_interval),
+            action_time: Duration::from_millis(self.world.action_time),
+        }
+    }
+}
+
+/// Returns the directory of the config file.
+fn dir() -> &'static Path {
+    Path::new(PATH).parent().unwrap_or_else(|| Path::new(""))
 }
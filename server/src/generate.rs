@@ -0,0 +1,137 @@
+use crate::{
+    cluster::Cluster,
+    region::{self, Regions},
+    tile::TileSet,
+};
+use core::{point::Point, tile::TileList};
+use std::rc::Rc;
+
+/// Half size of the ground.
+const GROUND: i64 = 32;
+
+/// The demo structures placed on the ground.
+const STRUCTURES: &[(&str, (i64, i64, i64))] = &[
+    ("dirt", (0, -2, 0)),
+    ("dirt", (0, 0, 0)),
+    ("grass", (0, 1, 0)),
+    ("dirt", (1, 0, 0)),
+    ("grass", (1, 1, 0)),
+    ("dirt", (2, 0, 0)),
+    ("dirt", (4, 0, 0)),
+    ("dirt", (4, 0, 1)),
+    ("dirt", (4, 0, 2)),
+    ("stone", (0, 0, 1)),
+    ("rocks", (0, 2, 1)),
+    ("stone", (1, 0, 1)),
+    ("stone", (0, 0, 2)),
+    ("stone", (1, 0, 2)),
+    ("stone", (2, 0, 2)),
+    ("stone", (3, 0, 2)),
+    ("stone", (3, 1, 1)),
+    ("stone", (3, 2, 0)),
+    ("stone", (4, 3, 0)),
+    ("dirt", (5, 4, 0)),
+    ("dirt", (6, 4, 0)),
+    ("ladder", (6, 1, 3)),
+    ("dirt", (5, 4, 2)),
+    ("dirt", (6, 4, 2)),
+    ("dirt", (5, 5, 2)),
+    ("dirt", (5, 6, 2)),
+    ("dirt", (5, 7, 2)),
+    ("dirt", (0, 0, 3)),
+    ("dirt", (1, 0, 3)),
+    ("dirt", (0, 0, 4)),
+    ("dirt", (1, 0, 4)),
+    ("grass", (0, 1, 3)),
+    ("grass", (0, 1, 4)),
+    ("rocks", (0, 1, 5)),
+    ("dirt", (0, 0, 5)),
+    ("dirt", (1, 0, 5)),
+    ("dirt", (2, 0, 5)),
+    ("dirt", (1, 1, 5)),
+    ("dirt", (1, 3, 5)),
+    ("dirt", (2, 1, 5)),
+    ("dirt", (2, 2, 5)),
+    ("bricks", (1, 2, 6)),
+    ("bricks", (1, 4, 6)),
+    ("grass", (2, 3, 5)),
+    ("dirt", (0, 0, 6)),
+    ("dirt", (2, 0, 6)),
+    ("bricks", (2, 0, 3)),
+    ("bricks", (3, 0, 3)),
+    ("bricks", (4, 0, 3)),
+    ("bricks", (4, 1, 4)),
+    ("bricks", (5, 2, 4)),
+    ("bricks", (5, 0, 4)),
+    ("bricks", (6, 0, 4)),
+    ("box", (3, 0, 4)),
+    ("box", (3, 2, 4)),
+    ("box", (3, 1, 5)),
+    ("box", (3, 3, 5)),
+    ("box", (3, 0, 6)),
+    ("box", (3, 4, 6)),
+    ("grass", (5, 1, 0)),
+    ("grass", (5, 1, 2)),
+    ("dirt", (5, 0, 0)),
+    ("dirt", (5, 0, 1)),
+    ("dirt", (5, 0, 2)),
+    ("dirt", (5, 0, 3)),
+    ("dirt", (6, 0, 0)),
+    ("dirt", (6, 0, 1)),
+    ("dirt", (6, 0, 2)),
+    ("dirt", (6, 0, 3)),
+    ("stone", (5, 0, 5)),
+    ("stone", (5, 0, 6)),
+    ("stone", (6, 0, 5)),
+    ("stone", (6, 0, 6)),
+    ("stone", (6, 2, 5)),
+    ("stone", (6, 2, 6)),
+    ("bricks", (7, 0, 7)),
+    ("bricks", (7, 0, 6)),
+    ("bricks", (5, 1, 7)),
+    ("bricks", (5, 3, 7)),
+    ("bricks", (7, 0, 7)),
+    ("bricks", (9, 0, 5)),
+    ("bricks", (9, 2, 5)),
+    ("bricks", (9, 4, 5)),
+    ("bricks", (10, 0, 4)),
+    ("bricks", (10, 2, 4)),
+    ("bricks", (10, 4, 4)),
+    ("bricks", (10, 0, 5)),
+    ("bricks", (9, 0, 4)),
+    ("bricks", (9, 2, 4)),
+    ("bricks", (9, 0, 2)),
+    ("bricks", (9, 2, 2)),
+    ("bricks", (9, 0, 0)),
+    ("bricks", (9, 2, 0)),
+    ("bricks", (11, 0, 2)),
+    ("bricks", (11, 2, 2)),
+    ("bricks", (11, 4, 2)),
+];
+
+/// Loads the world from the regions, or generates it if there is nothing saved.
+pub fn generate(regions: Regions) -> Result<Cluster, region::Error> {
+    let tiles = TileList::new();
+    let tile_set = Rc::new(TileSet::new(tiles.iter()));
+    let is_new = regions.is_empty()?;
+    let mut cluster = Cluster::with_regions(Rc::clone(&tile_set), regions);
+    if !is_new {
+        return Ok(cluster);
+    }
+
+    let dirt = tile_set.get_index("dirt").expect("dirt tile");
+    for x in -GROUND..GROUND {
+        for z in -GROUND..GROUND {
+            let pn = Point::from_absolute(x, -3, z).unwrap();
+            cluster.place(pn, dirt)?;
+        }
+    }
+
+    for &(name, (x, y, z)) in STRUCTURES {
+        let pn = Point::from_absolute(x, y, z).unwrap();
+        let tile = tile_set.get_index(name).expect("tile");
+        cluster.place(pn, tile)?;
+    }
+
+    Ok(cluster)
+}
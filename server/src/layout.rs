@@ -1,6 +1,5 @@
-use crate::slab::*;
+use crate::{slab::*, state::State};
 use core::prelude::*;
-use std::{any::Any, rc::Rc};
 
 #[derive(Copy, Clone)]
 pub struct Num(u16);
@@ -19,10 +18,11 @@ impl Num {
     }
 }
 
+#[derive(Clone)]
 pub enum Data {
     None,
     Num(Num),
-    Obj(Rc<dyn Any>),
+    State(State),
 }
 
 impl Data {
@@ -35,10 +35,10 @@ impl Data {
     }
 
     #[allow(clippy::wrong_self_convention)]
-    pub fn as_obj(self) -> Rc<dyn Any> {
+    pub fn as_state(self) -> State {
         match self {
-            Data::Obj(obj) => obj,
-            _ => panic!("expected obj"),
+            Data::State(state) => state,
+            _ => panic!("expected state"),
         }
     }
 }
@@ -59,13 +59,13 @@ impl<'a> Layout<'a> {
         Base::new(self.tile, self.variant, self.height())
     }
 
-    pub fn trunks(self) -> impl Iterator<Item = (Trunk, Option<Rc<dyn Any>>)> + 'a {
+    pub fn trunks(self) -> impl Iterator<Item = (Trunk, Option<&'a State>)> + 'a {
         self.data.iter().enumerate().map(move |(i, data)| {
             let level = i as u8 + 1;
             match data {
                 Data::None => (Trunk::new(self.tile, 0, false, level), None),
                 Data::Num(num) => (Trunk::new(self.tile, num.get(), false, level), None),
-                Data::Obj(obj) => (Trunk::new(self.tile, 0, true, level), Some(Rc::clone(obj))),
+                Data::State(state) => (Trunk::new(self.tile, 0, true, level), Some(state)),
             }
         })
     }
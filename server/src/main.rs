@@ -1,52 +1,39 @@
+mod account;
 #[allow(dead_code)]
 mod cluster;
 mod config;
+mod generate;
 #[allow(dead_code)]
 mod layout;
+mod movement;
+mod region;
+mod session;
 mod slab;
+mod state;
 #[allow(dead_code)]
 mod tile;
 mod tiles;
+mod world;
 
-use self::{config::Config, tile::TileSet};
-use core::{
-    net::{Login, Unpacked},
-    tile::TileList,
+use self::{
+    account::Accounts,
+    config::Config,
+    region::Regions,
+    session::{Context, Registry},
 };
-use std::io;
-use tokio::{
-    io::{AsyncReadExt, BufReader},
-    net::{TcpListener, TcpStream},
-};
-
-#[derive(Debug)]
-enum Error {
-    IO(io::Error),
-    Pack(core::net::Error),
-}
-
-impl From<io::Error> for Error {
-    fn from(err: io::Error) -> Self {
-        Self::IO(err)
-    }
-}
+use tokio::{net::TcpListener, task};
 
-impl From<core::net::Error> for Error {
-    fn from(err: core::net::Error) -> Self {
-        Self::Pack(err)
-    }
+// Not `#[tokio::main]`, its expansion refers to `::core::future`,
+// which resolves to the `core` crate of the game
+fn main() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("build runtime")
+        .block_on(run())
 }
 
-async fn process(stream: &mut TcpStream) -> Result<Login, Error> {
-    let mut reader = BufReader::with_capacity(1024, stream);
-    let len = reader.read_u32().await?;
-    let mut unpacked = Unpacked::new(len)?;
-    reader.read_exact(unpacked.bytes()).await?;
-    Ok(unpacked.to()?)
-}
-
-#[tokio::main]
-async fn main() {
+async fn run() {
     let config = Config::load();
     let listener = TcpListener::bind(config.socket_addr())
         .await
@@ -55,28 +42,39 @@ async fn main() {
     let addr = listener.local_addr().unwrap();
     println!("The server is listening on {addr}");
 
-    let tiles = TileList::new();
-    let _ = TileSet::new(tiles.iter());
+    let accounts =
+        Accounts::load(config.accounts_path(), config.account_settings()).expect("load accounts");
+
+    let regions = Regions::open(config.world_path()).expect("open world");
+    let registry = Registry::new();
+    let (world, thread) = world::spawn(registry.clone(), config.world_settings(), || {
+        generate::generate(regions)
+    });
+
+    let context = Context {
+        registry,
+        accounts,
+        world: world.clone(),
+    };
 
-    loop {
-        let mut stream = match listener.accept().await {
-            Ok((stream, addr)) => {
-                println!("Connection accepted: {addr}");
-                stream
-            }
-            Err(err) => {
-                println!("Connection failed: {err}");
-                continue;
-            }
-        };
+    // The world thread stops by itself when the world can't be read
+    let mut stopped = task::spawn_blocking(|| thread.join());
+    let joined = tokio::select! {
+        _ = session::serve(listener, context, config.session_settings()) => None,
+        _ = tokio::signal::ctrl_c() => {
+            println!("Shutdown ..");
+            None
+        }
+        joined = &mut stopped => Some(joined),
+    };
 
-        tokio::spawn(async move {
-            let Login { name, pass } = match process(&mut stream).await {
-                Ok(login) => login,
-                Err(err) => panic!("Login failed: {:?}", err),
-            };
+    world.shutdown();
+    let joined = match joined {
+        Some(joined) => joined,
+        None => stopped.await,
+    };
 
-            println!("Try to login: {name} {pass}");
-        });
-    }
+    joined
+        .expect("join world task")
+        .expect("world thread panicked");
 }
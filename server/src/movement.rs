@@ -0,0 +1,114 @@
+use core::{
+    path::{Action, Position, Space, Walk},
+    point::Point,
+};
+
+/// Checks the actions submitted by the clients.
+///
+/// The clients aren't trusted, so every action is replayed
+/// by the walker against the world of the server.
+pub struct Validator<W> {
+    walk: W,
+}
+
+impl<W> Validator<W> {
+    pub fn new(walk: W) -> Self {
+        Self { walk }
+    }
+
+    /// Runs the `action` from `pn` in the `space`.
+    ///
+    /// Returns the point the action leads to with its cost
+    /// or `None` if the action is illegal.
+    pub fn check<S>(&self, space: &S, pn: Point, action: Action) -> Option<(Point, u32)>
+    where
+        S: Space,
+        W: Walk<S>,
+    {
+        let pos = Position {
+            pn,
+            value: u32::MAX,
+            heading: None,
+        };
+
+        self.walk
+            .run(space, pos, action)
+            .map(|next| (next.pn, pos.value - next.value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cluster::Cluster, tile::TileSet, tiles};
+    use core::{
+        path::{player, Pass},
+        prelude::*,
+        tile::Variant,
+    };
+    use std::rc::Rc;
+
+    fn cluster() -> (Cluster, TileIndex) {
+        let variant = Variant {
+            idx: VariantIndex(0),
+            name: "dirt".into(),
+            rotation: Rotation::Q0,
+            passes: vec![Pass::solid()],
+            auto: None,
+        };
+
+        let mut tile_set = TileSet::new([]);
+        let index = tile_set.add("dirt", Box::new(tiles::Base::new(1, vec![variant])));
+        let mut cluster = Cluster::new(Rc::new(tile_set));
+        for x in 0..8 {
+            for z in 0..8 {
+                let pn = Point::from_absolute(x, 0, z).unwrap();
+                cluster.place(pn, index).unwrap().unwrap();
+            }
+        }
+
+        (cluster, index)
+    }
+
+    #[test]
+    fn check() {
+        let validator = Validator::new(player());
+        let (mut cluster, index) = cluster();
+        let pn = Point::from_absolute(2, 1, 2).unwrap();
+        let step = Action::Step(Rotation::Q0);
+        let ahead = pn.to(Rotation::Q0);
+        assert_eq!(validator.check(&cluster, pn, step), Some((ahead, 1)));
+        assert_eq!(validator.check(&cluster, pn, Action::Stay), Some((pn, 0)));
+
+        // Can't fly without wings
+        assert_eq!(validator.check(&cluster, pn, Action::Fly(Side::Up)), None);
+
+        // Steps up on the single block
+        cluster.place(ahead, index).unwrap().unwrap();
+        let up = ahead.to(Side::Up);
+        assert_eq!(validator.check(&cluster, pn, step), Some((up, 1)));
+
+        // Can't walk through the wall
+        cluster.place(up, index).unwrap().unwrap();
+        assert_eq!(validator.check(&cluster, pn, step), None);
+
+        // But can jump on the higher one
+        let top = up.to(Side::Up);
+        cluster.place(top, index).unwrap().unwrap();
+        let jump = Action::JumpUp(Rotation::Q0);
+        assert_eq!(
+            validator.check(&cluster, pn, jump),
+            Some((top.to(Side::Up), 3))
+        );
+
+        // The wall is too high to jump
+        cluster.place(top.to(Side::Up), index).unwrap().unwrap();
+        assert_eq!(validator.check(&cluster, pn, jump), None);
+
+        // The removed tiles free the way
+        cluster.remove(top.to(Side::Up)).unwrap().unwrap();
+        cluster.remove(top).unwrap().unwrap();
+        cluster.remove(up).unwrap().unwrap();
+        assert_eq!(validator.check(&cluster, pn, step), Some((up, 1)));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::state::State;
+use core::{chunk, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    error, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Clusters in a region on the horizontal axes.
+const SIDE: i32 = 8;
+/// Clusters in a region on the vertical axis.
+const HEIGHT: i32 = 4;
+
+/// The region file version.
+///
+/// Must be increased on every incompatible change of the region file.
+const VERSION: u8 = 2;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Bincode(bincode::Error),
+    Chunk(chunk::Error),
+    Version(u8),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error {err}"),
+            Self::Bincode(err) => write!(f, "bincode error {err}"),
+            Self::Chunk(err) => write!(f, "chunk error {err}"),
+            Self::Version(version) => write!(f, "unsupported region version {version}"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<bincode::Error> for Error {
+    fn from(err: bincode::Error) -> Self {
+        Self::Bincode(err)
+    }
+}
+
+impl From<chunk::Error> for Error {
+    fn from(err: chunk::Error) -> Self {
+        Self::Chunk(err)
+    }
+}
+
+/// A chunk as it's stored on disk.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct SavedChunk {
+    /// Slabs in the `core::chunk` format.
+    pub slabs: Vec<u8>,
+    /// States referenced by the trunks, in the storage order.
+    pub states: Vec<Option<State>>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Region {
+    version: u8,
+    chunks: HashMap<(i32, i32, i32), SavedChunk>,
+}
+
+#[derive(Copy, Clone, Eq, Hash, PartialEq)]
+struct RegionPoint(i32, i32, i32);
+
+impl RegionPoint {
+    fn new(cl: ClusterPoint) -> Self {
+        Self(
+            cl.x().div_euclid(SIDE),
+            cl.y().div_euclid(HEIGHT),
+            cl.z().div_euclid(SIDE),
+        )
+    }
+}
+
+/// Region files in the world directory.
+///
+/// A region groups nearby clusters in one file.
+/// Loaded regions are kept in memory.
+pub struct Regions {
+    dir: PathBuf,
+    regions: HashMap<RegionPoint, Region>,
+    dirty: HashSet<RegionPoint>,
+}
+
+impl Regions {
+    /// Opens the world directory, creating it if needed.
+    pub fn open<P>(dir: P) -> Result<Self, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            regions: HashMap::default(),
+            dirty: HashSet::default(),
+        })
+    }
+
+    /// Checks if there is no region saved.
+    pub fn is_empty(&self) -> Result<bool, Error> {
+        for entry in fs::read_dir(&self.dir)? {
+            if entry?.path().extension().is_some_and(|ext| ext == "region") {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    pub fn get(&mut self, cl: ClusterPoint) -> Result<Option<&SavedChunk>, Error> {
+        let region = self.region(RegionPoint::new(cl))?;
+        Ok(region.chunks.get(&cl.into()))
+    }
+
+    /// Puts the chunk to the region, it's written on the next flush.
+    pub fn put(&mut self, cl: ClusterPoint, chunk: SavedChunk) -> Result<(), Error> {
+        let rn = RegionPoint::new(cl);
+        let region = self.region(rn)?;
+        region.chunks.insert(cl.into(), chunk);
+        self.dirty.insert(rn);
+        Ok(())
+    }
+
+    /// Writes the changed regions to disk.
+    ///
+    /// The regions that failed to be written stay changed.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        let dirty: Vec<_> = self.dirty.iter().copied().collect();
+        for rn in dirty {
+            let path = path(&self.dir, rn);
+            let tmp = path.with_extension("tmp");
+            let content = bincode::serialize(&self.regions[&rn])?;
+            fs::write(&tmp, content)?;
+            fs::rename(&tmp, &path)?;
+            self.dirty.remove(&rn);
+        }
+
+        Ok(())
+    }
+
+    fn region(&mut self, rn: RegionPoint) -> Result<&mut Region, Error> {
+        if !self.regions.contains_key(&rn) {
+            let region = match fs::read(path(&self.dir, rn)) {
+                Ok(content) => bincode::deserialize(&content)?,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Region {
+                    version: VERSION,
+                    chunks: HashMap::default(),
+                },
+                Err(err) => return Err(err.into()),
+            };
+
+            if region.version != VERSION {
+                return Err(Error::Version(region.version));
+            }
+
+            self.regions.insert(rn, region);
+        }
+
+        Ok(self.regions.get_mut(&rn).unwrap())
+    }
+}
+
+fn path(dir: &Path, RegionPoint(x, y, z): RegionPoint) -> PathBuf {
+    dir.join(format!("{x}.{y}.{z}.region"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Door;
+
+    #[test]
+    fn region_point() {
+        let rn = |x, y, z| RegionPoint::new(ClusterPoint::new(x, y, z).unwrap());
+        assert!(rn(0, 0, 0) == rn(7, 3, 7));
+        assert!(rn(0, 0, 0) != rn(-1, 0, 0));
+        assert!(rn(-8, -4, -8) == rn(-1, -1, -1));
+    }
+
+    #[test]
+    fn put() {
+        let dir = std::env::temp_dir().join(format!("arkipelago_regions_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut regions = Regions::open(&dir).unwrap();
+        assert!(regions.is_empty().unwrap());
+
+        let cl = ClusterPoint::new(-3, 1, 9).unwrap();
+        let chunk = SavedChunk {
+            slabs: vec![1, 2, 3],
+            states: vec![None, Some(State::Door(Door { open: true }))],
+        };
+        regions.put(cl, chunk).unwrap();
+        regions.flush().unwrap();
+        assert!(!regions.is_empty().unwrap());
+
+        let mut regions = Regions::open(&dir).unwrap();
+        let saved = regions.get(cl).unwrap().unwrap();
+        assert_eq!(saved.slabs, [1, 2, 3]);
+        assert_eq!(saved.states[1], Some(State::Door(Door { open: true })));
+        assert!(regions.get(cl.to(Side::Up)).unwrap().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
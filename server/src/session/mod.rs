@@ -0,0 +1,7 @@
+mod registry;
+mod this;
+
+pub use self::{
+    registry::{Registry, SessionId},
+    this::{serve, Context, Settings},
+};
@@ -0,0 +1,125 @@
+use core::net::ServerMessage;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::mpsc::Sender;
+
+#[derive(Copy, Clone, Eq, Hash, PartialEq)]
+pub struct SessionId(u32);
+
+impl fmt::Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "session: {}", self.0)
+    }
+}
+
+impl fmt::Debug for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+pub struct Player {
+    pub name: String,
+    sender: Sender<ServerMessage>,
+}
+
+impl Player {
+    /// Puts the message in the write queue of the player.
+    ///
+    /// Returns `false` if the queue is full or the session is closed.
+    pub fn send(&self, message: ServerMessage) -> bool {
+        self.sender.try_send(message).is_ok()
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    players: HashMap<SessionId, Player>,
+    next_id: u32,
+}
+
+/// The registry of connected players.
+///
+/// Cheap to clone, all clones share the same players.
+#[derive(Clone, Default)]
+pub struct Registry(Arc<Mutex<Inner>>);
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn next_id(&self) -> SessionId {
+        let mut inner = self.0.lock().unwrap();
+        let id = inner.next_id;
+        inner.next_id = id.wrapping_add(1);
+        SessionId(id)
+    }
+
+    /// Registers the player.
+    ///
+    /// Returns `false` if a player with the same name is already online.
+    pub(crate) fn insert(
+        &self,
+        id: SessionId,
+        name: String,
+        sender: Sender<ServerMessage>,
+    ) -> bool {
+        let mut inner = self.0.lock().unwrap();
+        if inner.players.values().any(|player| player.name == name) {
+            return false;
+        }
+
+        let old = inner.players.insert(id, Player { name, sender });
+        assert!(old.is_none());
+        true
+    }
+
+    pub(crate) fn remove(&self, id: SessionId) -> Option<Player> {
+        let mut inner = self.0.lock().unwrap();
+        inner.players.remove(&id)
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        let inner = self.0.lock().unwrap();
+        inner.players.len()
+    }
+
+    pub fn is_online(&self, name: &str) -> bool {
+        let inner = self.0.lock().unwrap();
+        inner.players.values().any(|player| player.name == name)
+    }
+
+    #[cfg(test)]
+    pub fn names(&self) -> Vec<String> {
+        let inner = self.0.lock().unwrap();
+        inner
+            .players
+            .values()
+            .map(|player| player.name.clone())
+            .collect()
+    }
+
+    /// Sends the message to the player with the `id`.
+    ///
+    /// Returns `false` if there is no such player or its write queue is full.
+    pub fn send(&self, id: SessionId, message: ServerMessage) -> bool {
+        let inner = self.0.lock().unwrap();
+        match inner.players.get(&id) {
+            Some(player) => player.send(message),
+            None => false,
+        }
+    }
+
+    /// Sends the message to every connected player.
+    pub fn broadcast(&self, message: &ServerMessage) {
+        let inner = self.0.lock().unwrap();
+        for player in inner.players.values() {
+            player.send(message.clone());
+        }
+    }
+}
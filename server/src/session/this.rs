@@ -0,0 +1,579 @@
+use crate::{
+    account::Accounts,
+    session::{Registry, SessionId},
+    world::WorldHandle,
+};
+use core::{
+    net::{self, ClientMessage, Disconnect, Login, LoginResult, ServerMessage, PROTOCOL_VERSION},
+    point::Point,
+};
+use std::{fmt, io, time::Duration};
+use tokio::{
+    io::{AsyncWriteExt, BufReader},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener, TcpStream,
+    },
+    sync::mpsc::{self, error::TrySendError, Receiver, Sender},
+    task, time,
+};
+
+const QUEUE_LEN: usize = 256;
+
+/// Where the players appear after the login.
+const SPAWN: (i64, i64, i64) = (0, 0, 0);
+
+#[derive(Copy, Clone)]
+pub struct Settings {
+    pub idle_timeout: Duration,
+}
+
+/// The server state shared between sessions.
+#[derive(Clone)]
+pub struct Context {
+    pub registry: Registry,
+    pub accounts: Accounts,
+    pub world: WorldHandle,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Pack(net::Error),
+    Handshake,
+    Version(u16),
+    Timeout,
+    Overflow,
+}
+
+impl Error {
+    fn reason(&self) -> Option<Disconnect> {
+        match self {
+            Self::Io(_) | Self::Overflow => None,
+            Self::Pack(net::Error::Io(_)) => None,
+            Self::Pack(_) | Self::Handshake => Some(Disconnect::ProtocolError),
+            Self::Version(_) => Some(Disconnect::VersionMismatch {
+                server: PROTOCOL_VERSION,
+            }),
+            Self::Timeout => Some(Disconnect::Timeout),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error {err}"),
+            Self::Pack(err) => write!(f, "pack error {err}"),
+            Self::Handshake => write!(f, "handshake failed"),
+            Self::Version(version) => write!(f, "wrong protocol version {version}"),
+            Self::Timeout => write!(f, "idle timeout"),
+            Self::Overflow => write!(f, "write queue overflow"),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<net::Error> for Error {
+    fn from(err: net::Error) -> Self {
+        Self::Pack(err)
+    }
+}
+
+/// Accepts connections and runs a session for each of them.
+pub async fn serve(listener: TcpListener, context: Context, settings: Settings) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, addr)) => {
+                println!("Connection accepted: {addr}");
+                stream
+            }
+            Err(err) => {
+                println!("Connection failed: {err}");
+                continue;
+            }
+        };
+
+        let context = context.clone();
+        tokio::spawn(async move {
+            if let Err(err) = Session::run(stream, context, settings).await {
+                println!("Session closed: {err}");
+            }
+        });
+    }
+}
+
+/// The connection of one client.
+///
+/// Messages are read in the session loop, and written
+/// from the queue by a separate task.
+pub struct Session {
+    id: SessionId,
+    reader: BufReader<OwnedReadHalf>,
+    sender: Sender<ServerMessage>,
+    context: Context,
+    settings: Settings,
+    name: Option<String>,
+}
+
+impl Session {
+    pub async fn run(stream: TcpStream, context: Context, settings: Settings) -> Result<(), Error> {
+        let (reader, writer) = stream.into_split();
+        let (sender, receiver) = mpsc::channel(QUEUE_LEN);
+        let writer = tokio::spawn(write_queue(writer, receiver));
+
+        let mut session = Self {
+            id: context.registry.next_id(),
+            reader: BufReader::with_capacity(1024, reader),
+            sender,
+            context,
+            settings,
+            name: None,
+        };
+
+        let res = session.read_loop().await;
+        if let Some(reason) = res.as_ref().err().and_then(Error::reason) {
+            let _ = session.send(ServerMessage::Disconnect(reason));
+        }
+
+        session.close();
+        match writer.await {
+            Ok(Err(err)) => println!("Write failed: {err}"),
+            Err(err) => println!("Write task failed: {err}"),
+            Ok(Ok(())) => (),
+        }
+
+        res
+    }
+
+    fn send(&self, message: ServerMessage) -> Result<(), Error> {
+        match self.sender.try_send(message) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => Err(Error::Overflow),
+            Err(TrySendError::Closed(_)) => Err(Error::Io(io::ErrorKind::BrokenPipe.into())),
+        }
+    }
+
+    /// Reads the next message.
+    ///
+    /// Returns `None` if the client has closed the connection.
+    async fn read(&mut self) -> Result<Option<ClientMessage>, Error> {
+        let read = net::read_async(&mut self.reader);
+        match time::timeout(self.settings.idle_timeout, read).await {
+            Err(_) => Err(Error::Timeout),
+            Ok(Err(net::Error::Io(err))) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Ok(res) => Ok(Some(res?)),
+        }
+    }
+
+    async fn handshake(&mut self) -> Result<(), Error> {
+        match self.read().await? {
+            Some(ClientMessage::Handshake {
+                version: PROTOCOL_VERSION,
+            }) => self.send(ServerMessage::Handshake {
+                version: PROTOCOL_VERSION,
+            }),
+            Some(ClientMessage::Handshake { version }) => Err(Error::Version(version)),
+            _ => Err(Error::Handshake),
+        }
+    }
+
+    async fn read_loop(&mut self) -> Result<(), Error> {
+        self.handshake().await?;
+        while let Some(message) = self.read().await? {
+            if !self.handle(message).await? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handles the message.
+    ///
+    /// Returns `false` if the session should be closed.
+    async fn handle(&mut self, message: ClientMessage) -> Result<bool, Error> {
+        match (message, &self.name) {
+            (ClientMessage::Disconnect(reason), _) => {
+                println!("Disconnected {}: {reason:?}", self.id);
+                return Ok(false);
+            }
+            (ClientMessage::Login(login), None) => self.login(login, false).await?,
+            (ClientMessage::Register(login), None) => self.login(login, true).await?,
+            (ClientMessage::Login(_) | ClientMessage::Register(_), Some(_)) => {
+                self.send(ServerMessage::LoginResult(LoginResult::Rejected))?;
+            }
+            (ClientMessage::Act { action }, Some(_)) => self.context.world.act(self.id, action),
+            (ClientMessage::Chat(text), Some(name)) => {
+                self.context.registry.broadcast(&ServerMessage::Chat {
+                    from: name.clone(),
+                    text,
+                });
+            }
+            (message, _) => println!("Unhandled message in {}: {message:?}", self.id),
+        }
+
+        Ok(true)
+    }
+
+    async fn login(&mut self, login: Login, register: bool) -> Result<(), Error> {
+        let Login { name, pass } = login;
+        let result = if self.context.registry.is_online(&name) {
+            LoginResult::AlreadyOnline
+        } else {
+            let accounts = self.context.accounts.clone();
+            let key = name.clone();
+            let checked = task::spawn_blocking(move || match register {
+                false => accounts.login(&key, &pass),
+                true => accounts.register(&key, &pass),
+            });
+
+            match checked.await.expect("join account task") {
+                Ok(result) => result,
+                Err(err) => {
+                    println!("Account error: {err}");
+                    LoginResult::Rejected
+                }
+            }
+        };
+
+        let result = match result {
+            LoginResult::Accepted => {
+                let registry = &self.context.registry;
+                if registry.insert(self.id, name.clone(), self.sender.clone()) {
+                    println!("Login {name} in {}", self.id);
+                    self.name = Some(name);
+                    LoginResult::Accepted
+                } else {
+                    LoginResult::AlreadyOnline
+                }
+            }
+            result => {
+                println!("Login {name} failed in {}: {result:?}", self.id);
+                result
+            }
+        };
+
+        self.send(ServerMessage::LoginResult(result))?;
+        if result == LoginResult::Accepted {
+            // Stream the world after the result
+            let spawn = Point::try_from(SPAWN).unwrap();
+            self.send(ServerMessage::Spawned { pn: spawn })?;
+            self.context.world.join(self.id, spawn);
+        }
+
+        Ok(())
+    }
+
+    fn close(self) {
+        if let Some(name) = &self.name {
+            println!("Logout {name} in {}", self.id);
+            self.context.world.leave(self.id);
+        }
+
+        self.context.registry.remove(self.id);
+    }
+}
+
+async fn write_queue(
+    mut writer: OwnedWriteHalf,
+    mut receiver: Receiver<ServerMessage>,
+) -> Result<(), net::Error> {
+    while let Some(message) = receiver.recv().await {
+        net::write_async(&mut writer, &message).await?;
+    }
+
+    writer.shutdown().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        account::Settings as AccountSettings,
+        cluster::Cluster,
+        tile::TileSet,
+        world::{self, Settings as WorldSettings},
+    };
+    use core::{
+        chunk::{self, Chunk},
+        net::TileSlab,
+        path::Action,
+        prelude::Rotation,
+    };
+    use std::{fs, future::Future, rc::Rc};
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    async fn start(name: &str, idle_timeout: Duration) -> (Registry, String) {
+        let path = std::env::temp_dir().join(format!(
+            "arkipelago_session_{name}_{}.json",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        let accounts = Accounts::load(
+            path,
+            AccountSettings {
+                max_failures: 3,
+                lockout: Duration::from_secs(60),
+            },
+        )
+        .unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let registry = Registry::new();
+        let settings = WorldSettings {
+            view_distance: 0,
+            view_height: 0,
+            save_interval: Duration::from_secs(60),
+            action_time: Duration::ZERO,
+        };
+        let (world, _) = world::spawn(registry.clone(), settings, || {
+            Ok(Cluster::new(Rc::new(TileSet::new([]))))
+        });
+        let context = Context {
+            registry: registry.clone(),
+            accounts,
+            world,
+        };
+        let settings = Settings { idle_timeout };
+        tokio::spawn(serve(listener, context, settings));
+        (registry, addr)
+    }
+
+    async fn connect(addr: &str) -> TcpStream {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let handshake = ClientMessage::Handshake {
+            version: PROTOCOL_VERSION,
+        };
+        net::write_async(&mut stream, &handshake).await.unwrap();
+        let message: ServerMessage = net::read_async(&mut stream).await.unwrap();
+        assert_eq!(
+            message,
+            ServerMessage::Handshake {
+                version: PROTOCOL_VERSION
+            }
+        );
+        stream
+    }
+
+    /// Reads the next message skipping the streamed world.
+    async fn read(stream: &mut TcpStream) -> ServerMessage {
+        loop {
+            match net::read_async(&mut *stream).await.unwrap() {
+                ServerMessage::Chunk { .. }
+                | ServerMessage::Unload { .. }
+                | ServerMessage::Spawned { .. } => continue,
+                message => break message,
+            }
+        }
+    }
+
+    async fn send_login(stream: &mut TcpStream, message: ClientMessage) -> LoginResult {
+        net::write_async(&mut *stream, &message).await.unwrap();
+        match read(stream).await {
+            ServerMessage::LoginResult(result) => result,
+            message => panic!("unexpected message {message:?}"),
+        }
+    }
+
+    async fn login(stream: &mut TcpStream, name: &str, pass: &str) -> LoginResult {
+        let login = Login {
+            name: name.into(),
+            pass: pass.into(),
+        };
+        send_login(stream, ClientMessage::Login(login)).await
+    }
+
+    async fn register(stream: &mut TcpStream, name: &str) -> LoginResult {
+        let login = Login {
+            name: name.into(),
+            pass: "secret".into(),
+        };
+        send_login(stream, ClientMessage::Register(login)).await
+    }
+
+    async fn wait_len(registry: &Registry, len: usize) {
+        while registry.len() != len {
+            time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+
+    #[test]
+    fn chat() {
+        block_on(async {
+            let (registry, addr) = start("chat", Duration::from_secs(10)).await;
+            let mut a = connect(&addr).await;
+            let mut b = connect(&addr).await;
+            assert_eq!(register(&mut a, "a").await, LoginResult::Accepted);
+            assert_eq!(register(&mut b, "b").await, LoginResult::Accepted);
+            assert_eq!(registry.len(), 2);
+
+            net::write_async(&mut a, &ClientMessage::Chat("hi".into()))
+                .await
+                .unwrap();
+
+            let expected = ServerMessage::Chat {
+                from: "a".into(),
+                text: "hi".into(),
+            };
+
+            for stream in [&mut a, &mut b] {
+                assert_eq!(read(stream).await, expected);
+            }
+
+            net::write_async(&mut a, &ClientMessage::Disconnect(Disconnect::Quit))
+                .await
+                .unwrap();
+
+            wait_len(&registry, 1).await;
+            assert_eq!(registry.names(), ["b"]);
+
+            drop(b);
+            wait_len(&registry, 0).await;
+        })
+    }
+
+    #[test]
+    fn login_twice() {
+        block_on(async {
+            let (registry, addr) = start("login_twice", Duration::from_secs(10)).await;
+            let mut a = connect(&addr).await;
+            let mut b = connect(&addr).await;
+            assert_eq!(register(&mut a, "a").await, LoginResult::Accepted);
+            assert_eq!(register(&mut b, "a").await, LoginResult::AlreadyOnline);
+            assert_eq!(register(&mut a, "c").await, LoginResult::Rejected);
+            assert_eq!(registry.names(), ["a"]);
+        })
+    }
+
+    #[test]
+    fn wrong_password() {
+        block_on(async {
+            let (registry, addr) = start("wrong_password", Duration::from_secs(10)).await;
+            let mut stream = connect(&addr).await;
+            assert_eq!(register(&mut stream, "a").await, LoginResult::Accepted);
+            drop(stream);
+            wait_len(&registry, 0).await;
+
+            let mut stream = connect(&addr).await;
+            assert_eq!(
+                login(&mut stream, "a", "wrong").await,
+                LoginResult::Rejected
+            );
+            assert_eq!(
+                login(&mut stream, "b", "secret").await,
+                LoginResult::Rejected
+            );
+            assert_eq!(
+                login(&mut stream, "a", "secret").await,
+                LoginResult::Accepted
+            );
+            assert_eq!(registry.names(), ["a"]);
+        })
+    }
+
+    #[test]
+    fn stream_world() {
+        block_on(async {
+            let (_, addr) = start("stream_world", Duration::from_secs(10)).await;
+            let mut stream = connect(&addr).await;
+            let message = ClientMessage::Register(Login {
+                name: "a".into(),
+                pass: "secret".into(),
+            });
+            net::write_async(&mut stream, &message).await.unwrap();
+            let message: ServerMessage = net::read_async(&mut stream).await.unwrap();
+            assert_eq!(message, ServerMessage::LoginResult(LoginResult::Accepted));
+
+            let spawn = Point::try_from(SPAWN).unwrap();
+            let message: ServerMessage = net::read_async(&mut stream).await.unwrap();
+            assert_eq!(message, ServerMessage::Spawned { pn: spawn });
+            let message: ServerMessage = net::read_async(&mut stream).await.unwrap();
+            assert_eq!(
+                message,
+                ServerMessage::Chunk {
+                    cl: spawn.cluster_point(),
+                    slabs: chunk::encode(&Chunk::filled(TileSlab::Empty)),
+                }
+            );
+
+            // There is no ground to step on in the empty world
+            let message = ClientMessage::Act {
+                action: Action::Step(Rotation::Q1),
+            };
+            net::write_async(&mut stream, &message).await.unwrap();
+            loop {
+                let message: ServerMessage = net::read_async(&mut stream).await.unwrap();
+                match message {
+                    ServerMessage::Chunk { .. } => continue,
+                    message => {
+                        assert_eq!(message, ServerMessage::Corrected { pn: spawn });
+                        break;
+                    }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn version_mismatch() {
+        block_on(async {
+            let (_, addr) = start("version_mismatch", Duration::from_secs(10)).await;
+            let mut stream = TcpStream::connect(&addr).await.unwrap();
+            let handshake = ClientMessage::Handshake { version: 0 };
+            net::write_async(&mut stream, &handshake).await.unwrap();
+            let message: ServerMessage = net::read_async(&mut stream).await.unwrap();
+            assert_eq!(
+                message,
+                ServerMessage::Disconnect(Disconnect::VersionMismatch {
+                    server: PROTOCOL_VERSION
+                })
+            );
+        })
+    }
+
+    #[test]
+    fn idle_timeout() {
+        block_on(async {
+            let (_, addr) = start("idle_timeout", Duration::from_millis(50)).await;
+            let mut stream = connect(&addr).await;
+            let message: ServerMessage = net::read_async(&mut stream).await.unwrap();
+            assert_eq!(message, ServerMessage::Disconnect(Disconnect::Timeout));
+            assert!(net::read_async::<_, ServerMessage>(&mut stream)
+                .await
+                .is_err());
+        })
+    }
+
+    #[test]
+    fn malformed_frame() {
+        block_on(async {
+            let (_, addr) = start("malformed_frame", Duration::from_secs(10)).await;
+            let mut stream = connect(&addr).await;
+            stream.write_all(&[0, 0, 0, 2, 0xFF, 0xFF]).await.unwrap();
+            let message: ServerMessage = net::read_async(&mut stream).await.unwrap();
+            assert_eq!(
+                message,
+                ServerMessage::Disconnect(Disconnect::ProtocolError)
+            );
+
+            // The server is still alive
+            let mut stream = connect(&addr).await;
+            assert_eq!(register(&mut stream, "a").await, LoginResult::Accepted);
+        })
+    }
+}
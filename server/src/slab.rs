@@ -1,4 +1,7 @@
-use core::prelude::{Height, TileIndex, VariantIndex};
+use core::{
+    chunk::Encode,
+    prelude::{Height, TileIndex, VariantIndex},
+};
 
 /// Slab layout.
 ///
@@ -9,13 +12,13 @@ use core::prelude::{Height, TileIndex, VariantIndex};
 ///     t: tile
 ///     h: height (actual height - 1)
 ///     v: variant
-/// Trunk -> tttttttt_tttttttt_lllodddd_dddddddd
+/// Trunk -> tttttttt_tttttttt_lllsdddd_dddddddd
 /// where
 ///     t: tile
-///     o: obj
+///     s: state, then data is the index in the storage
 ///     l: level (always > 0)
 ///     d: data
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub(crate) struct Slab(u16, u16);
 
 impl Slab {
@@ -32,6 +35,20 @@ impl Slab {
     }
 }
 
+impl Encode for Slab {
+    fn encode(self) -> u32 {
+        (self.0 as u32) << 16 | self.1 as u32
+    }
+
+    fn decode(raw: u32) -> Option<Self> {
+        match Self((raw >> 16) as u16, raw as u16) {
+            Self(0, 0) => Some(Self(0, 0)),
+            Self(0, _) => None,
+            slab => Some(slab),
+        }
+    }
+}
+
 impl From<Empty> for Slab {
     fn from(_: Empty) -> Self {
         Self(0, 0)
@@ -87,15 +104,19 @@ impl Base {
 pub(crate) struct Trunk(u16, u16);
 
 impl Trunk {
-    pub const fn new(tile: TileIndex, data: u16, obj: bool, level: u8) -> Self {
+    pub const fn new(tile: TileIndex, data: u16, state: bool, level: u8) -> Self {
         let mut b = data | (level as u16) << 13;
-        if obj {
+        if state {
             b |= 1 << 12;
         }
         Self(tile.get(), b)
     }
 
-    pub const fn is_obj(self) -> bool {
+    pub fn tile(self) -> TileIndex {
+        TileIndex::new(self.0).unwrap()
+    }
+
+    pub const fn is_state(self) -> bool {
         self.1 & 1 << 12 != 0
     }
 
@@ -108,6 +129,40 @@ impl Trunk {
     }
 
     pub fn set_data(&mut self, data: u16) {
-        self.1 |= data;
+        self.1 = self.1 & !0b111111111111 | data & 0b111111111111;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::{chunk, point::ChunkPoints, prelude::*};
+
+    #[test]
+    fn encode() {
+        let tile = TileIndex::new(300).unwrap();
+        let mut slabs = Chunk::filled(Slab::from(Empty));
+        for (i, ch) in ChunkPoints::new().enumerate() {
+            if ch.y() % 4 != 0 || ch.x() == 3 {
+                continue;
+            }
+
+            let height = Height::new(4).unwrap();
+            let base = Base::new(tile, VariantIndex(i as u8), height);
+            *slabs.get_mut(ch) = base.into();
+            for level in 1..height.get() {
+                let ch = ch.to(Side::Up, level).unwrap();
+                let state = level == 3;
+                *slabs.get_mut(ch) = Trunk::new(tile, i as u16 & 0xFFF, state, level).into();
+            }
+        }
+
+        let bytes = chunk::encode(&slabs);
+        let decoded: Chunk<Slab> = chunk::decode(&bytes).unwrap();
+        for ch in ChunkPoints::new() {
+            assert_eq!(decoded.get(ch), slabs.get(ch));
+        }
+
+        assert!(<Slab as chunk::Encode>::decode(1).is_none());
     }
 }
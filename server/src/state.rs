@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+
+/// The kind of the state a tile keeps in its trunks.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Schema {
+    Door,
+    Chest,
+}
+
+/// The state of a tile stored in its trunk.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum State {
+    Door(Door),
+    Chest(Chest),
+}
+
+impl State {
+    pub const fn schema(&self) -> Schema {
+        match self {
+            Self::Door(_) => Schema::Door,
+            Self::Chest(_) => Schema::Chest,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Door {
+    pub open: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Item {
+    pub name: String,
+    pub count: u16,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Chest {
+    pub items: Vec<Item>,
+}
+
+/// The states of a chunk.
+///
+/// A trunk refers to its state by the 12 bit index,
+/// so there can't be more than 4096 states in a chunk.
+#[derive(Default)]
+pub struct Storage {
+    slots: Vec<Option<State>>,
+    free: Vec<u16>,
+}
+
+impl Storage {
+    pub const MAX_LEN: usize = 1 << 12;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restores the storage from the saved slots.
+    pub fn from_slots(slots: Vec<Option<State>>) -> Self {
+        let free = slots
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, slot)| slot.is_none())
+            .map(|(idx, _)| idx as u16)
+            .collect();
+
+        Self { slots, free }
+    }
+
+    pub fn slots(&self) -> &[Option<State>] {
+        &self.slots
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, idx: u16) -> Option<&State> {
+        self.slots.get(idx as usize)?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, idx: u16) -> Option<&mut State> {
+        self.slots.get_mut(idx as usize)?.as_mut()
+    }
+
+    /// Adds the state and returns its index.
+    ///
+    /// Returns `None` if the storage is full.
+    pub fn add(&mut self, state: State) -> Option<u16> {
+        match self.free.pop() {
+            Some(idx) => {
+                self.slots[idx as usize] = Some(state);
+                Some(idx)
+            }
+            None if self.slots.len() < Self::MAX_LEN => {
+                self.slots.push(Some(state));
+                Some(self.slots.len() as u16 - 1)
+            }
+            None => None,
+        }
+    }
+
+    pub fn remove(&mut self, idx: u16) -> Option<State> {
+        let state = self.slots.get_mut(idx as usize)?.take()?;
+        self.free.push(idx);
+        Some(state)
+    }
+
+    /// Removes the states which aren't referenced anymore.
+    pub fn collect<I>(&mut self, referenced: I)
+    where
+        I: IntoIterator<Item = u16>,
+    {
+        let mut marked = vec![false; self.slots.len()];
+        for idx in referenced {
+            if let Some(mark) = marked.get_mut(idx as usize) {
+                *mark = true;
+            }
+        }
+
+        for (idx, mark) in marked.into_iter().enumerate() {
+            if !mark {
+                self.remove(idx as u16);
+            }
+        }
+
+        while let Some(None) = self.slots.last() {
+            self.slots.pop();
+        }
+
+        let len = self.slots.len() as u16;
+        self.free.retain(|&idx| idx < len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn door(open: bool) -> State {
+        State::Door(Door { open })
+    }
+
+    #[test]
+    fn storage() {
+        let mut storage = Storage::new();
+        assert_eq!(storage.add(door(false)), Some(0));
+        assert_eq!(storage.add(door(true)), Some(1));
+        assert_eq!(storage.add(State::Chest(Chest::default())), Some(2));
+        assert_eq!(storage.remove(1), Some(door(true)));
+        assert_eq!(storage.remove(1), None);
+        assert_eq!(storage.len(), 2);
+
+        // The free slot is reused
+        assert_eq!(storage.add(door(true)), Some(1));
+
+        storage.collect([0]);
+        assert_eq!(storage.len(), 1);
+        assert_eq!(storage.slots().len(), 1);
+        assert_eq!(storage.get(0), Some(&door(false)));
+        assert_eq!(storage.add(door(true)), Some(1));
+
+        let restored = Storage::from_slots(vec![None, Some(door(true)), None]);
+        assert_eq!(restored.len(), 1);
+        let mut restored = restored;
+        assert_eq!(restored.add(door(false)), Some(0));
+        assert_eq!(restored.add(door(false)), Some(2));
+        assert_eq!(restored.add(door(false)), Some(3));
+    }
+
+    #[test]
+    fn full() {
+        let mut storage = Storage::new();
+        for _ in 0..Storage::MAX_LEN {
+            assert!(storage.add(door(false)).is_some());
+        }
+
+        assert_eq!(storage.add(door(false)), None);
+    }
+}
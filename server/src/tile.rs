@@ -1,4 +1,4 @@
-use crate::{cluster::Cluster, layout::Data, tiles};
+use crate::{cluster::Cluster, layout::Data, state::Schema, tiles};
 use core::{point::Point, prelude::*, tile};
 use std::collections::HashMap;
 
@@ -10,9 +10,14 @@ pub struct Placement<'a> {
 pub trait Tile {
     fn height(&self) -> u8;
 
-    fn variants(&self) -> &[&'static str];
+    fn variants(&self) -> &[tile::Variant];
 
-    fn place(&self, cluster: &mut Cluster, pn: Point) -> Placement;
+    /// The schema of the states in the trunks.
+    fn schema(&self) -> Option<Schema> {
+        None
+    }
+
+    fn place(&self, cluster: &mut Cluster, pn: Point) -> Placement<'_>;
 }
 
 pub struct TileSet {
@@ -30,9 +35,13 @@ impl TileSet {
             vec: vec![Box::new(tiles::Empty)],
         };
 
+        let behaviours = tiles::Behaviours::new();
         for info in tiles {
-            let tile = Box::new(tiles::Base::new(2, vec!["cube"]));
-            tile_set.add(info.name.as_ref(), tile);
+            let tile = behaviours
+                .make(info)
+                .unwrap_or_else(|| panic!("unknown behaviour {} of {}", info.behaviour, info.name));
+            let idx = tile_set.add(info.name.as_ref(), tile);
+            assert_eq!(idx, info.idx);
         }
 
         tile_set
@@ -49,7 +58,7 @@ impl TileSet {
         self.map.get(key).copied()
     }
 
-    pub fn tiles(&self) -> Tiles {
+    pub fn tiles(&self) -> Tiles<'_> {
         Tiles {
             tile_set: self,
             idx: 1,
@@ -3,16 +3,21 @@ use crate::{
     layout::Data,
     tile::{Placement, Tile},
 };
-use core::{point::Point, prelude::VariantIndex};
+use core::{auto, point::Point, prelude::*, tile::Variant};
 
 pub struct Base {
     height: u8,
-    variants: Vec<&'static str>,
+    variants: Vec<Variant>,
+    data: Vec<Data>,
 }
 
 impl Base {
-    pub fn new(height: u8, variants: Vec<&'static str>) -> Self {
-        Self { height, variants }
+    pub fn new(height: u8, variants: Vec<Variant>) -> Self {
+        Self {
+            height,
+            variants,
+            data: vec![Data::None; height as usize - 1],
+        }
     }
 }
 
@@ -21,23 +26,16 @@ impl Tile for Base {
         self.height
     }
 
-    fn variants(&self) -> &[&'static str] {
+    fn variants(&self) -> &[Variant] {
         &self.variants
     }
 
-    fn place(&self, _: &mut Cluster, _: Point) -> Placement {
+    fn place(&self, cluster: &mut Cluster, pn: Point) -> Placement<'_> {
+        let height = Height::new(self.height).unwrap();
+        let variant = auto::pick(&self.variants, pn, height, cluster);
         Placement {
-            variant: VariantIndex(0),
-            data: &[
-                Data::None,
-                Data::None,
-                Data::None,
-                Data::None,
-                Data::None,
-                Data::None,
-                Data::None,
-                Data::None,
-            ][0..self.height as usize - 1],
+            variant: variant.unwrap_or(VariantIndex(0)),
+            data: &self.data,
         }
     }
 }
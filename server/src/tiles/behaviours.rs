@@ -0,0 +1,177 @@
+use crate::{
+    tile::Tile,
+    tiles::{Base, Door, GrassOnDirt, Ladder},
+};
+use core::tile;
+use std::collections::HashMap;
+
+type Make = fn(u8, Vec<tile::Variant>) -> Box<dyn Tile>;
+
+/// Named tile behaviours, selected by the `behaviour` of the tile in tiles.json.
+pub struct Behaviours {
+    map: HashMap<&'static str, Make>,
+}
+
+impl Behaviours {
+    pub fn new() -> Self {
+        let mut behaviours = Self {
+            map: HashMap::default(),
+        };
+
+        behaviours.add("base", |height, variants| {
+            Box::new(Base::new(height, variants))
+        });
+        behaviours.add("ladder", |height, variants| {
+            Box::new(Ladder::new(height, variants))
+        });
+        behaviours.add("grass-on-dirt", |height, variants| {
+            Box::new(GrassOnDirt::new(height, variants))
+        });
+        behaviours.add("door", |height, variants| {
+            Box::new(Door::new(height, variants))
+        });
+        behaviours
+    }
+
+    pub fn add(&mut self, name: &'static str, make: Make) {
+        let old = self.map.insert(name, make);
+        assert!(old.is_none());
+    }
+
+    /// Makes the server tile from the shared tile info.
+    ///
+    /// Returns `None` if the behaviour is unknown.
+    pub fn make(&self, info: &tile::Tile) -> Option<Box<dyn Tile>> {
+        let make = self.map.get(info.behaviour.as_ref())?;
+        Some(make(info.height.get(), info.variants.clone()))
+    }
+}
+
+impl Default for Behaviours {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cluster::Cluster, layout::Data, tile::TileSet};
+    use core::{auto::Rule, path::Pass, point::Point, prelude::*, tile::Variant};
+    use std::rc::Rc;
+
+    fn info(idx: u16, name: &str, height: u8, behaviour: &str, variants: u8) -> tile::Tile {
+        tile::Tile {
+            idx: TileIndex::new(idx).unwrap(),
+            name: name.into(),
+            height: Height::new(height).unwrap(),
+            behaviour: behaviour.into(),
+            variants: (0..variants)
+                .map(|i| Variant {
+                    idx: VariantIndex(i),
+                    name: name.into(),
+                    rotation: Rotation::from_quarters(i % 4).unwrap(),
+                    passes: vec![Pass::empty(); height as usize],
+                    auto: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn cluster() -> Cluster {
+        let infos = [
+            info(1, "dirt", 1, "base", 5),
+            info(2, "grass", 2, "grass-on-dirt", 2),
+            info(3, "ladder", 4, "ladder", 4),
+            info(4, "door", 2, "door", 1),
+        ];
+        Cluster::new(Rc::new(TileSet::new(&infos)))
+    }
+
+    fn point(x: i64, y: i64, z: i64) -> Point {
+        Point::from_absolute(x, y, z).unwrap()
+    }
+
+    #[test]
+    fn make() {
+        let cluster = cluster();
+        let tile_set = cluster.tile_set();
+        let ladder = tile_set.get("ladder").unwrap();
+        assert_eq!(ladder.height(), 4);
+        assert_eq!(ladder.variants().len(), 4);
+        assert_eq!(tile_set.get("dirt").unwrap().variants().len(), 5);
+        assert!(tile_set.get("door").unwrap().schema().is_some());
+
+        let unknown = info(1, "unknown", 1, "unknown", 1);
+        assert!(Behaviours::new().make(&unknown).is_none());
+    }
+
+    #[test]
+    fn place() {
+        let mut cluster = cluster();
+        let index = |cluster: &Cluster, name| cluster.tile_set().get_index(name).unwrap();
+        let (dirt, grass, ladder, door) = (
+            index(&cluster, "dirt"),
+            index(&cluster, "grass"),
+            index(&cluster, "ladder"),
+            index(&cluster, "door"),
+        );
+
+        cluster.place(point(0, 0, 0), dirt).unwrap().unwrap();
+        let placed = cluster.place(point(0, 1, 0), grass).unwrap().unwrap();
+        assert_eq!(placed.variant, VariantIndex(0));
+        let placed = cluster.place(point(1, 1, 0), grass).unwrap().unwrap();
+        assert_eq!(placed.variant, VariantIndex(1));
+
+        // Without a wall the first variant is used
+        let placed = cluster.place(point(5, 0, 5), ladder).unwrap().unwrap();
+        assert_eq!(placed.variant, VariantIndex(0));
+
+        // The wall is on the left, so the ladder is turned to it
+        cluster.place(point(3, 0, 0), dirt).unwrap().unwrap();
+        let placed = cluster.place(point(2, 0, 0), ladder).unwrap().unwrap();
+        let variants = cluster.tile_set().get(ladder).unwrap().variants();
+        let rotation = variants[placed.variant.get() as usize].rotation;
+        assert_eq!(Side::from(rotation.opposite()), Side::Left);
+
+        let pn = point(7, 0, 7);
+        cluster.place(pn, door).unwrap().unwrap();
+        let (slice, _) = cluster.get(pn).unwrap().unwrap();
+        assert!(matches!(slice.data(1), Data::State(_)));
+    }
+
+    #[test]
+    fn auto() {
+        let mut dirt = info(1, "dirt", 1, "base", 2);
+        dirt.variants[0].auto = Some(Rule::default());
+        dirt.variants[1].auto = Some(Rule {
+            solid: Side::Back.into(),
+            open: Side::Forth | Side::Up,
+        });
+        for variant in &mut dirt.variants {
+            variant.passes = vec![Pass::solid()];
+        }
+
+        let mut cluster = Cluster::new(Rc::new(TileSet::new([&dirt])));
+        let index = dirt.idx;
+        let variant = |cluster: &mut Cluster, pn| cluster.get(pn).unwrap().unwrap().0.index().1;
+
+        let (a, b) = (point(0, 0, 0), point(0, 0, -1));
+        let placed = cluster.place(a, index).unwrap().unwrap();
+        assert_eq!(placed.variant, VariantIndex(0));
+
+        // The neighbours are picked again after the placement
+        let placed = cluster.place(b, index).unwrap().unwrap();
+        assert_eq!(placed.variant, VariantIndex(0));
+        assert_eq!(variant(&mut cluster, a), VariantIndex(1));
+
+        cluster.place(a.to(Side::Up), index).unwrap().unwrap();
+        assert_eq!(variant(&mut cluster, a), VariantIndex(0));
+        cluster.remove(a.to(Side::Up)).unwrap().unwrap();
+        assert_eq!(variant(&mut cluster, a), VariantIndex(1));
+
+        // And after the removal
+        cluster.remove(b).unwrap().unwrap();
+        assert_eq!(variant(&mut cluster, a), VariantIndex(0));
+    }
+}
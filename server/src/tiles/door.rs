@@ -0,0 +1,50 @@
+use crate::{
+    cluster::Cluster,
+    layout::Data,
+    state::{self, Schema, State},
+    tile::{Placement, Tile},
+};
+use core::{point::Point, prelude::*, tile::Variant};
+
+/// The tile which can be opened.
+///
+/// Keeps its state in the first trunk, it's placed closed.
+pub struct Door {
+    height: u8,
+    variants: Vec<Variant>,
+    data: Vec<Data>,
+}
+
+impl Door {
+    pub fn new(height: u8, variants: Vec<Variant>) -> Self {
+        assert!(height > 1, "door must have a trunk");
+        let mut data = vec![Data::None; height as usize - 1];
+        data[0] = Data::State(State::Door(state::Door::default()));
+        Self {
+            height,
+            variants,
+            data,
+        }
+    }
+}
+
+impl Tile for Door {
+    fn height(&self) -> u8 {
+        self.height
+    }
+
+    fn variants(&self) -> &[Variant] {
+        &self.variants
+    }
+
+    fn schema(&self) -> Option<Schema> {
+        Some(Schema::Door)
+    }
+
+    fn place(&self, _: &mut Cluster, _: Point) -> Placement<'_> {
+        Placement {
+            variant: VariantIndex(0),
+            data: &self.data,
+        }
+    }
+}
@@ -2,7 +2,7 @@ use crate::{
     cluster::Cluster,
     tile::{Placement, Tile},
 };
-use core::point::Point;
+use core::{point::Point, tile::Variant};
 
 pub struct Empty;
 
@@ -11,11 +11,11 @@ impl Tile for Empty {
         unreachable!()
     }
 
-    fn variants(&self) -> &[&'static str] {
+    fn variants(&self) -> &[Variant] {
         unreachable!()
     }
 
-    fn place(&self, _: &mut Cluster, _: Point) -> Placement {
+    fn place(&self, _: &mut Cluster, _: Point) -> Placement<'_> {
         unreachable!()
     }
 }
@@ -0,0 +1,54 @@
+use crate::{
+    cluster::Cluster,
+    layout::Data,
+    tile::{Placement, Tile},
+};
+use core::{point::Point, prelude::*, tile::Variant};
+
+/// The grass which looks differently when grows on dirt.
+///
+/// The first variant is placed on dirt, the last one anywhere else.
+pub struct GrassOnDirt {
+    height: u8,
+    variants: Vec<Variant>,
+    data: Vec<Data>,
+}
+
+impl GrassOnDirt {
+    pub const DIRT: &'static str = "dirt";
+
+    pub fn new(height: u8, variants: Vec<Variant>) -> Self {
+        Self {
+            height,
+            variants,
+            data: vec![Data::None; height as usize - 1],
+        }
+    }
+}
+
+impl Tile for GrassOnDirt {
+    fn height(&self) -> u8 {
+        self.height
+    }
+
+    fn variants(&self) -> &[Variant] {
+        &self.variants
+    }
+
+    fn place(&self, cluster: &mut Cluster, pn: Point) -> Placement<'_> {
+        let dirt = cluster.tile_set().get_index(Self::DIRT);
+        let below = cluster
+            .slice(pn.to(Side::Down))
+            .map(|(slice, _)| slice.index().0);
+        let idx = if below.is_some() && below == dirt {
+            0
+        } else {
+            self.variants.len().saturating_sub(1)
+        };
+
+        Placement {
+            variant: VariantIndex(idx as u8),
+            data: &self.data,
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use crate::{
+    cluster::Cluster,
+    layout::Data,
+    tile::{Placement, Tile},
+};
+use core::{point::Point, prelude::*, tile::Variant};
+
+/// The tile attached to a wall.
+///
+/// Picks the variant which rotation faces the first occupied neighbour.
+pub struct Ladder {
+    height: u8,
+    variants: Vec<Variant>,
+    data: Vec<Data>,
+}
+
+impl Ladder {
+    pub fn new(height: u8, variants: Vec<Variant>) -> Self {
+        Self {
+            height,
+            variants,
+            data: vec![Data::None; height as usize - 1],
+        }
+    }
+}
+
+impl Tile for Ladder {
+    fn height(&self) -> u8 {
+        self.height
+    }
+
+    fn variants(&self) -> &[Variant] {
+        &self.variants
+    }
+
+    fn place(&self, cluster: &mut Cluster, pn: Point) -> Placement<'_> {
+        // The ladder of the first rotation is placed at the back of the tile
+        let idx = self.variants.iter().position(|variant| {
+            let side = Side::from(variant.rotation.opposite());
+            cluster.slice(pn.to(side)).is_some()
+        });
+
+        Placement {
+            variant: VariantIndex(idx.unwrap_or(0) as u8),
+            data: &self.data,
+        }
+    }
+}
@@ -1,4 +1,11 @@
 mod base;
+mod behaviours;
+mod door;
 mod empty;
+mod grass;
+mod ladder;
 
-pub use self::{base::Base, empty::Empty};
+pub use self::{
+    base::Base, behaviours::Behaviours, door::Door, empty::Empty, grass::GrassOnDirt,
+    ladder::Ladder,
+};
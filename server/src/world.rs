@@ -0,0 +1,651 @@
+use crate::{
+    cluster::Cluster,
+    movement::Validator,
+    region,
+    session::{Registry, SessionId},
+};
+use core::{
+    chunk,
+    net::{ServerMessage, TileSlab},
+    path::{self, Action, PlayerWalk},
+    prelude::*,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// How often the clusters that didn't fit into the full queues are sent again.
+const RETRY: Duration = Duration::from_millis(100);
+/// How early the actions can arrive, since the network delays them unevenly.
+const LAG: Duration = Duration::from_millis(200);
+
+#[derive(Copy, Clone)]
+pub struct Settings {
+    /// Clusters loaded around the player on the horizontal axes.
+    pub view_distance: u8,
+    /// Clusters loaded above and below the player.
+    pub view_height: u8,
+    /// How often the changed clusters are saved.
+    pub save_interval: Duration,
+    /// How long the action of the unit cost takes.
+    pub action_time: Duration,
+}
+
+enum Command {
+    Join { id: SessionId, pn: Point },
+    Act { id: SessionId, action: Action },
+    Leave { id: SessionId },
+    Shutdown,
+}
+
+/// The handle to the world thread.
+///
+/// Cheap to clone, all clones send commands to the same world.
+#[derive(Clone)]
+pub struct WorldHandle(Sender<Command>);
+
+impl WorldHandle {
+    /// Starts to stream the clusters around `pn` to the player.
+    pub fn join(&self, id: SessionId, pn: Point) {
+        let _ = self.0.send(Command::Join { id, pn });
+    }
+
+    /// Validates the `action` of the player and moves it.
+    pub fn act(&self, id: SessionId, action: Action) {
+        let _ = self.0.send(Command::Act { id, action });
+    }
+
+    pub fn leave(&self, id: SessionId) {
+        let _ = self.0.send(Command::Leave { id });
+    }
+
+    /// Saves the world and stops the thread.
+    pub fn shutdown(&self) {
+        let _ = self.0.send(Command::Shutdown);
+    }
+}
+
+/// Spawns the world thread.
+///
+/// The cluster isn't `Send`, so it's made by `make` in the thread.
+/// The thread stops on shutdown, when all handles are dropped
+/// or when the world can't be read.
+pub fn spawn<F>(registry: Registry, settings: Settings, make: F) -> (WorldHandle, JoinHandle<()>)
+where
+    F: FnOnce() -> Result<Cluster, region::Error> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    let thread = thread::Builder::new()
+        .name("world".into())
+        .spawn(move || match make() {
+            Ok(cluster) => World::new(cluster, registry, settings).run(receiver),
+            Err(err) => println!("Load failed: {err}"),
+        })
+        .expect("spawn world thread");
+
+    (WorldHandle(sender), thread)
+}
+
+struct Viewer {
+    /// The position of the player, only changed by the validated actions.
+    pn: Point,
+    center: ClusterPoint,
+    loaded: HashSet<ClusterPoint>,
+    /// Some clusters weren't sent or unloaded, since the queue was full.
+    pending: bool,
+    /// When the accepted actions of the player are done.
+    ready: Instant,
+}
+
+impl Viewer {
+    /// Schedules the action of the `cost` after the previous ones.
+    ///
+    /// Returns `false` if the previous actions aren't done yet,
+    /// the free ones are always accepted.
+    fn charge(&mut self, cost: u32, action_time: Duration) -> bool {
+        let now = Instant::now();
+        let start = self.ready.max(now);
+        if cost > 0 && start > now + LAG {
+            return false;
+        }
+
+        self.ready = start + action_time * cost;
+        true
+    }
+}
+
+pub struct World {
+    cluster: Cluster,
+    registry: Registry,
+    settings: Settings,
+    viewers: HashMap<SessionId, Viewer>,
+    validator: Validator<PlayerWalk>,
+}
+
+impl World {
+    pub fn new(cluster: Cluster, registry: Registry, settings: Settings) -> Self {
+        Self {
+            cluster,
+            registry,
+            settings,
+            viewers: HashMap::default(),
+            validator: Validator::new(path::player()),
+        }
+    }
+
+    fn run(mut self, receiver: Receiver<Command>) {
+        let mut saved = Instant::now();
+        loop {
+            let timeout = self.settings.save_interval.saturating_sub(saved.elapsed());
+            let result = match receiver.recv_timeout(timeout.min(RETRY)) {
+                Ok(Command::Shutdown) | Err(RecvTimeoutError::Disconnected) => break,
+                Ok(command) => self.handle(command),
+                Err(RecvTimeoutError::Timeout) => self.retry(),
+            };
+
+            // The broken chunks aren't loaded, so the rest is still saved
+            if let Err(err) = result {
+                println!("Load failed: {err}");
+                break;
+            }
+
+            if saved.elapsed() >= self.settings.save_interval {
+                self.save();
+                saved = Instant::now();
+            }
+        }
+
+        self.save();
+    }
+
+    fn save(&mut self) {
+        if let Err(err) = self.cluster.save() {
+            println!("Save failed: {err}");
+        }
+    }
+
+    fn handle(&mut self, command: Command) -> Result<(), region::Error> {
+        match command {
+            Command::Join { id, pn } => {
+                let viewer = Viewer {
+                    pn,
+                    center: pn.cluster_point(),
+                    loaded: HashSet::default(),
+                    pending: false,
+                    ready: Instant::now(),
+                };
+
+                self.viewers.insert(id, viewer);
+                self.update(id)?;
+            }
+            Command::Act { id, action } => {
+                let viewer = match self.viewers.get_mut(&id) {
+                    Some(viewer) => viewer,
+                    None => return Ok(()),
+                };
+
+                // The moves reach the neighbour clusters at most,
+                // the unloaded ones would look like the air
+                self.cluster.load_around(viewer.pn.cluster_point())?;
+                let checked = self.validator.check(&self.cluster, viewer.pn, action);
+                let pn = match checked {
+                    Some((pn, cost)) if viewer.charge(cost, self.settings.action_time) => pn,
+                    _ => {
+                        let pn = viewer.pn;
+                        self.registry.send(id, ServerMessage::Corrected { pn });
+                        return Ok(());
+                    }
+                };
+
+                viewer.pn = pn;
+                let cl = pn.cluster_point();
+                if viewer.center != cl || viewer.pending {
+                    viewer.center = cl;
+                    self.update(id)?;
+                }
+            }
+            Command::Leave { id } => {
+                self.viewers.remove(&id);
+            }
+            Command::Shutdown => {}
+        }
+
+        Ok(())
+    }
+
+    /// Updates the viewers with the pending clusters.
+    fn retry(&mut self) -> Result<(), region::Error> {
+        let pending: Vec<_> = self
+            .viewers
+            .iter()
+            .filter_map(|(&id, viewer)| viewer.pending.then_some(id))
+            .collect();
+
+        for id in pending {
+            self.update(id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends the clusters that came into the view of the player
+    /// and unloads the clusters that left it.
+    ///
+    /// When the queue is full, the rest is pending until the next retry.
+    fn update(&mut self, id: SessionId) -> Result<(), region::Error> {
+        let viewer = match self.viewers.get_mut(&id) {
+            Some(viewer) => viewer,
+            None => return Ok(()),
+        };
+
+        viewer.pending = true;
+        let visible = visible(viewer.center, self.settings);
+        let unloaded: Vec<_> = viewer.loaded.difference(&visible).copied().collect();
+        for cl in unloaded {
+            if !self.registry.send(id, ServerMessage::Unload { cl }) {
+                return Ok(());
+            }
+
+            viewer.loaded.remove(&cl);
+        }
+
+        // Send the nearest clusters first
+        let center = viewer.center;
+        let mut new: Vec<_> = visible.difference(&viewer.loaded).copied().collect();
+        new.sort_by_key(|&cl| distance(center, cl));
+        for cl in new {
+            let slabs = chunk_slabs(&mut self.cluster, cl)?;
+            if !self.registry.send(id, ServerMessage::Chunk { cl, slabs }) {
+                return Ok(());
+            }
+
+            viewer.loaded.insert(cl);
+        }
+
+        viewer.pending = false;
+        Ok(())
+    }
+}
+
+fn visible(center: ClusterPoint, settings: Settings) -> HashSet<ClusterPoint> {
+    let dist = settings.view_distance as i32;
+    let height = settings.view_height as i32;
+    let (x, y, z) = center.into();
+    let mut visible = HashSet::default();
+    for dx in -dist..=dist {
+        for dy in -height..=height {
+            for dz in -dist..=dist {
+                if let Ok(cl) = ClusterPoint::new(x + dx, y + dy, z + dz) {
+                    visible.insert(cl);
+                }
+            }
+        }
+    }
+
+    visible
+}
+
+fn distance(a: ClusterPoint, b: ClusterPoint) -> u32 {
+    a.x().abs_diff(b.x()) + a.y().abs_diff(b.y()) + a.z().abs_diff(b.z())
+}
+
+/// Encodes the tiles of the chunk, the unloaded one is empty.
+fn chunk_slabs(cluster: &mut Cluster, cl: ClusterPoint) -> Result<Vec<u8>, region::Error> {
+    let slabs = cluster
+        .tile_slabs(cl)?
+        .unwrap_or_else(|| Chunk::filled(TileSlab::Empty));
+    Ok(chunk::encode(&slabs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{region::Regions, tile::TileSet, tiles};
+    use core::{path::Pass, tile::Variant};
+    use std::rc::Rc;
+    use tokio::sync::mpsc::{self, Receiver};
+
+    /// Encodes the chunk with the slabs at the points.
+    fn slabs<const N: usize>(points: [(ChunkPoint, TileSlab); N]) -> Vec<u8> {
+        let mut slabs = Chunk::filled(TileSlab::Empty);
+        for (ch, slab) in points {
+            *slabs.get_mut(ch) = slab;
+        }
+
+        chunk::encode(&slabs)
+    }
+
+    fn world(settings: Settings) -> (World, TileIndex) {
+        let mut tile_set = TileSet::new([]);
+        let index = tile_set.add("test", Box::new(tiles::Base::new(2, Vec::new())));
+        let cluster = Cluster::new(Rc::new(tile_set));
+        (World::new(cluster, Registry::new(), settings), index)
+    }
+
+    fn join(world: &mut World, pn: Point) -> (SessionId, Receiver<ServerMessage>) {
+        join_with(world, pn, 64)
+    }
+
+    fn join_with(
+        world: &mut World,
+        pn: Point,
+        capacity: usize,
+    ) -> (SessionId, Receiver<ServerMessage>) {
+        let (sender, receiver) = mpsc::channel(capacity);
+        let id = world.registry.next_id();
+        assert!(world.registry.insert(id, "a".into(), sender));
+        world.handle(Command::Join { id, pn }).unwrap();
+        (id, receiver)
+    }
+
+    fn received(receiver: &mut Receiver<ServerMessage>) -> Vec<ServerMessage> {
+        let mut messages = Vec::new();
+        while let Ok(message) = receiver.try_recv() {
+            messages.push(message);
+        }
+        messages
+    }
+
+    #[test]
+    fn stream() {
+        let (mut world, index) = world(Settings {
+            view_distance: 1,
+            view_height: 0,
+            save_interval: Duration::from_secs(60),
+            action_time: Duration::ZERO,
+        });
+
+        let pn = Point::from_absolute(1, 31, 2).unwrap();
+        world.cluster.place(pn, index).unwrap().unwrap();
+
+        let origin = Point::from_absolute(0, 0, 0).unwrap();
+        let (id, mut receiver) = join(&mut world, origin);
+        let messages = received(&mut receiver);
+        assert_eq!(messages.len(), 9);
+        assert_eq!(
+            messages[0],
+            ServerMessage::Chunk {
+                cl: origin.cluster_point(),
+                slabs: slabs([(pn.chunk_point(), (index, VariantIndex(0)).into())]),
+            }
+        );
+
+        // The trunk of the tile reaches the upper cluster
+        let upper = origin.cluster_point().to(Side::Up);
+        teleport(&mut world, id, Point::new(origin.chunk_point(), upper));
+        let messages = received(&mut receiver);
+        assert_eq!(messages.len(), 18);
+        let trunk = ChunkPoint::new(1, 0, 2).unwrap();
+        assert!(messages.contains(&ServerMessage::Chunk {
+            cl: upper,
+            slabs: slabs([(trunk, TileSlab::Trunk(0))]),
+        }));
+        assert!(messages.contains(&ServerMessage::Unload {
+            cl: origin.cluster_point(),
+        }));
+
+        // Moving inside the cluster sends nothing
+        teleport(
+            &mut world,
+            id,
+            Point::new(ChunkPoint::new(3, 0, 3).unwrap(), upper),
+        );
+        assert!(received(&mut receiver).is_empty());
+
+        world.handle(Command::Leave { id }).unwrap();
+        assert!(world.viewers.is_empty());
+    }
+
+    /// Moves the viewer without the validation.
+    fn teleport(world: &mut World, id: SessionId, pn: Point) {
+        let viewer = world.viewers.get_mut(&id).unwrap();
+        viewer.pn = pn;
+        viewer.center = pn.cluster_point();
+        world.update(id).unwrap();
+    }
+
+    #[test]
+    fn forth() {
+        let (mut world, _) = world(Settings {
+            view_distance: 1,
+            view_height: 1,
+            save_interval: Duration::from_secs(60),
+            action_time: Duration::ZERO,
+        });
+
+        let origin = Point::from_absolute(0, 0, 0).unwrap();
+        let (id, mut receiver) = join(&mut world, origin);
+        assert_eq!(received(&mut receiver).len(), 27);
+
+        let cl = origin.cluster_point().to(Side::Forth);
+        teleport(&mut world, id, Point::new(origin.chunk_point(), cl));
+
+        let messages = received(&mut receiver);
+        let loaded = messages
+            .iter()
+            .filter(|message| matches!(message, ServerMessage::Chunk { .. }))
+            .count();
+        let unloaded = messages
+            .iter()
+            .filter(|message| matches!(message, ServerMessage::Unload { .. }))
+            .count();
+        assert_eq!((loaded, unloaded), (9, 9));
+        assert_eq!(world.viewers[&id].loaded, visible(cl, world.settings));
+    }
+
+    #[test]
+    fn full_queue() {
+        let (mut world, _) = world(Settings {
+            view_distance: 1,
+            view_height: 0,
+            save_interval: Duration::from_secs(60),
+            action_time: Duration::ZERO,
+        });
+
+        // Only 4 of 9 clusters fit into the queue
+        let origin = Point::from_absolute(0, 0, 0).unwrap();
+        let (id, mut receiver) = join_with(&mut world, origin, 4);
+        let mut sent = received(&mut receiver);
+        assert_eq!(sent.len(), 4);
+        assert!(world.viewers[&id].pending);
+
+        // The rest arrives with the retries, though the player doesn't move
+        world.retry().unwrap();
+        sent.extend(received(&mut receiver));
+        world.retry().unwrap();
+        sent.extend(received(&mut receiver));
+        assert!(!world.viewers[&id].pending);
+        let sent: HashSet<_> = sent
+            .into_iter()
+            .map(|message| match message {
+                ServerMessage::Chunk { cl, .. } => cl,
+                message => panic!("unexpected {message:?}"),
+            })
+            .collect();
+        assert_eq!(sent, visible(origin.cluster_point(), world.settings));
+
+        // The unloads wait for the queue too
+        let cl = origin.cluster_point().to(Side::Forth).to(Side::Forth);
+        teleport(&mut world, id, Point::new(origin.chunk_point(), cl));
+        assert_eq!(received(&mut receiver).len(), 4);
+        while world.viewers[&id].pending {
+            world.retry().unwrap();
+            received(&mut receiver);
+        }
+
+        assert_eq!(world.viewers[&id].loaded, visible(cl, world.settings));
+    }
+
+    #[test]
+    fn act() {
+        let (mut world, _) = world(Settings {
+            view_distance: 0,
+            view_height: 0,
+            save_interval: Duration::from_secs(60),
+            action_time: Duration::ZERO,
+        });
+
+        let variant = Variant {
+            idx: VariantIndex(0),
+            name: "dirt".into(),
+            rotation: Rotation::Q0,
+            passes: vec![Pass::solid()],
+            auto: None,
+        };
+
+        let mut tile_set = TileSet::new([]);
+        let index = tile_set.add("dirt", Box::new(tiles::Base::new(1, vec![variant])));
+        world.cluster = Cluster::new(Rc::new(tile_set));
+        for x in 0..4 {
+            let pn = Point::from_absolute(x, 0, 0).unwrap();
+            world.cluster.place(pn, index).unwrap().unwrap();
+        }
+
+        let pn = Point::from_absolute(0, 1, 0).unwrap();
+        let (id, mut receiver) = join(&mut world, pn);
+        received(&mut receiver);
+
+        let step = Action::Step(Rotation::Q1);
+        world.handle(Command::Act { id, action: step }).unwrap();
+        let pn = pn.to(Rotation::Q1);
+        assert_eq!(world.viewers[&id].pn, pn);
+        assert!(received(&mut receiver).is_empty());
+
+        // The wall rejects the step
+        let wall = pn.to(Rotation::Q1);
+        world.cluster.place(wall, index).unwrap().unwrap();
+        world
+            .cluster
+            .place(wall.to(Side::Up), index)
+            .unwrap()
+            .unwrap();
+        world.handle(Command::Act { id, action: step }).unwrap();
+        assert_eq!(world.viewers[&id].pn, pn);
+        assert_eq!(received(&mut receiver), [ServerMessage::Corrected { pn }]);
+    }
+
+    #[test]
+    fn act_rate() {
+        let (mut world, _) = world(Settings {
+            view_distance: 0,
+            view_height: 0,
+            save_interval: Duration::from_secs(60),
+            action_time: Duration::from_secs(1),
+        });
+
+        let variant = Variant {
+            idx: VariantIndex(0),
+            name: "dirt".into(),
+            rotation: Rotation::Q0,
+            passes: vec![Pass::solid()],
+            auto: None,
+        };
+
+        let mut tile_set = TileSet::new([]);
+        let index = tile_set.add("dirt", Box::new(tiles::Base::new(1, vec![variant])));
+        world.cluster = Cluster::new(Rc::new(tile_set));
+        for x in 0..4 {
+            let pn = Point::from_absolute(x, 0, 0).unwrap();
+            world.cluster.place(pn, index).unwrap().unwrap();
+        }
+
+        let pn = Point::from_absolute(0, 1, 0).unwrap();
+        let (id, mut receiver) = join(&mut world, pn);
+        received(&mut receiver);
+
+        // The second step comes before the first one is done
+        let step = Action::Step(Rotation::Q1);
+        world.handle(Command::Act { id, action: step }).unwrap();
+        let pn = pn.to(Rotation::Q1);
+        world.handle(Command::Act { id, action: step }).unwrap();
+        assert_eq!(world.viewers[&id].pn, pn);
+        assert_eq!(received(&mut receiver), [ServerMessage::Corrected { pn }]);
+
+        // Waiting is free
+        world
+            .handle(Command::Act {
+                id,
+                action: Action::Stay,
+            })
+            .unwrap();
+        assert!(received(&mut receiver).is_empty());
+
+        world.viewers.get_mut(&id).unwrap().ready = Instant::now();
+        world.handle(Command::Act { id, action: step }).unwrap();
+        assert_eq!(world.viewers[&id].pn, pn.to(Rotation::Q1));
+    }
+
+    #[test]
+    fn act_unloaded() {
+        let dir = std::env::temp_dir().join(format!("arkipelago_world_act_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (mut world, _) = world(Settings {
+            view_distance: 0,
+            view_height: 0,
+            save_interval: Duration::from_secs(60),
+            action_time: Duration::ZERO,
+        });
+
+        let variant = Variant {
+            idx: VariantIndex(0),
+            name: "dirt".into(),
+            rotation: Rotation::Q0,
+            passes: vec![Pass::solid()],
+            auto: None,
+        };
+
+        let mut tile_set = TileSet::new([]);
+        let index = tile_set.add("dirt", Box::new(tiles::Base::new(1, vec![variant])));
+        let tile_set = Rc::new(tile_set);
+
+        // The floor crosses the border of the clusters
+        let mut cluster = Cluster::with_regions(Rc::clone(&tile_set), Regions::open(&dir).unwrap());
+        for z in 14..18 {
+            let pn = Point::from_absolute(0, 0, z).unwrap();
+            cluster.place(pn, index).unwrap().unwrap();
+        }
+
+        cluster.save().unwrap();
+        world.cluster = Cluster::with_regions(tile_set, Regions::open(&dir).unwrap());
+
+        // Only the cluster of the player is streamed, the next one isn't loaded
+        let pn = Point::from_absolute(0, 1, 15).unwrap();
+        let (id, mut receiver) = join(&mut world, pn);
+        received(&mut receiver);
+
+        let step = Action::Step(Rotation::Q0);
+        world.handle(Command::Act { id, action: step }).unwrap();
+        assert_eq!(world.viewers[&id].pn, pn.to(Rotation::Q0));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn broken() {
+        let dir =
+            std::env::temp_dir().join(format!("arkipelago_world_broken_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("0.0.0.region"), b"broken").unwrap();
+
+        // The world stops by itself instead of panicking
+        let registry = Registry::new();
+        let settings = Settings {
+            view_distance: 0,
+            view_height: 0,
+            save_interval: Duration::from_secs(60),
+            action_time: Duration::ZERO,
+        };
+
+        let regions = Regions::open(&dir).unwrap();
+        let (world, thread) = spawn(registry.clone(), settings, || {
+            Ok(Cluster::with_regions(Rc::new(TileSet::new([])), regions))
+        });
+
+        world.join(registry.next_id(), Point::from_absolute(0, 0, 0).unwrap());
+        thread.join().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}